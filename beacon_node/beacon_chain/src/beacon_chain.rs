@@ -1,33 +1,52 @@
 use crate::checkpoint::CheckPoint;
 use crate::errors::{BeaconChainError as Error, BlockProductionError};
+use crate::eth1::{Eth1Backend, Eth1Chain};
+use crate::events::{BeaconChainEvent, EventBus, PooledOperation};
+use crate::persisted_beacon_chain::PersistedBeaconChain;
+use crate::proposer_cache::{EpochProposers, ProposerCache, PROPOSER_CACHE_SIZE};
+use crate::state_cache::{StateCache, StateCacheStats, DEFAULT_STATE_CACHE_SIZE};
+use crossbeam_channel::Receiver;
 use db::{
-    stores::{BeaconBlockStore, BeaconStateStore},
-    ClientDB, DBError,
+    stores::{BeaconBlockStore, BeaconStateStore, ChainMetadataStore},
+    ClientDB, DBBatch, DBError,
 };
-use fork_choice::{ForkChoice, ForkChoiceError};
-use log::{debug, trace};
+use fork_choice::{BlockTree, ForkChoice, ForkChoiceError, Justification, JustifiedCheckpoint};
+use log::{debug, error, info, trace, warn};
 use operation_pool::DepositInsertStatus;
-use operation_pool::OperationPool;
-use parking_lot::{RwLock, RwLockReadGuard};
+use operation_pool::{OperationPool, PersistedOperationPool};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 use slot_clock::SlotClock;
-use ssz::ssz_encode;
+use ssz::{decode, ssz_encode};
 use state_processing::per_block_processing::errors::{
     AttestationValidationError, AttesterSlashingValidationError, DepositValidationError,
     ExitValidationError, ProposerSlashingValidationError, TransferValidationError,
 };
+use state_processing::per_block_processing::verify_block_signature;
 use state_processing::{
-    per_block_processing, per_block_processing_without_verifying_block_signature,
-    per_slot_processing, BlockProcessingError, SlotProcessingError,
+    per_block_processing_without_verifying_block_signature, per_slot_processing,
+    BlockProcessingError, SlotProcessingError,
 };
+use std::collections::HashSet;
 use std::sync::Arc;
+use tree_hash::SignedRoot;
 use types::*;
 
+/// The maximum number of slots between a block and its parent.
+///
+/// Each skipped slot must be processed before the block can be applied to its parent state, so
+/// blocks which skip more slots are rejected before any state is loaded.
+pub const MAX_SKIP_SLOTS: u64 = 700;
+
 #[derive(Debug, PartialEq)]
 pub enum ValidBlock {
     /// The block was successfully processed.
     Processed,
 }
 
+/// The reason a block was not imported.
+///
+/// Blocks are checked in stages of increasing cost (see `BeaconChain::process_block`); the
+/// variants are roughly ordered by the stage in which they are detected.
 #[derive(Debug, PartialEq)]
 pub enum InvalidBlock {
     /// The block slot is greater than the present slot.
@@ -35,10 +54,25 @@ pub enum InvalidBlock {
         present_slot: Slot,
         block_slot: Slot,
     },
-    /// The block state_root does not match the generated state.
-    StateRootMismatch,
+    /// The block is not later than the finalized block, it cannot be added to the chain.
+    FinalizedSlot {
+        finalized_slot: Slot,
+        block_slot: Slot,
+    },
+    /// The block body contains more of some operation than is permitted in a single block.
+    TooManyOperations,
+    /// The block has already been imported.
+    BlockIsAlreadyKnown,
     /// The blocks parent_root is unknown.
     ParentUnknown,
+    /// The block slot is not greater than the slot of its parent.
+    SlotNotAfterParent { parent_slot: Slot, block_slot: Slot },
+    /// The block skips more than `MAX_SKIP_SLOTS` slots after its parent.
+    TooManySkippedSlots { parent_slot: Slot, block_slot: Slot },
+    /// The block signature is not valid for the proposer of the block slot.
+    ProposalSignatureInvalid,
+    /// The block state_root does not match the generated state.
+    StateRootMismatch,
     /// There was an error whilst advancing the parent state to the present slot. This condition
     /// should not occur, it likely represents an internal error.
     SlotProcessingError(SlotProcessingError),
@@ -62,8 +96,14 @@ impl BlockProcessingOutcome {
             BlockProcessingOutcome::ValidBlock(_) => false,
             BlockProcessingOutcome::InvalidBlock(r) => match r {
                 InvalidBlock::FutureSlot { .. } => true,
-                InvalidBlock::StateRootMismatch => true,
+                InvalidBlock::FinalizedSlot { .. } => false,
+                InvalidBlock::TooManyOperations => true,
+                InvalidBlock::BlockIsAlreadyKnown => false,
                 InvalidBlock::ParentUnknown => false,
+                InvalidBlock::SlotNotAfterParent { .. } => true,
+                InvalidBlock::TooManySkippedSlots { .. } => false,
+                InvalidBlock::ProposalSignatureInvalid => true,
+                InvalidBlock::StateRootMismatch => true,
                 InvalidBlock::SlotProcessingError(_) => false,
                 InvalidBlock::PerBlockProcessingError(e) => match e {
                     BlockProcessingError::Invalid(_) => true,
@@ -73,11 +113,12 @@ impl BlockProcessingOutcome {
         }
     }
 
-    /// Returns `true` if the block was successfully processed and can be removed from any import
-    /// queues or temporary storage.
+    /// Returns `true` if the block was successfully processed (now or previously) and can be
+    /// removed from any import queues or temporary storage.
     pub fn sucessfully_processed(&self) -> bool {
         match self {
             BlockProcessingOutcome::ValidBlock(_) => true,
+            BlockProcessingOutcome::InvalidBlock(InvalidBlock::BlockIsAlreadyKnown) => true,
             _ => false,
         }
     }
@@ -86,13 +127,30 @@ impl BlockProcessingOutcome {
 pub struct BeaconChain<T: ClientDB + Sized, U: SlotClock, F: ForkChoice> {
     pub block_store: Arc<BeaconBlockStore<T>>,
     pub state_store: Arc<BeaconStateStore<T>>,
+    pub chain_metadata_store: Arc<ChainMetadataStore<T>>,
     pub slot_clock: U,
     pub op_pool: OperationPool,
     canonical_head: RwLock<CheckPoint>,
     finalized_head: RwLock<CheckPoint>,
+    /// The roots of all known blocks without any children.
+    heads: RwLock<Vec<Hash256>>,
     pub state: RwLock<BeaconState>,
     pub spec: ChainSpec,
     pub fork_choice: RwLock<F>,
+    /// The best justified checkpoint of all imported blocks, from which the fork choice starts.
+    ///
+    /// Must be locked after `fork_choice`.
+    justified_checkpoint: RwLock<JustifiedCheckpoint>,
+    /// The slot at which an attestation last triggered the fork choice.
+    attestation_fork_choice_slot: RwLock<Option<Slot>>,
+    /// Recently used block post-states, with their caches built.
+    state_cache: Mutex<StateCache>,
+    /// The block proposers of recent epochs on each known branch.
+    proposer_cache: Mutex<ProposerCache>,
+    /// Publishes changes to the chain to subscribers.
+    events: EventBus,
+    /// The source of eth1 data and deposits for block production, if any.
+    eth1_chain: RwLock<Option<Eth1Chain>>,
 }
 
 impl<T, U, F> BeaconChain<T, U, F>
@@ -105,6 +163,7 @@ where
     pub fn from_genesis(
         state_store: Arc<BeaconStateStore<T>>,
         block_store: Arc<BeaconBlockStore<T>>,
+        chain_metadata_store: Arc<ChainMetadataStore<T>>,
         slot_clock: U,
         mut genesis_state: BeaconState,
         genesis_block: BeaconBlock,
@@ -112,10 +171,20 @@ where
         fork_choice: F,
     ) -> Result<Self, Error> {
         let state_root = genesis_state.canonical_root();
-        state_store.put(&state_root, &ssz_encode(&genesis_state)[..])?;
-
         let block_root = genesis_block.block_header().canonical_root();
-        block_store.put(&block_root, &ssz_encode(&genesis_block)[..])?;
+
+        let persisted = PersistedBeaconChain {
+            canonical_head_block_root: block_root,
+            finalized_head_block_root: block_root,
+            heads: vec![block_root],
+        };
+
+        let mut batch = DBBatch::new();
+        state_store.batch_put(&mut batch, &state_root, &ssz_encode(&genesis_state)[..]);
+        block_store.batch_put(&mut batch, &block_root, &ssz_encode(&genesis_block)[..]);
+        chain_metadata_store.batch_put(&mut batch, &ssz_encode(&persisted)[..]);
+        block_store.batch_put_canonical_root(&mut batch, genesis_block.slot, &block_root);
+        block_store.write(batch)?;
 
         let finalized_head = RwLock::new(CheckPoint::new(
             genesis_block.clone(),
@@ -130,21 +199,225 @@ where
             state_root,
         ));
 
+        let justified_checkpoint = RwLock::new(JustifiedCheckpoint::new(
+            block_root,
+            Justification::from(&genesis_state),
+            &genesis_state,
+            &spec,
+        ));
+
         genesis_state.build_all_caches(&spec)?;
 
         Ok(Self {
             block_store,
             state_store,
+            chain_metadata_store,
             slot_clock,
             op_pool: OperationPool::new(),
             state: RwLock::new(genesis_state),
             finalized_head,
             canonical_head,
+            heads: RwLock::new(persisted.heads),
             spec,
             fork_choice: RwLock::new(fork_choice),
+            justified_checkpoint,
+            attestation_fork_choice_slot: RwLock::new(None),
+            state_cache: Mutex::new(StateCache::new(DEFAULT_STATE_CACHE_SIZE)),
+            proposer_cache: Mutex::new(ProposerCache::new(PROPOSER_CACHE_SIZE)),
+            events: EventBus::default(),
+            eth1_chain: RwLock::new(None),
         })
     }
 
+    /// Attempt to resume a Beacon Chain from a `PersistedBeaconChain` in the database.
+    ///
+    /// Returns `Ok(None)` if the database does not contain a persisted chain (e.g., it is a fresh
+    /// database), in which case the caller should start `from_genesis`.
+    ///
+    /// The canonical and finalized heads are loaded from the block and state stores and
+    /// `fork_choice` is rebuilt from the finalized block, by replaying each of its stored
+    /// descendants which is reachable from the known heads. The `slot_clock` is built from the
+    /// `genesis_time` of the stored chain.
+    pub fn from_store<C>(
+        state_store: Arc<BeaconStateStore<T>>,
+        block_store: Arc<BeaconBlockStore<T>>,
+        chain_metadata_store: Arc<ChainMetadataStore<T>>,
+        slot_clock: C,
+        spec: ChainSpec,
+        mut fork_choice: F,
+    ) -> Result<Option<Self>, Error>
+    where
+        C: FnOnce(u64) -> U,
+    {
+        let persisted = match chain_metadata_store.get()? {
+            Some(ssz) => decode::<PersistedBeaconChain>(&ssz)
+                .map_err(|_| Error::DBError("Bad PersistedBeaconChain SSZ.".to_string()))?,
+            None => return Ok(None),
+        };
+
+        let load_checkpoint = |block_root: Hash256| -> Result<CheckPoint, Error> {
+            let block = block_store
+                .get_deserialized(&block_root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(block_root))?;
+            let state_root = block.state_root;
+            let state = state_store
+                .get_deserialized(&state_root)?
+                .ok_or_else(|| Error::MissingBeaconState(state_root))?;
+
+            Ok(CheckPoint::new(block, block_root, state, state_root))
+        };
+
+        let canonical_head = load_checkpoint(persisted.canonical_head_block_root)?;
+        let finalized_head = load_checkpoint(persisted.finalized_head_block_root)?;
+
+        // Fork choice is rebuilt from the finalized block, as no block prior to it can become the
+        // head. The genesis block is never given to fork choice.
+        let finalized_root = finalized_head.beacon_block_root;
+        let finalized_slot = finalized_head.beacon_block.slot;
+        if finalized_head.beacon_block.previous_block_root != spec.zero_hash {
+            fork_choice.add_block(
+                &finalized_head.beacon_block,
+                &finalized_root,
+                Justification::from(&finalized_head.beacon_state),
+                &spec,
+            )?;
+        }
+
+        // Collect every block which descends from the finalized block from the heads, stopping at
+        // blocks we have already seen.
+        let mut seen: HashSet<Hash256> = HashSet::new();
+        seen.insert(finalized_root);
+        let mut to_replay: Vec<(Slot, Hash256)> = vec![];
+        for head in &persisted.heads {
+            let mut branch = vec![];
+            let mut block_root = *head;
+            while !seen.contains(&block_root) {
+                let block = block_store
+                    .get_deserialized(&block_root)?
+                    .ok_or_else(|| Error::MissingBeaconBlock(block_root))?;
+                if block.slot <= finalized_slot {
+                    break;
+                }
+
+                branch.push((block.slot, block_root));
+                block_root = block.previous_block_root;
+            }
+
+            if seen.contains(&block_root) {
+                seen.extend(branch.iter().map(|(_, root)| *root));
+                to_replay.append(&mut branch);
+            } else {
+                warn!(
+                    "Not replaying head {} which does not descend from the finalized block.",
+                    head
+                );
+            }
+        }
+
+        // A parent always has a lower slot than its child, so replaying in slot order ensures
+        // fork choice always knows of a blocks parent.
+        to_replay.sort();
+        for (_, block_root) in &to_replay {
+            let block = block_store
+                .get_deserialized(block_root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(*block_root))?;
+            // The states of blocks after the finalized block have not been frozen.
+            let mut state = state_store
+                .get_deserialized(&block.state_root)?
+                .ok_or_else(|| Error::MissingBeaconState(block.state_root))?;
+
+            fork_choice.add_block(&block, block_root, Justification::from(&state), &spec)?;
+
+            state.build_epoch_cache(RelativeEpoch::Previous, &spec)?;
+            state.build_epoch_cache(RelativeEpoch::Current, &spec)?;
+            for attestation in &block.body.attestations {
+                fork_choice.add_attestation(attestation, &state, &spec)?;
+            }
+        }
+
+        info!(
+            "Resuming beacon chain from database at slot {} ({} blocks since finalization replayed into fork choice).",
+            canonical_head.beacon_block.slot,
+            to_replay.len()
+        );
+
+        let slot_clock = slot_clock(canonical_head.beacon_state.genesis_time);
+
+        let mut state = canonical_head.beacon_state.clone();
+        state.build_all_caches(&spec)?;
+
+        // Databases written before the operation pool was persisted start with an empty pool.
+        let op_pool = match chain_metadata_store.get_op_pool()? {
+            Some(ssz) => decode::<PersistedOperationPool>(&ssz)
+                .map_err(|_| Error::DBError("Bad PersistedOperationPool SSZ.".to_string()))?
+                .into_operation_pool(&state, &spec),
+            None => OperationPool::new(),
+        };
+
+        // Start from the finalized head, the justified checkpoint of the canonical head is loaded
+        // once the chain (and its state reconstruction) is available.
+        let head_justification = Justification::from(&canonical_head.beacon_state);
+        let head_justified_root = canonical_head.beacon_state.current_justified_root;
+        let justified_checkpoint = RwLock::new(JustifiedCheckpoint::new(
+            finalized_head.beacon_block_root,
+            Justification::from(&finalized_head.beacon_state),
+            &finalized_head.beacon_state,
+            &spec,
+        ));
+
+        let chain = Self {
+            block_store,
+            state_store,
+            chain_metadata_store,
+            slot_clock,
+            op_pool,
+            state: RwLock::new(state),
+            finalized_head: RwLock::new(finalized_head),
+            canonical_head: RwLock::new(canonical_head),
+            heads: RwLock::new(persisted.heads),
+            spec,
+            fork_choice: RwLock::new(fork_choice),
+            justified_checkpoint,
+            attestation_fork_choice_slot: RwLock::new(None),
+            state_cache: Mutex::new(StateCache::new(DEFAULT_STATE_CACHE_SIZE)),
+            proposer_cache: Mutex::new(ProposerCache::new(PROPOSER_CACHE_SIZE)),
+            events: EventBus::default(),
+            eth1_chain: RwLock::new(None),
+        };
+
+        chain.update_justified_checkpoint(head_justification, head_justified_root)?;
+
+        Ok(Some(chain))
+    }
+
+    /// Returns a `PersistedBeaconChain` describing the present heads of the chain.
+    pub fn persisted_beacon_chain(&self) -> PersistedBeaconChain {
+        let canonical_head_block_root = self.head().beacon_block_root;
+        let finalized_head_block_root = self.finalized_head().beacon_block_root;
+        let heads = self.heads.read().clone();
+
+        PersistedBeaconChain {
+            canonical_head_block_root,
+            finalized_head_block_root,
+            heads,
+        }
+    }
+
+    /// Writes the present heads of the chain and the contents of the operation pool to the
+    /// database, allowing the chain to be resumed with `from_store` after a restart.
+    pub fn persist(&self) -> Result<(), Error> {
+        let op_pool = PersistedOperationPool::from_operation_pool(&self.op_pool);
+
+        let mut batch = DBBatch::new();
+        self.chain_metadata_store
+            .batch_put(&mut batch, &ssz_encode(&self.persisted_beacon_chain())[..]);
+        self.chain_metadata_store
+            .batch_put_op_pool(&mut batch, &ssz_encode(&op_pool)[..]);
+        self.chain_metadata_store.write(batch)?;
+
+        Ok(())
+    }
+
     /// Returns the beacon block body for each beacon block root in `roots`.
     ///
     /// Fails if any root in `roots` does not have a corresponding block.
@@ -178,82 +451,127 @@ where
     /// Returns `count `beacon block roots, starting from `start_slot` with an
     /// interval of `skip` slots between each root.
     ///
+    /// Roots are read from the canonical `slot -> block_root` index, so the cost is proportional
+    /// to `count`, not to the distance between `earliest_slot` and the head. A skipped slot has
+    /// the root of the most recent block prior to it.
+    ///
+    /// The range is clamped to the canonical head: fewer than `count` roots (possibly none) are
+    /// returned if the range extends past the head.
+    ///
     /// ## Errors:
     ///
-    /// - `SlotOutOfBounds`: Unable to return the full specified range.
-    /// - `SlotOutOfBounds`: Unable to load a state from the DB.
-    /// - `SlotOutOfBounds`: Start slot is higher than the first slot.
-    /// - Other: BeaconState` is inconsistent.
+    /// - `DBInconsistent`: The index does not contain an entry for some slot in the range.
     pub fn get_block_roots(
         &self,
         earliest_slot: Slot,
         count: usize,
         skip: usize,
     ) -> Result<Vec<Hash256>, Error> {
-        let spec = &self.spec;
-        let step_by = Slot::from(skip + 1);
+        let head_slot = self.head().beacon_block.slot;
+        if count == 0 || earliest_slot > head_slot {
+            return Ok(vec![]);
+        }
+
+        let step_by = skip + 1;
 
-        let mut roots: Vec<Hash256> = vec![];
+        // The number of slots in the range which are not past the head.
+        let available = (head_slot - earliest_slot).as_u64() as usize / step_by + 1;
+        let count = std::cmp::min(count, available);
 
-        // The state for reading block roots. Will be updated with an older state if slots go too
-        // far back in history.
-        let mut state = self.state.read().clone();
+        let roots: Vec<(Slot, Hash256)> = self
+            .block_store
+            .canonical_roots_from(earliest_slot)?
+            .step_by(step_by)
+            .take(count)
+            .collect::<Result<_, _>>()?;
 
-        // The final slot in this series, will be reduced by `skip` each loop iteration.
-        let mut slot = earliest_slot + Slot::from(count * (skip + 1)) - 1;
+        // The index has an entry for each slot, so the `i`th root should be at
+        // `earliest_slot + i * step_by`.
+        let is_contiguous = roots
+            .iter()
+            .enumerate()
+            .all(|(i, (slot, _))| *slot == earliest_slot + Slot::from(i * step_by));
 
-        // If the highest slot requested is that of the current state insert the root of the
-        // head block, unless the head block's slot is not matching.
-        if slot == state.slot && self.head().beacon_block.slot == slot {
-            roots.push(self.head().beacon_block_root);
+        if roots.len() == count && is_contiguous {
+            Ok(roots.into_iter().map(|(_, root)| root).collect())
+        } else {
+            Err(Error::DBInconsistent(format!(
+                "Canonical root index incomplete from slot {}",
+                earliest_slot
+            )))
+        }
+    }
 
-            slot -= step_by;
-        } else if slot >= state.slot {
-            return Err(BeaconStateError::SlotOutOfBounds.into());
+    /// Adds the operations required to make `new_head_block` the head of the canonical
+    /// `slot -> block_root` index to `batch`.
+    ///
+    /// Walks back from the new head, rewriting the index for each slot, until a block which is
+    /// already in the index is found. Entries above the new head (e.g., from a longer chain that
+    /// has been re-org'd out) are removed.
+    ///
+    /// Returns the slot of the latest block common to the old and new canonical chains.
+    fn batch_update_canonical_roots(
+        &self,
+        batch: &mut DBBatch,
+        new_head_block: &BeaconBlock,
+        new_head_block_root: Hash256,
+        old_head_slot: Slot,
+    ) -> Result<Slot, Error> {
+        for slot in (new_head_block.slot + 1).as_u64()..=old_head_slot.as_u64() {
+            self.block_store
+                .batch_delete_canonical_root(batch, Slot::from(slot));
         }
 
+        let mut block = new_head_block.clone();
+        let mut block_root = new_head_block_root;
+        // Each block is canonical from its own slot up to (but excluding) this slot.
+        let mut next_slot = new_head_block.slot + 1;
+
         loop {
-            // If the slot is within the range of the current state's block roots, append the root
-            // to the output vec.
-            //
-            // If we get `SlotOutOfBounds` error, load the oldest available historic
-            // state from the DB.
-            match state.get_block_root(slot, spec) {
-                Ok(root) => {
-                    if slot < earliest_slot {
-                        break;
-                    } else {
-                        roots.push(*root);
-                        slot -= step_by;
-                    }
-                }
-                Err(BeaconStateError::SlotOutOfBounds) => {
-                    // Read the earliest historic state in the current slot.
-                    let earliest_historic_slot =
-                        state.slot - Slot::from(spec.slots_per_historical_root);
-                    // Load the earlier state from disk.
-                    let new_state_root = state.get_state_root(earliest_historic_slot, spec)?;
-
-                    // Break if the DB is unable to load the state.
-                    state = match self.state_store.get_deserialized(&new_state_root) {
-                        Ok(Some(state)) => state,
-                        _ => break,
-                    }
-                }
-                Err(e) => return Err(e.into()),
-            };
+            let is_canonical =
+                self.block_store.canonical_root_at_slot(block.slot)? == Some(block_root);
+
+            for slot in block.slot.as_u64()..next_slot.as_u64() {
+                self.block_store
+                    .batch_put_canonical_root(batch, Slot::from(slot), &block_root);
+            }
+
+            if is_canonical || block.previous_block_root == self.spec.zero_hash {
+                break;
+            }
+
+            next_slot = block.slot;
+            block_root = block.previous_block_root;
+            block = self
+                .block_store
+                .get_deserialized(&block_root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(block_root))?;
         }
 
-        // Return the results if they pass a sanity check.
-        if (slot <= earliest_slot) && (roots.len() == count) {
-            // Reverse the ordering of the roots. We extracted them in reverse order to make it
-            // simpler to lookup historic states.
-            //
-            // This is a potential optimisation target.
-            Ok(roots.iter().rev().cloned().collect())
-        } else {
-            Err(BeaconStateError::SlotOutOfBounds.into())
+        Ok(block.slot)
+    }
+
+    /// Returns the number of blocks in the canonical chain with a slot in
+    /// `(ancestor_slot, old_head_slot]`, i.e., the number of blocks which are removed from the
+    /// canonical chain if the chain is re-org'd back to the block at `ancestor_slot`.
+    fn reorg_depth(&self, ancestor_slot: Slot, old_head_slot: Slot) -> Result<usize, Error> {
+        let mut previous_root = self.block_store.canonical_root_at_slot(ancestor_slot)?;
+        let mut depth = 0;
+
+        for entry in self.block_store.canonical_roots_from(ancestor_slot + 1)? {
+            let (slot, root) = entry?;
+            if slot > old_head_slot {
+                break;
+            }
+
+            // Skipped slots repeat the root of the prior block.
+            if previous_root != Some(root) {
+                depth += 1;
+                previous_root = Some(root);
+            }
         }
+
+        Ok(depth)
     }
 
     /// Returns the block at the given root, if any.
@@ -265,25 +583,40 @@ where
         Ok(self.block_store.get_deserialized(block_root)?)
     }
 
-    /// Update the canonical head to some new values.
-    pub fn update_canonical_head(
+    /// Update the canonical head and the canonical `BeaconState` together.
+    ///
+    /// The state is advanced to the present slot before either lock is taken, so readers never
+    /// observe the new head with the old state (or vice versa).
+    fn update_canonical_head_and_state(
         &self,
         new_beacon_block: BeaconBlock,
         new_beacon_block_root: Hash256,
         new_beacon_state: BeaconState,
         new_beacon_state_root: Hash256,
-    ) {
-        debug!(
-            "Updating canonical head with block at slot: {}",
-            new_beacon_block.slot
-        );
+    ) -> Result<(), Error> {
+        let present_slot = match self.slot_clock.present_slot() {
+            Ok(Some(slot)) => slot,
+            _ => return Err(Error::UnableToReadSlot),
+        };
+
+        let mut state = new_beacon_state.clone();
+        for _ in state.slot.as_u64()..present_slot.as_u64() {
+            per_slot_processing(&mut state, &self.spec)?;
+        }
+        state.build_all_caches(&self.spec)?;
+
         let mut head = self.canonical_head.write();
+        let mut canonical_state = self.state.write();
+
         head.update(
             new_beacon_block,
             new_beacon_block_root,
             new_beacon_state,
             new_beacon_state_root,
         );
+        *canonical_state = state;
+
+        Ok(())
     }
 
     /// Returns a read-lock guarded `CheckPoint` struct for reading the head (as chosen by the
@@ -352,21 +685,385 @@ where
         Ok(())
     }
 
-    /// Update the justified head to some new values.
+    /// Update the finalized head to some new values.
+    ///
+    /// The new finalized head is persisted to the database and the states of the canonical blocks
+    /// between the old and new finalized heads are moved from the "hot" to the "cold" column of the
+    /// `state_store` (see `BeaconStateStore` for a description of the columns).
+    ///
+    /// All blocks (and their states) which do not descend from the new finalized head are pruned
+    /// from the database and the fork choice.
     pub fn update_finalized_head(
         &self,
         new_beacon_block: BeaconBlock,
         new_beacon_block_root: Hash256,
         new_beacon_state: BeaconState,
         new_beacon_state_root: Hash256,
-    ) {
-        let mut finalized_head = self.finalized_head.write();
-        finalized_head.update(
+    ) -> Result<(), Error> {
+        let old_finalized_slot = self.finalized_head().beacon_block.slot;
+        let new_finalized_slot = new_beacon_block.slot;
+
+        // Hold the fork choice lock from before the pruned blocks are deleted until they have
+        // been removed from fork choice, so fork choice never finds a head which is not stored.
+        let mut fork_choice = self.fork_choice.write();
+
+        let mut batch = DBBatch::new();
+        self.batch_freeze_states(&mut batch, old_finalized_slot, new_finalized_slot)?;
+
+        let pruned = {
+            let mut heads = self.heads.write();
+
+            let pruned = self.batch_prune_abandoned_forks(
+                &mut batch,
+                &heads,
+                new_beacon_block_root,
+                new_finalized_slot,
+            )?;
+
+            let persisted = PersistedBeaconChain {
+                canonical_head_block_root: self.head().beacon_block_root,
+                finalized_head_block_root: new_beacon_block_root,
+                heads: pruned.heads.clone(),
+            };
+            self.chain_metadata_store
+                .batch_put(&mut batch, &ssz_encode(&persisted)[..]);
+            self.state_store.write(batch)?;
+
+            *heads = persisted.heads;
+
+            pruned
+        };
+
+        self.finalized_head.write().update(
             new_beacon_block,
             new_beacon_block_root,
             new_beacon_state,
             new_beacon_state_root,
         );
+
+        fork_choice.prune(
+            &pruned.block_roots,
+            new_finalized_slot.epoch(self.spec.slots_per_epoch),
+        )?;
+        drop(fork_choice);
+
+        if !pruned.block_roots.is_empty() {
+            info!(
+                "Pruned {} blocks and {} states from abandoned forks, reclaiming {} bytes",
+                pruned.block_roots.len(),
+                pruned.states,
+                pruned.bytes
+            );
+        }
+
+        self.events.publish(BeaconChainEvent::Finalized {
+            block_root: new_beacon_block_root,
+            slot: new_finalized_slot,
+        });
+
+        Ok(())
+    }
+
+    /// Adds the operations required to delete all blocks (and their states) which do not descend
+    /// from the finalized block to `batch`.
+    ///
+    /// Each of the `heads` is walked back to the finalized slot; if it does not pass through the
+    /// finalized block, its branch is deleted back to the first canonical block.
+    fn batch_prune_abandoned_forks(
+        &self,
+        batch: &mut DBBatch,
+        heads: &[Hash256],
+        finalized_block_root: Hash256,
+        finalized_slot: Slot,
+    ) -> Result<PrunedForks, Error> {
+        let mut pruned = PrunedForks::default();
+        let mut deleted = HashSet::new();
+
+        let get_block = |root: Hash256| -> Result<BeaconBlock, Error> {
+            self.block_store
+                .get_deserialized(&root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(root))
+        };
+
+        for head in heads {
+            let mut root = *head;
+            let mut block = get_block(root)?;
+            while block.slot > finalized_slot {
+                root = block.previous_block_root;
+                block = get_block(root)?;
+            }
+
+            if root == finalized_block_root {
+                pruned.heads.push(*head);
+                continue;
+            }
+
+            root = *head;
+            block = get_block(root)?;
+            loop {
+                // Stop at the canonical chain, or at a branch which another head has already
+                // pruned.
+                if self.block_store.canonical_root_at_slot(block.slot)? == Some(root)
+                    || !deleted.insert(root)
+                {
+                    break;
+                }
+
+                trace!("Pruning abandoned block {} at slot {}", root, block.slot);
+
+                self.block_store.batch_delete(batch, &root);
+                pruned.bytes += ssz_encode(&block).len();
+                pruned.block_roots.push(root);
+
+                if let Some(state_ssz) = self.state_store.get(&block.state_root)? {
+                    self.state_store.batch_delete(batch, &block.state_root);
+                    pruned.bytes += state_ssz.len();
+                    pruned.states += 1;
+                }
+
+                root = block.previous_block_root;
+                block = get_block(root)?;
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    /// Adds the operations required to move the states of all canonical blocks with a slot in
+    /// `[from_slot, to_slot)` from the hot column to the cold column of the `state_store`.
+    ///
+    /// Only the states of the first blocks at or after a cold state boundary are written to the
+    /// cold column, the rest are simply deleted and must be reconstructed by `get_block_state`.
+    fn batch_freeze_states(
+        &self,
+        batch: &mut DBBatch,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) -> Result<(), Error> {
+        let genesis_slot = self.spec.genesis_slot;
+        let slots_per_epoch = self.spec.slots_per_epoch;
+
+        let mut previous_root = None;
+        let mut previous_slot = None;
+        let mut frozen = 0;
+
+        for entry in self.block_store.canonical_roots_from(from_slot)? {
+            let (slot, block_root) = entry?;
+            if slot >= to_slot {
+                break;
+            }
+
+            // A block is repeated in the index for each skipped slot after it; only its first
+            // entry is at the slot of the block.
+            if previous_root == Some(block_root) {
+                continue;
+            }
+            previous_root = Some(block_root);
+
+            let block = self
+                .block_store
+                .get_deserialized(&block_root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(block_root))?;
+
+            // The parent of the first block was frozen previously.
+            let parent_slot = match previous_slot {
+                Some(parent_slot) => Some(parent_slot),
+                None if block.slot == genesis_slot => None,
+                None => {
+                    let parent_root = block.previous_block_root;
+                    let parent = self
+                        .block_store
+                        .get_deserialized(&parent_root)?
+                        .ok_or_else(|| Error::MissingBeaconBlock(parent_root))?;
+                    Some(parent.slot)
+                }
+            };
+            previous_slot = Some(block.slot);
+
+            if self.state_store.is_cold_state_slot(
+                block.slot,
+                parent_slot,
+                genesis_slot,
+                slots_per_epoch,
+            ) {
+                let cold_slot = self.state_store.cold_state_slot_at_or_before(
+                    block.slot,
+                    genesis_slot,
+                    slots_per_epoch,
+                );
+                let state_ssz = self
+                    .state_store
+                    .get(&block.state_root)?
+                    .ok_or_else(|| Error::MissingBeaconState(block.state_root))?;
+                self.state_store
+                    .batch_put_cold(batch, cold_slot, &state_ssz);
+            }
+            self.state_store.batch_delete(batch, &block.state_root);
+
+            frozen += 1;
+        }
+
+        debug!(
+            "Moving {} finalized states between slots {} and {} to cold storage.",
+            frozen, from_slot, to_slot
+        );
+
+        Ok(())
+    }
+
+    /// Checks the finalized checkpoint of the canonical head state and, if it has advanced beyond
+    /// the present `finalized_head`, updates the `finalized_head`.
+    fn update_finalization(&self) -> Result<(), Error> {
+        let finalized_root = self.head().beacon_state.finalized_root;
+
+        if finalized_root == self.spec.zero_hash
+            || finalized_root == self.finalized_head().beacon_block_root
+        {
+            return Ok(());
+        }
+
+        let block = self
+            .block_store
+            .get_deserialized(&finalized_root)?
+            .ok_or_else(|| Error::MissingBeaconBlock(finalized_root))?;
+
+        // Never allow the finalized head to move backwards.
+        if block.slot <= self.finalized_head().beacon_block.slot {
+            return Ok(());
+        }
+
+        let state_root = block.state_root;
+        let state = self
+            .state_store
+            .get_deserialized(&state_root)?
+            .ok_or_else(|| Error::MissingBeaconState(state_root))?;
+
+        info!("Finalized block at slot {}", block.slot);
+
+        self.update_finalized_head(block, finalized_root, state, state_root)
+    }
+
+    /// Returns the post-state of the given block, if it is known. The caches of the state are
+    /// built.
+    ///
+    /// States are first read from the state cache, then the hot column of the `state_store`. If
+    /// the state has been frozen, it is reconstructed by replaying the canonical blocks on top of
+    /// the nearest prior cold state.
+    pub fn get_block_state(
+        &self,
+        block_root: &Hash256,
+        block: &BeaconBlock,
+    ) -> Result<Option<BeaconState>, Error> {
+        if let Some(state) = self.state_cache.lock().get(&block.state_root) {
+            return Ok(Some(state));
+        }
+
+        if let Some(mut state) = self.state_store.get_deserialized(&block.state_root)? {
+            self.cache_state(block.state_root, &mut state)?;
+            return Ok(Some(state));
+        }
+
+        // Only canonical states prior to the finalized head are frozen.
+        if block.slot >= self.finalized_head().beacon_block.slot
+            || self.block_store.canonical_root_at_slot(block.slot)? != Some(*block_root)
+        {
+            return Ok(None);
+        }
+
+        // The state kept for the boundary is that of the first block at or after it, which may be
+        // later than the boundary if it was a skipped slot.
+        let cold_slot = self.state_store.cold_state_slot_at_or_before(
+            block.slot,
+            self.spec.genesis_slot,
+            self.spec.slots_per_epoch,
+        );
+        let mut state = self
+            .state_store
+            .get_cold_deserialized(cold_slot)?
+            .ok_or_else(|| {
+                Error::DBInconsistent(format!("No cold state prior to slot {}", block.slot))
+            })?;
+
+        trace!(
+            "Reconstructing state at slot {} from cold state at slot {}.",
+            block.slot,
+            state.slot
+        );
+
+        let mut previous_root = self.block_store.canonical_root_at_slot(state.slot)?;
+
+        for entry in self.block_store.canonical_roots_from(state.slot + 1)? {
+            let (slot, root) = entry?;
+            if slot > block.slot {
+                break;
+            }
+
+            if previous_root == Some(root) {
+                continue;
+            }
+            previous_root = Some(root);
+
+            let replay_block = self
+                .block_store
+                .get_deserialized(&root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(root))?;
+
+            for _ in state.slot.as_u64()..replay_block.slot.as_u64() {
+                per_slot_processing(&mut state, &self.spec)?;
+            }
+
+            // Signatures were verified when the block was first imported.
+            per_block_processing_without_verifying_block_signature(
+                &mut state,
+                &replay_block,
+                &self.spec,
+            )?;
+        }
+
+        if state.canonical_root() != block.state_root {
+            return Err(Error::DBInconsistent(format!(
+                "Reconstructed state does not match state root {}",
+                block.state_root
+            )));
+        }
+
+        self.cache_state(block.state_root, &mut state)?;
+
+        Ok(Some(state))
+    }
+
+    /// Builds the caches of `state` and adds a copy to the state cache.
+    fn cache_state(&self, state_root: Hash256, state: &mut BeaconState) -> Result<(), Error> {
+        state.build_all_caches(&self.spec)?;
+        self.state_cache.lock().insert(state_root, state.clone());
+
+        Ok(())
+    }
+
+    /// Returns the number of hits and misses of the state cache.
+    pub fn state_cache_stats(&self) -> StateCacheStats {
+        self.state_cache.lock().stats()
+    }
+
+    /// Sets the maximum number of states held in the state cache.
+    pub fn set_state_cache_capacity(&self, capacity: usize) {
+        self.state_cache.lock().set_capacity(capacity);
+    }
+
+    /// Reads eth1 data and deposits for block production from `backend`, voting for eth1 blocks
+    /// `follow_distance` behind the eth1 head.
+    ///
+    /// Without a backend, produced blocks vote for the present `latest_eth1_data` and take their
+    /// deposits from the `op_pool`.
+    pub fn set_eth1_backend(&self, backend: Box<dyn Eth1Backend>, follow_distance: u64) {
+        *self.eth1_chain.write() = Some(Eth1Chain::new(backend, follow_distance, &self.spec));
+    }
+
+    /// Returns a channel which receives every `BeaconChainEvent` published from now on.
+    ///
+    /// The channel is unbounded, so a subscriber should drain it regularly or drop it.
+    pub fn subscribe_events(&self) -> Receiver<BeaconChainEvent> {
+        self.events.subscribe()
     }
 
     /// Returns a read-lock guarded `CheckPoint` struct for reading the justified head (as chosen,
@@ -375,6 +1072,66 @@ where
         self.finalized_head.read()
     }
 
+    /// Returns a read-lock guarded `JustifiedCheckpoint` from which the fork choice searches for
+    /// the head.
+    pub fn justified_checkpoint(&self) -> RwLockReadGuard<JustifiedCheckpoint> {
+        self.justified_checkpoint.read()
+    }
+
+    /// Returns the blocks known to the fork choice which descend from the justified checkpoint,
+    /// with the weight and latest votes of each.
+    pub fn block_tree(&self) -> Result<BlockTree, Error> {
+        let fork_choice = self.fork_choice.read();
+
+        Ok(fork_choice.block_tree(&*self.justified_checkpoint())?)
+    }
+
+    /// Replaces the justified checkpoint if `justification` (the justification of the post-state
+    /// of an imported block) is better than that of the present checkpoint.
+    ///
+    /// A later justified epoch is better, as is a later finalized epoch with an equal justified
+    /// epoch. The balances of the checkpoint are read from the state of the block at
+    /// `justified_root`, which may need to be reconstructed if it is frozen.
+    fn update_justified_checkpoint(
+        &self,
+        justification: Justification,
+        justified_root: Hash256,
+    ) -> Result<(), Error> {
+        let (present, present_root) = {
+            let checkpoint = self.justified_checkpoint.read();
+            (checkpoint.justification, checkpoint.root)
+        };
+
+        let is_better = (justification.justified_epoch, justification.finalized_epoch)
+            > (present.justified_epoch, present.finalized_epoch);
+        if !is_better || justified_root == self.spec.zero_hash {
+            return Ok(());
+        }
+
+        if justified_root == present_root {
+            self.justified_checkpoint.write().justification = justification;
+            return Ok(());
+        }
+
+        let block = self
+            .block_store
+            .get_deserialized(&justified_root)?
+            .ok_or_else(|| Error::MissingBeaconBlock(justified_root))?;
+        let state = self
+            .get_block_state(&justified_root, &block)?
+            .ok_or_else(|| Error::MissingBeaconState(block.state_root))?;
+
+        info!(
+            "Justified epoch {} at block {}",
+            justification.justified_epoch, justified_root
+        );
+
+        *self.justified_checkpoint.write() =
+            JustifiedCheckpoint::new(justified_root, justification, &state, &self.spec);
+
+        Ok(())
+    }
+
     /// Returns the validator index (if any) for the given public key.
     ///
     /// Information is retrieved from the present `beacon_state.validator_registry`.
@@ -521,13 +1278,45 @@ where
     /// Accept a new attestation from the network.
     ///
     /// If valid, the attestation is added to the `op_pool` and aggregated with another attestation
-    /// if possible.
+    /// if possible, then given to the fork choice.
+    ///
+    /// The fork choice is run on the first valid attestation received in each slot.
     pub fn process_attestation(
         &self,
         attestation: Attestation,
     ) -> Result<(), AttestationValidationError> {
-        self.op_pool
-            .insert_attestation(attestation, &*self.state.read(), &self.spec)
+        {
+            // The fork choice lock must be taken before the state lock, see `fork_choice`.
+            let mut fork_choice = self.fork_choice.write();
+            let state = self.state.read();
+
+            self.op_pool
+                .insert_attestation(attestation.clone(), &*state, &self.spec)?;
+
+            if let Err(e) = fork_choice.add_attestation(&attestation, &*state, &self.spec) {
+                warn!("Unable to add attestation to fork choice: {:?}", e);
+            }
+        }
+
+        self.events
+            .publish(BeaconChainEvent::AttestationReceived(attestation.data));
+
+        let present_slot = self.present_slot();
+        let is_first_in_slot = {
+            let mut last_slot = self.attestation_fork_choice_slot.write();
+            let is_first = *last_slot != Some(present_slot);
+            *last_slot = Some(present_slot);
+            is_first
+        };
+
+        // A fork choice failure does not invalidate the attestation.
+        if is_first_in_slot {
+            if let Err(e) = self.fork_choice() {
+                warn!("Fork choice failed after attestation: {:?}", e);
+            }
+        }
+
+        Ok(())
     }
 
     /// Accept some deposit and queue it for inclusion in an appropriate block.
@@ -535,20 +1324,31 @@ where
         &self,
         deposit: Deposit,
     ) -> Result<DepositInsertStatus, DepositValidationError> {
-        self.op_pool
-            .insert_deposit(deposit, &*self.state.read(), &self.spec)
+        let status = self
+            .op_pool
+            .insert_deposit(deposit, &*self.state.read(), &self.spec)?;
+
+        if status != DepositInsertStatus::Duplicate {
+            self.publish_pooled(PooledOperation::Deposit);
+        }
+
+        Ok(status)
     }
 
     /// Accept some exit and queue it for inclusion in an appropriate block.
     pub fn process_voluntary_exit(&self, exit: VoluntaryExit) -> Result<(), ExitValidationError> {
         self.op_pool
-            .insert_voluntary_exit(exit, &*self.state.read(), &self.spec)
+            .insert_voluntary_exit(exit, &*self.state.read(), &self.spec)?;
+        self.publish_pooled(PooledOperation::VoluntaryExit);
+        Ok(())
     }
 
     /// Accept some transfer and queue it for inclusion in an appropriate block.
     pub fn process_transfer(&self, transfer: Transfer) -> Result<(), TransferValidationError> {
         self.op_pool
-            .insert_transfer(transfer, &*self.state.read(), &self.spec)
+            .insert_transfer(transfer, &*self.state.read(), &self.spec)?;
+        self.publish_pooled(PooledOperation::Transfer);
+        Ok(())
     }
 
     /// Accept some proposer slashing and queue it for inclusion in an appropriate block.
@@ -556,8 +1356,13 @@ where
         &self,
         proposer_slashing: ProposerSlashing,
     ) -> Result<(), ProposerSlashingValidationError> {
-        self.op_pool
-            .insert_proposer_slashing(proposer_slashing, &*self.state.read(), &self.spec)
+        self.op_pool.insert_proposer_slashing(
+            proposer_slashing,
+            &*self.state.read(),
+            &self.spec,
+        )?;
+        self.publish_pooled(PooledOperation::ProposerSlashing);
+        Ok(())
     }
 
     /// Accept some attester slashing and queue it for inclusion in an appropriate block.
@@ -565,18 +1370,38 @@ where
         &self,
         attester_slashing: AttesterSlashing,
     ) -> Result<(), AttesterSlashingValidationError> {
-        self.op_pool
-            .insert_attester_slashing(attester_slashing, &*self.state.read(), &self.spec)
+        self.op_pool.insert_attester_slashing(
+            attester_slashing,
+            &*self.state.read(),
+            &self.spec,
+        )?;
+        self.publish_pooled(PooledOperation::AttesterSlashing);
+        Ok(())
+    }
+
+    /// Notifies subscribers that an operation was added to the `op_pool`.
+    fn publish_pooled(&self, operation: PooledOperation) {
+        self.events
+            .publish(BeaconChainEvent::OperationPooled(operation));
     }
 
     /// Accept some block and attempt to add it to block DAG.
     ///
     /// Will accept blocks from prior slots, however it will reject any block from a future slot.
+    ///
+    /// To limit the work an invalid block can cause, the block is checked in stages of increasing
+    /// cost:
+    ///
+    /// 1. Checks which only require the block (e.g., its slot and the size of its body).
+    /// 2. Checks against the database (the parent must be known, the block must not be, and at
+    ///    most `MAX_SKIP_SLOTS` slots may separate the block from its parent).
+    /// 3. Verification of the proposer signature, using the shuffling of the canonical state or
+    ///    the proposers cached from an earlier block of the same epoch and branch. Only if neither
+    ///    is available is the signature checked after the parent state has been loaded.
+    /// 4. The state transition.
     pub fn process_block(&self, block: BeaconBlock) -> Result<BlockProcessingOutcome, Error> {
         debug!("Processing block with slot {}...", block.slot);
 
-        let block_root = block.block_header().canonical_root();
-
         let present_slot = self.present_slot();
 
         if block.slot > present_slot {
@@ -588,6 +1413,31 @@ where
             ));
         }
 
+        let finalized_slot = self.finalized_head().beacon_block.slot;
+
+        if block.slot <= finalized_slot {
+            return Ok(BlockProcessingOutcome::InvalidBlock(
+                InvalidBlock::FinalizedSlot {
+                    finalized_slot,
+                    block_slot: block.slot,
+                },
+            ));
+        }
+
+        if exceeds_operation_limits(&block, &self.spec) {
+            return Ok(BlockProcessingOutcome::InvalidBlock(
+                InvalidBlock::TooManyOperations,
+            ));
+        }
+
+        let block_root = block.block_header().canonical_root();
+
+        if self.block_store.exists(&block_root)? {
+            return Ok(BlockProcessingOutcome::InvalidBlock(
+                InvalidBlock::BlockIsAlreadyKnown,
+            ));
+        }
+
         // Load the blocks parent block from the database, returning invalid if that block is not
         // found.
         let parent_block_root = block.previous_block_root;
@@ -600,19 +1450,62 @@ where
             }
         };
 
+        if block.slot <= parent_block.slot {
+            return Ok(BlockProcessingOutcome::InvalidBlock(
+                InvalidBlock::SlotNotAfterParent {
+                    parent_slot: parent_block.slot,
+                    block_slot: block.slot,
+                },
+            ));
+        }
+
+        if (block.slot - parent_block.slot).as_u64() > MAX_SKIP_SLOTS {
+            return Ok(BlockProcessingOutcome::InvalidBlock(
+                InvalidBlock::TooManySkippedSlots {
+                    parent_slot: parent_block.slot,
+                    block_slot: block.slot,
+                },
+            ));
+        }
+
+        // Blocks built upon the head in the epoch of the canonical state, or upon a branch whose
+        // proposers for the epoch are cached, can be checked without loading the parent state.
+        let block_epoch = block.slot.epoch(self.spec.slots_per_epoch);
+        let shuffling_root = self.shuffling_root(parent_block_root, &parent_block, block_epoch)?;
+        let known_signature = match self.verify_signature_with_head_shuffling(&block)? {
+            Some(valid) => Some(valid),
+            None => self.verify_signature_with_cached_proposers(&block, shuffling_root),
+        };
+        let mut signature_verified = match known_signature {
+            Some(false) => {
+                return Ok(BlockProcessingOutcome::InvalidBlock(
+                    InvalidBlock::ProposalSignatureInvalid,
+                ));
+            }
+            Some(true) => true,
+            None => false,
+        };
+
         // Load the parent blocks state from the database, returning an error if it is not found.
         // It is an error because if know the parent block we should also know the parent state.
         let parent_state_root = parent_block.state_root;
-        let parent_state = self
-            .state_store
-            .get_deserialized(&parent_state_root)?
+        let mut state = self
+            .get_block_state(&parent_block_root, &parent_block)?
             .ok_or_else(|| Error::DBInconsistent(format!("Missing state {}", parent_state_root)))?;
 
-        // TODO: check the block proposer signature BEFORE doing a state transition. This will
-        // significantly lower exposure surface to DoS attacks.
+        // The shuffling of the parent state can only be used if the block is in the same epoch.
+        // Otherwise, the signature is checked once the state has been transitioned to the block
+        // slot, but before the block is applied.
+        if !signature_verified && state.current_epoch(&self.spec) == block_epoch {
+            if !self.block_signature_is_valid(&mut state, &block)? {
+                return Ok(BlockProcessingOutcome::InvalidBlock(
+                    InvalidBlock::ProposalSignatureInvalid,
+                ));
+            }
+            signature_verified = true;
+        }
 
         // Transition the parent state to the block slot.
-        let mut state = parent_state;
         for _ in state.slot.as_u64()..block.slot.as_u64() {
             if let Err(e) = per_slot_processing(&mut state, &self.spec) {
                 return Ok(BlockProcessingOutcome::InvalidBlock(
@@ -621,9 +1514,22 @@ where
             }
         }
 
+        // Cache the proposers of the epoch (whether or not this block is valid), so later blocks
+        // on this branch are checked before their parent state is loaded.
+        state.build_epoch_cache(RelativeEpoch::Current, &self.spec)?;
+        self.cache_proposers(&state, block_epoch, shuffling_root)?;
+
+        if !signature_verified && !self.block_signature_is_valid(&mut state, &block)? {
+            return Ok(BlockProcessingOutcome::InvalidBlock(
+                InvalidBlock::ProposalSignatureInvalid,
+            ));
+        }
+
         // Apply the received block to its parent state (which has been transitioned into this
-        // slot).
-        if let Err(e) = per_block_processing(&mut state, &block, &self.spec) {
+        // slot). The proposer signature has already been verified.
+        if let Err(e) =
+            per_block_processing_without_verifying_block_signature(&mut state, &block, &self.spec)
+        {
             return Ok(BlockProcessingOutcome::InvalidBlock(
                 InvalidBlock::PerBlockProcessingError(e),
             ));
@@ -637,29 +1543,198 @@ where
             ));
         }
 
-        // Store the block and state.
-        self.block_store.put(&block_root, &ssz_encode(&block)[..])?;
-        self.state_store.put(&state_root, &ssz_encode(&state)[..])?;
+        // Store the block, its state and the updated chain metadata in a single batch so the
+        // database never contains a block without its post-state, or a head which is missing.
+        //
+        // The fork choice lock is held until the block has been added, so an attestation for this
+        // block cannot be given to fork choice before the block.
+        {
+            let mut fork_choice = self.fork_choice.write();
+            let mut heads = self.heads.write();
+
+            let mut new_heads = heads.clone();
+            new_heads.retain(|root| *root != parent_block_root);
+            new_heads.push(block_root);
 
-        // run the fork_choice add_block logic
-        self.fork_choice
-            .write()
-            .add_block(&block, &block_root, &self.spec)?;
+            let canonical_head_block_root = self.head().beacon_block_root;
+            let finalized_head_block_root = self.finalized_head().beacon_block_root;
 
-        // If the parent block was the parent_block, automatically update the canonical head.
-        //
-        // TODO: this is a first-in-best-dressed scenario that is not ideal; fork_choice should be
-        // run instead.
-        if self.head().beacon_block_root == parent_block_root {
-            self.update_canonical_head(block.clone(), block_root, state.clone(), state_root);
+            let persisted = PersistedBeaconChain {
+                canonical_head_block_root,
+                finalized_head_block_root,
+                heads: new_heads,
+            };
+
+            let mut batch = DBBatch::new();
+            self.block_store
+                .batch_put(&mut batch, &block_root, &ssz_encode(&block)[..]);
+            self.state_store
+                .batch_put(&mut batch, &state_root, &ssz_encode(&state)[..]);
+            self.chain_metadata_store
+                .batch_put(&mut batch, &ssz_encode(&persisted)[..]);
+            self.block_store.write(batch)?;
+
+            *heads = persisted.heads;
+            drop(heads);
+
+            // The block has been imported, so from here on errors are logged rather than
+            // returned. Otherwise, the caller would consider a stored block to be invalid.
+            if let Err(e) =
+                fork_choice.add_block(&block, &block_root, Justification::from(&state), &self.spec)
+            {
+                error!("Unable to add block {} to fork choice: {:?}", block_root, e);
+            }
+
+            // The post-state has the committee caches built for the epochs of the attestations.
+            for attestation in &block.body.attestations {
+                if let Err(e) = fork_choice.add_attestation(attestation, &state, &self.spec) {
+                    warn!(
+                        "Unable to add attestation from block {} to fork choice: {:?}",
+                        block_root, e
+                    );
+                }
+            }
+        }
+
+        // Children of this block (often from gossip) will start from this state.
+        if let Err(e) = self.cache_state(state_root, &mut state) {
+            warn!("Unable to cache the state of block {}: {:?}", block_root, e);
+        }
+
+        if let Err(e) = self
+            .update_justified_checkpoint(Justification::from(&state), state.current_justified_root)
+        {
+            error!(
+                "Unable to update the justified checkpoint after block {}: {:?}",
+                block_root, e
+            );
+        }
 
-            // Update the canonical `BeaconState`.
-            self.update_state(state)?;
+        self.events.publish(BeaconChainEvent::BlockImported {
+            block_root,
+            slot: block.slot,
+        });
+
+        // The new block may have changed the head (it need not be a child of the present head).
+        if let Err(e) = self.fork_choice() {
+            error!(
+                "Fork choice failed after importing block {}: {:?}",
+                block_root, e
+            );
         }
 
         Ok(BlockProcessingOutcome::ValidBlock(ValidBlock::Processed))
     }
 
+    /// Verifies the signature of `block` against its proposer in the canonical `state`, if the
+    /// block is a child of the canonical head in the epoch of the canonical state.
+    ///
+    /// Returns `None` if the shuffling of the canonical state cannot be used for the block.
+    fn verify_signature_with_head_shuffling(
+        &self,
+        block: &BeaconBlock,
+    ) -> Result<Option<bool>, Error> {
+        if block.previous_block_root != self.head().beacon_block_root {
+            return Ok(None);
+        }
+
+        let state = self.state.read();
+
+        if state.current_epoch(&self.spec) != block.slot.epoch(self.spec.slots_per_epoch) {
+            return Ok(None);
+        }
+
+        signature_is_valid(verify_block_signature(&state, block, &self.spec)).map(Some)
+    }
+
+    /// Verifies the signature of `block` against the cached proposers of its epoch on the branch
+    /// with the given shuffling root.
+    ///
+    /// Returns `None` if the proposers are not cached.
+    fn verify_signature_with_cached_proposers(
+        &self,
+        block: &BeaconBlock,
+        shuffling_root: Hash256,
+    ) -> Option<bool> {
+        let epoch = block.slot.epoch(self.spec.slots_per_epoch);
+        let cache = self.proposer_cache.lock();
+        let proposers = cache.get(epoch, shuffling_root)?;
+        let pubkey = proposers
+            .pubkeys
+            .get((block.slot - epoch.start_slot(self.spec.slots_per_epoch)).as_usize())?;
+
+        let domain = self
+            .spec
+            .get_domain(epoch, Domain::BeaconBlock, &proposers.fork);
+
+        Some(
+            block
+                .signature
+                .verify(&block.signed_root()[..], domain, pubkey),
+        )
+    }
+
+    /// Adds the proposers of `epoch` to the proposer cache, reading them from `state`, which must
+    /// be in `epoch` with its current epoch cache built.
+    fn cache_proposers(
+        &self,
+        state: &BeaconState,
+        epoch: Epoch,
+        shuffling_root: Hash256,
+    ) -> Result<(), Error> {
+        let mut cache = self.proposer_cache.lock();
+        if !cache.contains(epoch, shuffling_root) {
+            cache.insert(
+                epoch,
+                shuffling_root,
+                EpochProposers::from_state(state, &self.spec)?,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns the root of the last block before the first slot of `epoch` on the branch of
+    /// `parent_root`, which fixes the proposers of `epoch` on that branch. Blocks of the genesis
+    /// epoch all share the genesis block.
+    ///
+    /// At most one epoch of ancestors of `parent` is read.
+    fn shuffling_root(
+        &self,
+        parent_root: Hash256,
+        parent: &BeaconBlock,
+        epoch: Epoch,
+    ) -> Result<Hash256, Error> {
+        let start_slot = epoch.start_slot(self.spec.slots_per_epoch);
+
+        let mut root = parent_root;
+        let mut slot = parent.slot;
+        let mut previous_root = parent.previous_block_root;
+        while slot >= start_slot && slot > self.spec.genesis_slot {
+            let block = self
+                .block_store
+                .get_deserialized(&previous_root)?
+                .ok_or_else(|| Error::MissingBeaconBlock(previous_root))?;
+            root = previous_root;
+            slot = block.slot;
+            previous_root = block.previous_block_root;
+        }
+
+        Ok(root)
+    }
+
+    /// Verifies the signature of `block` against its proposer in `state`, which must be in the
+    /// same epoch as the block.
+    fn block_signature_is_valid(
+        &self,
+        state: &mut BeaconState,
+        block: &BeaconBlock,
+    ) -> Result<bool, Error> {
+        state.build_epoch_cache(RelativeEpoch::Current, &self.spec)?;
+
+        signature_is_valid(verify_block_signature(state, block, &self.spec))
+    }
+
     /// Produce a new block at the present slot.
     ///
     /// The produced block will not be inherently valid, it must be signed by a block producer.
@@ -670,18 +1745,76 @@ where
     ) -> Result<(BeaconBlock, BeaconState), BlockProductionError> {
         debug!("Producing block at slot {}...", self.state.read().slot);
 
-        let mut state = self.state.read().clone();
-
-        state.build_epoch_cache(RelativeEpoch::Current, &self.spec)?;
-
-        trace!("Finding attestations for new block...");
+        let state = self.state.read().clone();
 
         let previous_block_root = *state
             .get_block_root(state.slot - 1, &self.spec)
             .map_err(|_| BlockProductionError::UnableToGetBlockRootFromState)?;
 
+        self.produce_block_on_state(state, previous_block_root, randao_reveal)
+    }
+
+    /// Produce a new block at `slot` which is a child of the block with `parent_root`, which need
+    /// not be the canonical head. This allows forks to be built deliberately (e.g., for testing
+    /// re-orgs).
+    ///
+    /// The post-state of the parent is advanced to `slot` and only the operations which are valid
+    /// on that branch are included. As with `produce_block`, the block must be signed by a block
+    /// producer.
+    pub fn produce_block_on(
+        &self,
+        parent_root: Hash256,
+        slot: Slot,
+        randao_reveal: Signature,
+    ) -> Result<(BeaconBlock, BeaconState), BlockProductionError> {
+        debug!(
+            "Producing block at slot {} on parent {}...",
+            slot, parent_root
+        );
+
+        let parent = self
+            .block_store
+            .get_deserialized(&parent_root)
+            .map_err(Error::from)?
+            .ok_or_else(|| BlockProductionError::UnknownParent(parent_root))?;
+
+        if slot <= parent.slot {
+            return Err(BlockProductionError::SlotNotAfterParent {
+                parent_slot: parent.slot,
+                slot,
+            });
+        }
+
+        let mut state = self
+            .get_block_state(&parent_root, &parent)?
+            .ok_or_else(|| Error::MissingBeaconState(parent.state_root))?;
+
+        for _ in state.slot.as_u64()..slot.as_u64() {
+            // Ensure the next epoch state caches are built in case of an epoch transition.
+            state.build_epoch_cache(RelativeEpoch::NextWithoutRegistryChange, &self.spec)?;
+            state.build_epoch_cache(RelativeEpoch::NextWithRegistryChange, &self.spec)?;
+
+            per_slot_processing(&mut state, &self.spec)?;
+        }
+
+        self.produce_block_on_state(state, parent_root, randao_reveal)
+    }
+
+    /// Produce a block on `state`, which has been advanced to the slot of the new block, filling
+    /// it with the operations from the `op_pool` that are valid for `state`.
+    fn produce_block_on_state(
+        &self,
+        mut state: BeaconState,
+        previous_block_root: Hash256,
+        randao_reveal: Signature,
+    ) -> Result<(BeaconBlock, BeaconState), BlockProductionError> {
+        state.build_epoch_cache(RelativeEpoch::Current, &self.spec)?;
+
+        trace!("Finding attestations for new block...");
+
         let (proposer_slashings, attester_slashings) =
-            self.op_pool.get_slashings(&*self.state.read(), &self.spec);
+            self.op_pool.get_slashings(&state, &self.spec);
+        let (eth1_data, deposits) = self.eth1_data_and_deposits(&state);
 
         let mut block = BeaconBlock {
             slot: state.slot,
@@ -690,21 +1823,13 @@ where
             signature: self.spec.empty_signature.clone(), // To be completed by a validator.
             body: BeaconBlockBody {
                 randao_reveal,
-                eth1_data: Eth1Data {
-                    // TODO: replace with real data
-                    deposit_root: Hash256::zero(),
-                    block_hash: Hash256::zero(),
-                },
+                eth1_data,
                 proposer_slashings,
                 attester_slashings,
-                attestations: self
-                    .op_pool
-                    .get_attestations(&*self.state.read(), &self.spec),
-                deposits: self.op_pool.get_deposits(&*self.state.read(), &self.spec),
-                voluntary_exits: self
-                    .op_pool
-                    .get_voluntary_exits(&*self.state.read(), &self.spec),
-                transfers: self.op_pool.get_transfers(&*self.state.read(), &self.spec),
+                attestations: self.op_pool.get_attestations(&state, &self.spec),
+                deposits,
+                voluntary_exits: self.op_pool.get_voluntary_exits(&state, &self.spec),
+                transfers: self.op_pool.get_transfers(&state, &self.spec),
             },
         };
 
@@ -722,35 +1847,106 @@ where
         Ok((block, state))
     }
 
-    // TODO: Left this as is, modify later
+    /// Returns the `Eth1Data` vote and the deposits for a block produced on `state`.
+    ///
+    /// Failing to read the eth1 chain does not prevent block production, the block then repeats
+    /// the present `latest_eth1_data` and includes no deposits.
+    fn eth1_data_and_deposits(&self, state: &BeaconState) -> (Eth1Data, Vec<Deposit>) {
+        let mut eth1_chain = self.eth1_chain.write();
+
+        let eth1_chain = match eth1_chain.as_mut() {
+            Some(eth1_chain) => eth1_chain,
+            None => {
+                return (
+                    state.latest_eth1_data.clone(),
+                    self.op_pool.get_deposits(state, &self.spec),
+                )
+            }
+        };
+
+        if let Err(e) = eth1_chain.update_deposit_cache() {
+            warn!("Unable to update the deposit cache: {:?}", e);
+        }
+
+        let eth1_data = eth1_chain
+            .eth1_data_for_block_production(state)
+            .unwrap_or_else(|e| {
+                warn!("Unable to vote for eth1 data: {:?}", e);
+                state.latest_eth1_data.clone()
+            });
+
+        let deposits = eth1_chain
+            .deposits_for_block_production(state, &self.spec)
+            .unwrap_or_else(|e| {
+                warn!("Unable to get deposits for block production: {:?}", e);
+                vec![]
+            });
+
+        (eth1_data, deposits)
+    }
+
+    /// Runs the fork choice rule and, if it selects a new head, updates the canonical head,
+    /// `state` and the canonical root index.
+    ///
+    /// A change of head which does not simply extend the present head is logged as a re-org.
     pub fn fork_choice(&self) -> Result<(), Error> {
-        let present_head = self.finalized_head().beacon_block_root;
+        {
+            // Hold the fork choice lock until the head is updated, so concurrent calls apply
+            // their results in the order they were found.
+            let mut fork_choice = self.fork_choice.write();
 
-        let new_head = self
-            .fork_choice
-            .write()
-            .find_head(&present_head, &self.spec)?;
+            let new_head = fork_choice.find_head(&*self.justified_checkpoint(), &self.spec)?;
 
-        if new_head != present_head {
-            let block = self
-                .block_store
-                .get_deserialized(&new_head)?
-                .ok_or_else(|| Error::MissingBeaconBlock(new_head))?;
-            let block_root = block.canonical_root();
+            let (old_head, old_head_slot) = {
+                let head = self.head();
+                (head.beacon_block_root, head.beacon_block.slot)
+            };
 
-            let state = self
-                .state_store
-                .get_deserialized(&block.state_root)?
-                .ok_or_else(|| Error::MissingBeaconState(block.state_root))?;
-            let state_root = state.canonical_root();
+            if new_head != old_head {
+                let block = self
+                    .block_store
+                    .get_deserialized(&new_head)?
+                    .ok_or_else(|| Error::MissingBeaconBlock(new_head))?;
+
+                let state = self
+                    .get_block_state(&new_head, &block)?
+                    .ok_or_else(|| Error::MissingBeaconState(block.state_root))?;
+                let state_root = block.state_root;
+
+                // Update the canonical root index and the persisted chain together.
+                let mut persisted = self.persisted_beacon_chain();
+                persisted.canonical_head_block_root = new_head;
+
+                let mut batch = DBBatch::new();
+                let ancestor_slot =
+                    self.batch_update_canonical_roots(&mut batch, &block, new_head, old_head_slot)?;
+                let reorg_depth = self.reorg_depth(ancestor_slot, old_head_slot)?;
+                self.chain_metadata_store
+                    .batch_put(&mut batch, &ssz_encode(&persisted)[..]);
+                self.block_store.write(batch)?;
+
+                if reorg_depth > 0 {
+                    info!(
+                        "Beacon chain re-org from {} (slot {}) to {} (slot {}), depth {}",
+                        old_head, old_head_slot, new_head, block.slot, reorg_depth
+                    );
+                } else {
+                    debug!("New head {} at slot {}", new_head, block.slot);
+                }
 
-            self.update_canonical_head(block, block_root, state.clone(), state_root);
+                let slot = block.slot;
+                self.update_canonical_head_and_state(block, new_head, state, state_root)?;
 
-            // Update the canonical `BeaconState`.
-            self.update_state(state)?;
+                self.events.publish(BeaconChainEvent::HeadChanged {
+                    old_head,
+                    new_head,
+                    slot,
+                    reorg_depth,
+                });
+            }
         }
 
-        Ok(())
+        self.update_finalization()
     }
 
     /// Returns `true` if the given block root has not been processed.
@@ -789,8 +1985,7 @@ where
                 })?;
             let beacon_state_root = beacon_block.state_root;
             let beacon_state = self
-                .state_store
-                .get_deserialized(&beacon_state_root)?
+                .get_block_state(&beacon_block_root, &beacon_block)?
                 .ok_or_else(|| {
                     Error::DBInconsistent(format!("Missing state {}", beacon_state_root))
                 })?;
@@ -812,6 +2007,42 @@ where
     }
 }
 
+/// The result of pruning abandoned forks from the database.
+#[derive(Default)]
+struct PrunedForks {
+    /// The roots of all blocks which were pruned.
+    block_roots: Vec<Hash256>,
+    /// The heads which remain after pruning.
+    heads: Vec<Hash256>,
+    /// The number of states which were pruned.
+    states: usize,
+    /// The total size of the pruned blocks and states, in bytes.
+    bytes: usize,
+}
+
+/// Returns `true` if the body of `block` contains more of some operation than is permitted in a
+/// single block.
+fn exceeds_operation_limits(block: &BeaconBlock, spec: &ChainSpec) -> bool {
+    let body = &block.body;
+
+    body.proposer_slashings.len() as u64 > spec.max_proposer_slashings
+        || body.attester_slashings.len() as u64 > spec.max_attester_slashings
+        || body.attestations.len() as u64 > spec.max_attestations
+        || body.deposits.len() as u64 > spec.max_deposits
+        || body.voluntary_exits.len() as u64 > spec.max_voluntary_exits
+        || body.transfers.len() as u64 > spec.max_transfers
+}
+
+/// Maps the result of `verify_block_signature` to `Ok(true)` if the signature is valid,
+/// `Ok(false)` if it is not, or an error if the proposer could not be determined.
+fn signature_is_valid(result: Result<(), BlockProcessingError>) -> Result<bool, Error> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(BlockProcessingError::Invalid(_)) => Ok(false),
+        Err(BlockProcessingError::BeaconStateError(e)) => Err(e.into()),
+    }
+}
+
 impl From<DBError> for Error {
     fn from(e: DBError) -> Error {
         Error::DBError(e.message)
@@ -25,16 +25,24 @@ pub enum BeaconChainError {
     MissingBeaconBlock(Hash256),
     MissingBeaconState(Hash256),
     SlotProcessingError(SlotProcessingError),
+    BlockProcessingError(BlockProcessingError),
 }
 
 easy_from_to!(SlotProcessingError, BeaconChainError);
+easy_from_to!(BlockProcessingError, BeaconChainError);
 
 #[derive(Debug, PartialEq)]
 pub enum BlockProductionError {
     UnableToGetBlockRootFromState,
+    UnknownParent(Hash256),
+    SlotNotAfterParent { parent_slot: Slot, slot: Slot },
     BlockProcessingError(BlockProcessingError),
+    SlotProcessingError(SlotProcessingError),
     BeaconStateError(BeaconStateError),
+    BeaconChainError(BeaconChainError),
 }
 
 easy_from_to!(BlockProcessingError, BlockProductionError);
+easy_from_to!(SlotProcessingError, BlockProductionError);
 easy_from_to!(BeaconStateError, BlockProductionError);
+easy_from_to!(BeaconChainError, BlockProductionError);
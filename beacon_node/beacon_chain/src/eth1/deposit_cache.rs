@@ -0,0 +1,246 @@
+use super::{DepositLog, Eth1Error};
+use hashing::hash;
+use state_processing::per_block_processing::get_serialized_deposit_data;
+use types::{Deposit, Hash256};
+
+/// The deposits made to the deposit contract, in index order, with the leaves of the deposit
+/// Merkle tree.
+///
+/// Allows the deposit root (and a proof for each deposit) to be computed for any prefix of the
+/// deposits, which is required to include deposits against an `Eth1Data` vote that lags behind
+/// the eth1 head.
+pub struct DepositCache {
+    logs: Vec<DepositLog>,
+    leaves: Vec<Hash256>,
+    /// `zero_hashes[i]` is the root of an empty tree of height `i`.
+    zero_hashes: Vec<Hash256>,
+}
+
+impl DepositCache {
+    /// Returns an empty cache for a deposit tree of the given depth (see
+    /// `ChainSpec::deposit_contract_tree_depth`).
+    pub fn new(tree_depth: usize) -> Self {
+        let mut zero_hashes = vec![Hash256::zero()];
+        for height in 0..tree_depth {
+            zero_hashes.push(hash_concat(zero_hashes[height], zero_hashes[height]));
+        }
+
+        Self {
+            logs: vec![],
+            leaves: vec![],
+            zero_hashes,
+        }
+    }
+
+    /// The number of deposits in the cache.
+    pub fn len(&self) -> usize {
+        self.logs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.logs.is_empty()
+    }
+
+    /// Returns the most recent deposit.
+    pub fn last(&self) -> Option<&DepositLog> {
+        self.logs.last()
+    }
+
+    /// Adds the deposit with the next index to the cache. Deposits which are already known are
+    /// ignored.
+    pub fn insert_log(&mut self, log: DepositLog) -> Result<(), Eth1Error> {
+        let next_index = self.logs.len() as u64;
+
+        if log.index < next_index {
+            if self.logs[log.index as usize] != log {
+                return Err(Eth1Error::ConflictingDeposit(log.index));
+            }
+            return Ok(());
+        }
+        if log.index > next_index {
+            return Err(Eth1Error::NonConsecutiveDeposit {
+                expected: next_index,
+                found: log.index,
+            });
+        }
+
+        let leaf = hash(&get_serialized_deposit_data(&Deposit {
+            proof: vec![].into(),
+            index: log.index,
+            deposit_data: log.deposit_data.clone(),
+        }));
+        self.leaves.push(Hash256::from_slice(&leaf));
+        self.logs.push(log);
+
+        Ok(())
+    }
+
+    /// Returns the deposit root of the contract after the first `deposit_count` deposits.
+    pub fn deposit_root(&self, deposit_count: u64) -> Result<Hash256, Eth1Error> {
+        let levels = self.tree_levels(deposit_count)?;
+        let depth = self.zero_hashes.len() - 1;
+
+        Ok(levels[depth]
+            .first()
+            .cloned()
+            .unwrap_or(self.zero_hashes[depth]))
+    }
+
+    /// Returns the deposits with indices in `start..end`, each with a proof against the deposit
+    /// root after the first `deposit_count` deposits.
+    pub fn get_deposits(
+        &self,
+        start: u64,
+        end: u64,
+        deposit_count: u64,
+    ) -> Result<Vec<Deposit>, Eth1Error> {
+        if end > deposit_count {
+            return Err(Eth1Error::UnknownDeposits {
+                known: deposit_count,
+                requested: end,
+            });
+        }
+
+        let levels = self.tree_levels(deposit_count)?;
+
+        Ok((start..end)
+            .map(|index| {
+                let proof: Vec<Hash256> = (0..self.zero_hashes.len() - 1)
+                    .map(|height| {
+                        let sibling = (index as usize >> height) ^ 1;
+                        levels[height]
+                            .get(sibling)
+                            .cloned()
+                            .unwrap_or(self.zero_hashes[height])
+                    })
+                    .collect();
+
+                Deposit {
+                    proof: proof.into(),
+                    index,
+                    deposit_data: self.logs[index as usize].deposit_data.clone(),
+                }
+            })
+            .collect())
+    }
+
+    /// Returns each level of the tree of the first `deposit_count` leaves, from the leaves to the
+    /// root. The roots of empty subtrees are omitted.
+    fn tree_levels(&self, deposit_count: u64) -> Result<Vec<Vec<Hash256>>, Eth1Error> {
+        if deposit_count > self.leaves.len() as u64 {
+            return Err(Eth1Error::UnknownDeposits {
+                known: self.leaves.len() as u64,
+                requested: deposit_count,
+            });
+        }
+
+        let mut levels = vec![self.leaves[..deposit_count as usize].to_vec()];
+        for height in 0..self.zero_hashes.len() - 1 {
+            let level = levels[height]
+                .chunks(2)
+                .map(|pair| {
+                    let right = pair.get(1).cloned().unwrap_or(self.zero_hashes[height]);
+                    hash_concat(pair[0], right)
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        Ok(levels)
+    }
+}
+
+fn hash_concat(left: Hash256, right: Hash256) -> Hash256 {
+    let mut input = left.as_bytes().to_vec();
+    input.extend_from_slice(right.as_bytes());
+    Hash256::from_slice(&hash(&input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merkle_proof::verify_merkle_proof;
+    use types::test_utils::{SeedableRng, TestRandom, XorShiftRng};
+    use types::DepositData;
+
+    const TREE_DEPTH: usize = 32;
+
+    fn cache_with_deposits(count: u64) -> DepositCache {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let mut cache = DepositCache::new(TREE_DEPTH);
+        for index in 0..count {
+            cache
+                .insert_log(DepositLog {
+                    index,
+                    block_number: index,
+                    deposit_data: DepositData::random_for_test(rng),
+                })
+                .unwrap();
+        }
+        cache
+    }
+
+    #[test]
+    fn empty_root_is_the_zero_hash_of_the_tree_depth() {
+        let cache = DepositCache::new(TREE_DEPTH);
+
+        assert_eq!(
+            cache.deposit_root(0).unwrap(),
+            cache.zero_hashes[TREE_DEPTH]
+        );
+    }
+
+    #[test]
+    fn proofs_verify_against_each_deposit_root() {
+        let cache = cache_with_deposits(5);
+
+        for deposit_count in 1..=5 {
+            let root = cache.deposit_root(deposit_count).unwrap();
+
+            for deposit in cache.get_deposits(0, deposit_count, deposit_count).unwrap() {
+                let leaf = Hash256::from_slice(&hash(&get_serialized_deposit_data(&deposit)));
+                assert!(verify_merkle_proof(
+                    leaf,
+                    &deposit.proof,
+                    TREE_DEPTH,
+                    deposit.index as usize,
+                    root
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_deposits_out_of_order() {
+        let mut cache = cache_with_deposits(2);
+        let mut log = cache.last().cloned().unwrap();
+
+        // Known deposits are ignored.
+        assert_eq!(cache.insert_log(log.clone()), Ok(()));
+
+        log.index = 3;
+        assert_eq!(
+            cache.insert_log(log.clone()),
+            Err(Eth1Error::NonConsecutiveDeposit {
+                expected: 2,
+                found: 3
+            })
+        );
+
+        log.index = 0;
+        assert_eq!(cache.insert_log(log), Err(Eth1Error::ConflictingDeposit(0)));
+    }
+
+    #[test]
+    fn rejects_unknown_deposits() {
+        let cache = cache_with_deposits(2);
+
+        assert_eq!(
+            cache.get_deposits(0, 3, 3),
+            Err(Eth1Error::UnknownDeposits {
+                known: 2,
+                requested: 3
+            })
+        );
+    }
+}
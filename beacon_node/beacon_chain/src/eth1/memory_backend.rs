@@ -0,0 +1,167 @@
+use super::{DepositCache, DepositLog, Eth1Backend, Eth1Block, Eth1Error};
+use hashing::hash;
+use parking_lot::RwLock;
+use serde_derive::{Deserialize, Serialize};
+use ssz::ssz_encode;
+use std::fs::File;
+use std::path::Path;
+use types::{ChainSpec, DepositData, Hash256};
+
+/// The contents of an eth1 chain file (see `MemoryEth1Backend::from_file`).
+#[derive(Serialize, Deserialize)]
+struct Eth1ChainFile {
+    blocks: Vec<Eth1Block>,
+    deposit_logs: Vec<DepositLog>,
+}
+
+struct MemoryEth1Chain {
+    /// The blocks of the chain, indexed by block number.
+    blocks: Vec<Eth1Block>,
+    deposit_logs: Vec<DepositLog>,
+    /// Deposits which will be included in the next mined block.
+    pending_deposits: Vec<DepositData>,
+    deposit_cache: DepositCache,
+}
+
+/// An `Eth1Backend` which holds the eth1 chain in memory, standing in for an eth1 node during
+/// testing and simulation.
+///
+/// The chain is either built block by block (see `add_deposit` and `mine_block`) or loaded from
+/// a file (see `from_file`).
+pub struct MemoryEth1Backend {
+    chain: RwLock<MemoryEth1Chain>,
+}
+
+impl MemoryEth1Backend {
+    /// Returns a backend with a single block (number zero) and no deposits.
+    pub fn new(spec: &ChainSpec) -> Self {
+        let backend = Self {
+            chain: RwLock::new(MemoryEth1Chain {
+                blocks: vec![],
+                deposit_logs: vec![],
+                pending_deposits: vec![],
+                deposit_cache: DepositCache::new(spec.deposit_contract_tree_depth as usize),
+            }),
+        };
+        backend.mine_block();
+        backend
+    }
+
+    /// Loads the chain from a JSON file with a list of `blocks` (in order of block number) and a
+    /// list of `deposit_logs` (in order of index).
+    pub fn from_file(path: &Path, spec: &ChainSpec) -> Result<Self, Eth1Error> {
+        let file = File::open(path).map_err(|e| {
+            Eth1Error::BackendError(format!("Unable to open {}: {:?}", path.display(), e))
+        })?;
+        let contents: Eth1ChainFile = serde_json::from_reader(file).map_err(|e| {
+            Eth1Error::BackendError(format!("Unable to parse {}: {:?}", path.display(), e))
+        })?;
+
+        if let Some((index, _)) = contents
+            .blocks
+            .iter()
+            .enumerate()
+            .find(|(index, block)| block.number != *index as u64)
+        {
+            return Err(Eth1Error::BackendError(format!(
+                "Block at position {} of {} is not block number {}",
+                index,
+                path.display(),
+                index
+            )));
+        }
+
+        let mut deposit_cache = DepositCache::new(spec.deposit_contract_tree_depth as usize);
+        for log in &contents.deposit_logs {
+            deposit_cache.insert_log(log.clone())?;
+        }
+
+        Ok(Self {
+            chain: RwLock::new(MemoryEth1Chain {
+                blocks: contents.blocks,
+                deposit_logs: contents.deposit_logs,
+                pending_deposits: vec![],
+                deposit_cache,
+            }),
+        })
+    }
+
+    /// Queues a deposit for inclusion in the next mined block.
+    pub fn add_deposit(&self, deposit_data: DepositData) {
+        self.chain.write().pending_deposits.push(deposit_data);
+    }
+
+    /// Adds a block containing all queued deposits to the chain.
+    pub fn mine_block(&self) -> Eth1Block {
+        let mut chain = self.chain.write();
+        let number = chain.blocks.len() as u64;
+
+        for deposit_data in std::mem::replace(&mut chain.pending_deposits, vec![]) {
+            let log = DepositLog {
+                index: chain.deposit_logs.len() as u64,
+                block_number: number,
+                deposit_data,
+            };
+            chain
+                .deposit_cache
+                .insert_log(log.clone())
+                .expect("Deposits are added in order");
+            chain.deposit_logs.push(log);
+        }
+
+        let deposit_count = chain.deposit_logs.len() as u64;
+        let block = Eth1Block {
+            hash: Hash256::from_slice(&hash(&ssz_encode(&number))),
+            number,
+            deposit_root: chain
+                .deposit_cache
+                .deposit_root(deposit_count)
+                .expect("All deposits are in the cache"),
+            deposit_count,
+        };
+        chain.blocks.push(block.clone());
+
+        block
+    }
+
+    /// Mines `count` blocks, returning the last.
+    pub fn mine_blocks(&self, count: usize) -> Option<Eth1Block> {
+        (0..count).map(|_| self.mine_block()).last()
+    }
+}
+
+impl Eth1Backend for MemoryEth1Backend {
+    fn head_block_number(&self) -> Result<u64, Eth1Error> {
+        match self.chain.read().blocks.last() {
+            Some(block) => Ok(block.number),
+            None => Err(Eth1Error::BackendError(
+                "The eth1 chain is empty".to_string(),
+            )),
+        }
+    }
+
+    fn block_by_number(&self, number: u64) -> Result<Option<Eth1Block>, Eth1Error> {
+        Ok(self.chain.read().blocks.get(number as usize).cloned())
+    }
+
+    fn block_by_hash(&self, hash: &Hash256) -> Result<Option<Eth1Block>, Eth1Error> {
+        Ok(self
+            .chain
+            .read()
+            .blocks
+            .iter()
+            .find(|block| block.hash == *hash)
+            .cloned())
+    }
+
+    fn deposit_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<DepositLog>, Eth1Error> {
+        Ok(self
+            .chain
+            .read()
+            .deposit_logs
+            .iter()
+            .filter(|log| log.block_number >= from_block && log.block_number <= to_block)
+            .cloned()
+            .collect())
+    }
+}
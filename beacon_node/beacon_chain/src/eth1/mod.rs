@@ -0,0 +1,216 @@
+//! Eth1 data voting and deposit inclusion for block production.
+//!
+//! The eth1 chain (i.e., the deposit contract and the blocks containing it) is read through an
+//! `Eth1Backend`. Deposits are accumulated in a `DepositCache` so they can be included in blocks
+//! with Merkle proofs against the `Eth1Data` agreed upon by the beacon chain.
+mod deposit_cache;
+mod memory_backend;
+
+pub use self::deposit_cache::DepositCache;
+pub use self::memory_backend::MemoryEth1Backend;
+
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::min;
+use std::sync::Arc;
+use types::{BeaconState, ChainSpec, Deposit, DepositData, Eth1Data, Hash256};
+
+/// The number of eth1 blocks behind the eth1 head at which eth1 data is voted for, so the votes
+/// are unlikely to be affected by eth1 re-orgs.
+pub const DEFAULT_ETH1_FOLLOW_DISTANCE: u64 = 1_024;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Eth1Error {
+    /// The backend was unable to read from the eth1 chain.
+    BackendError(String),
+    /// A deposit was received out of order.
+    NonConsecutiveDeposit { expected: u64, found: u64 },
+    /// A deposit differs from a previously received deposit with the same index.
+    ConflictingDeposit(u64),
+    /// More deposits were requested than are known.
+    UnknownDeposits { known: u64, requested: u64 },
+}
+
+/// A block of the eth1 chain.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Eth1Block {
+    pub hash: Hash256,
+    pub number: u64,
+    /// The root of the deposit contract at the end of the block.
+    pub deposit_root: Hash256,
+    /// The number of deposits made to the deposit contract up to and including this block.
+    pub deposit_count: u64,
+}
+
+impl Eth1Block {
+    pub fn eth1_data(&self) -> Eth1Data {
+        Eth1Data {
+            deposit_root: self.deposit_root,
+            block_hash: self.hash,
+        }
+    }
+}
+
+/// A `Deposit` log of the deposit contract.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DepositLog {
+    pub index: u64,
+    /// The number of the eth1 block which contains the deposit.
+    pub block_number: u64,
+    pub deposit_data: DepositData,
+}
+
+/// A source of eth1 blocks and deposit contract logs (e.g., an eth1 node).
+///
+/// All methods refer to the canonical eth1 chain as seen by the backend.
+pub trait Eth1Backend: Send + Sync {
+    /// Returns the number of the head block.
+    fn head_block_number(&self) -> Result<u64, Eth1Error>;
+
+    fn block_by_number(&self, number: u64) -> Result<Option<Eth1Block>, Eth1Error>;
+
+    fn block_by_hash(&self, hash: &Hash256) -> Result<Option<Eth1Block>, Eth1Error>;
+
+    /// Returns the deposit logs in the blocks `from_block..=to_block`, ordered by index.
+    fn deposit_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<DepositLog>, Eth1Error>;
+}
+
+impl<T: Eth1Backend> Eth1Backend for Arc<T> {
+    fn head_block_number(&self) -> Result<u64, Eth1Error> {
+        (**self).head_block_number()
+    }
+
+    fn block_by_number(&self, number: u64) -> Result<Option<Eth1Block>, Eth1Error> {
+        (**self).block_by_number(number)
+    }
+
+    fn block_by_hash(&self, hash: &Hash256) -> Result<Option<Eth1Block>, Eth1Error> {
+        (**self).block_by_hash(hash)
+    }
+
+    fn deposit_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<DepositLog>, Eth1Error> {
+        (**self).deposit_logs(from_block, to_block)
+    }
+}
+
+/// Reads the eth1 chain from an `Eth1Backend` to vote for `Eth1Data` and to select the deposits
+/// for new blocks.
+pub struct Eth1Chain {
+    backend: Box<dyn Eth1Backend>,
+    deposit_cache: DepositCache,
+    /// The last eth1 block whose deposit logs are in the `deposit_cache`.
+    last_processed_block: Option<u64>,
+    follow_distance: u64,
+}
+
+impl Eth1Chain {
+    pub fn new(backend: Box<dyn Eth1Backend>, follow_distance: u64, spec: &ChainSpec) -> Self {
+        Self {
+            backend,
+            deposit_cache: DepositCache::new(spec.deposit_contract_tree_depth as usize),
+            last_processed_block: None,
+            follow_distance,
+        }
+    }
+
+    /// Adds the deposit logs of all eth1 blocks up to the head to the `deposit_cache`.
+    pub fn update_deposit_cache(&mut self) -> Result<(), Eth1Error> {
+        let head = self.backend.head_block_number()?;
+        let from_block = self.last_processed_block.map_or(0, |block| block + 1);
+
+        if from_block > head {
+            return Ok(());
+        }
+
+        for log in self.backend.deposit_logs(from_block, head)? {
+            self.deposit_cache.insert_log(log)?;
+        }
+        self.last_processed_block = Some(head);
+
+        Ok(())
+    }
+
+    pub fn deposit_cache(&self) -> &DepositCache {
+        &self.deposit_cache
+    }
+
+    /// Returns the `Eth1Data` a block producer should vote for on top of `state`.
+    ///
+    /// Votes already in `state.eth1_data_votes` are candidates if their block is on the canonical
+    /// eth1 chain, at least `follow_distance` blocks behind the head and newer than
+    /// `state.latest_eth1_data`. The candidate with the most votes is chosen, ties going to the
+    /// newest block. If there are no candidates, the block `follow_distance` behind the head is
+    /// voted for.
+    ///
+    /// Spec v0.5.1
+    pub fn eth1_data_for_block_production(
+        &self,
+        state: &BeaconState,
+    ) -> Result<Eth1Data, Eth1Error> {
+        let head = self.backend.head_block_number()?;
+        let latest_number = self
+            .backend
+            .block_by_hash(&state.latest_eth1_data.block_hash)?
+            .map(|block| block.number);
+
+        let mut best: Option<(u64, u64, Eth1Data)> = None;
+        for vote in &state.eth1_data_votes {
+            let block = match self.backend.block_by_hash(&vote.eth1_data.block_hash)? {
+                Some(block) => block,
+                None => continue,
+            };
+
+            let is_candidate = block.number + self.follow_distance <= head
+                && latest_number.map_or(true, |latest| block.number > latest)
+                && block.deposit_root == vote.eth1_data.deposit_root;
+
+            let is_better = best.as_ref().map_or(true, |(count, number, _)| {
+                (vote.vote_count, block.number) > (*count, *number)
+            });
+
+            if is_candidate && is_better {
+                best = Some((vote.vote_count, block.number, vote.eth1_data.clone()));
+            }
+        }
+
+        if let Some((_, _, eth1_data)) = best {
+            return Ok(eth1_data);
+        }
+
+        match self
+            .backend
+            .block_by_number(head.saturating_sub(self.follow_distance))?
+        {
+            Some(block) => Ok(block.eth1_data()),
+            None => Ok(state.latest_eth1_data.clone()),
+        }
+    }
+
+    /// Returns the deposits which should be included in a block on top of `state`, in order and
+    /// with proofs against `state.latest_eth1_data`.
+    ///
+    /// No deposits are returned if the deposit count of `state.latest_eth1_data` is unknown.
+    pub fn deposits_for_block_production(
+        &self,
+        state: &BeaconState,
+        spec: &ChainSpec,
+    ) -> Result<Vec<Deposit>, Eth1Error> {
+        let deposit_count = match self
+            .backend
+            .block_by_hash(&state.latest_eth1_data.block_hash)?
+        {
+            Some(ref block) if block.deposit_root == state.latest_eth1_data.deposit_root => {
+                block.deposit_count
+            }
+            _ => return Ok(vec![]),
+        };
+
+        let start = state.deposit_index;
+        let end = min(deposit_count, start + spec.max_deposits);
+
+        if start >= end {
+            return Ok(vec![]);
+        }
+
+        self.deposit_cache.get_deposits(start, end, deposit_count)
+    }
+}
@@ -0,0 +1,59 @@
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use parking_lot::Mutex;
+use types::{AttestationData, Hash256, Slot};
+
+/// A change to the `BeaconChain`, published to all subscribers (see `BeaconChain::subscribe_events`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum BeaconChainEvent {
+    /// A valid block was stored. It may or may not have become the head.
+    BlockImported { block_root: Hash256, slot: Slot },
+    /// The fork choice selected a new head.
+    ///
+    /// `reorg_depth` is the number of blocks removed from the canonical chain, it is zero if the
+    /// new head descends from the old head.
+    HeadChanged {
+        old_head: Hash256,
+        new_head: Hash256,
+        slot: Slot,
+        reorg_depth: usize,
+    },
+    /// The finalized block advanced.
+    Finalized { block_root: Hash256, slot: Slot },
+    /// A valid attestation was received and added to the operation pool.
+    AttestationReceived(AttestationData),
+    /// An operation (other than an attestation) was added to the operation pool.
+    OperationPooled(PooledOperation),
+}
+
+/// The kind of an operation added to the operation pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PooledOperation {
+    Deposit,
+    VoluntaryExit,
+    Transfer,
+    ProposerSlashing,
+    AttesterSlashing,
+}
+
+/// Delivers each published event to every subscriber.
+///
+/// Subscribers receive all events published after they subscribed. A subscriber is removed once
+/// its `Receiver` is dropped.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<BeaconChainEvent>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Receiver<BeaconChainEvent> {
+        let (sender, receiver) = unbounded();
+        self.subscribers.lock().push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, event: BeaconChainEvent) {
+        self.subscribers
+            .lock()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
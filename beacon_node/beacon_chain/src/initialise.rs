@@ -3,9 +3,9 @@
 // testnet. These are examples. Also. there is code duplication which can/should be cleaned up.
 
 use crate::BeaconChain;
-use db::stores::{BeaconBlockStore, BeaconStateStore};
-use db::{DiskDB, MemoryDB};
-use fork_choice::BitwiseLMDGhost;
+use db::stores::{BeaconBlockStore, BeaconStateStore, ChainMetadataStore, COLUMNS};
+use db::{DiskDB, DiskDBConfig, MemoryDB};
+use fork_choice::{ForkChoice, ForkChoiceAlgorithm};
 use slot_clock::SystemTimeSlotClock;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -18,15 +18,46 @@ use types::{BeaconBlock, ChainSpec, Hash256};
 pub fn initialise_beacon_chain(
     spec: &ChainSpec,
     db_name: Option<&PathBuf>,
-) -> Arc<BeaconChain<DiskDB, SystemTimeSlotClock, BitwiseLMDGhost<DiskDB>>> {
+    db_config: &DiskDBConfig,
+    epochs_per_cold_state: u64,
+    fork_choice_algorithm: &ForkChoiceAlgorithm,
+) -> Arc<BeaconChain<DiskDB, SystemTimeSlotClock, Box<dyn ForkChoice>>> {
     // set up the db
-    let db = Arc::new(DiskDB::open(
-        db_name.expect("Database directory must be included"),
-        None,
-    ));
+    //TODO: Handle error correctly
+    let db = Arc::new(
+        DiskDB::open(
+            db_name.expect("Database directory must be included"),
+            &COLUMNS,
+            db_config,
+        )
+        .expect("Unable to open database"),
+    );
 
     let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
-    let state_store = Arc::new(BeaconStateStore::new(db.clone()));
+    let state_store = Arc::new(BeaconStateStore::with_epochs_per_cold_state(
+        db.clone(),
+        epochs_per_cold_state,
+    ));
+    let chain_metadata_store = Arc::new(ChainMetadataStore::new(db.clone()));
+
+    // Resume from the database, if a previous run has persisted a chain.
+    //TODO: Handle error correctly
+    let fork_choice = fork_choice_algorithm.build(block_store.clone());
+    if let Some(beacon_chain) = BeaconChain::from_store(
+        state_store.clone(),
+        block_store.clone(),
+        chain_metadata_store.clone(),
+        |genesis_time| {
+            SystemTimeSlotClock::new(spec.genesis_slot, genesis_time, spec.seconds_per_slot)
+                .expect("Unable to load SystemTimeSlotClock")
+        },
+        spec.clone(),
+        fork_choice,
+    )
+    .expect("Terminate if the stored beacon chain cannot be loaded")
+    {
+        return Arc::new(beacon_chain);
+    }
 
     let state_builder = TestingBeaconStateBuilder::from_default_keypairs_file_if_exists(8, &spec);
     let (genesis_state, _keypairs) = state_builder.build();
@@ -42,7 +73,7 @@ pub fn initialise_beacon_chain(
     )
     .expect("Unable to load SystemTimeSlotClock");
     // Choose the fork choice
-    let fork_choice = BitwiseLMDGhost::new(block_store.clone(), state_store.clone());
+    let fork_choice = fork_choice_algorithm.build(block_store.clone());
 
     // Genesis chain
     //TODO: Handle error correctly
@@ -50,6 +81,7 @@ pub fn initialise_beacon_chain(
         BeaconChain::from_genesis(
             state_store.clone(),
             block_store.clone(),
+            chain_metadata_store.clone(),
             slot_clock,
             genesis_state,
             genesis_block,
@@ -64,10 +96,12 @@ pub fn initialise_beacon_chain(
 pub fn initialise_test_beacon_chain(
     spec: &ChainSpec,
     _db_name: Option<&PathBuf>,
-) -> Arc<BeaconChain<MemoryDB, SystemTimeSlotClock, BitwiseLMDGhost<MemoryDB>>> {
+    fork_choice_algorithm: &ForkChoiceAlgorithm,
+) -> Arc<BeaconChain<MemoryDB, SystemTimeSlotClock, Box<dyn ForkChoice>>> {
     let db = Arc::new(MemoryDB::open());
     let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
     let state_store = Arc::new(BeaconStateStore::new(db.clone()));
+    let chain_metadata_store = Arc::new(ChainMetadataStore::new(db.clone()));
 
     let state_builder = TestingBeaconStateBuilder::from_default_keypairs_file_if_exists(8, spec);
     let (genesis_state, _keypairs) = state_builder.build();
@@ -83,7 +117,7 @@ pub fn initialise_test_beacon_chain(
     )
     .expect("Unable to load SystemTimeSlotClock");
     // Choose the fork choice
-    let fork_choice = BitwiseLMDGhost::new(block_store.clone(), state_store.clone());
+    let fork_choice = fork_choice_algorithm.build(block_store.clone());
 
     // Genesis chain
     //TODO: Handle error correctly
@@ -91,6 +125,7 @@ pub fn initialise_test_beacon_chain(
         BeaconChain::from_genesis(
             state_store.clone(),
             block_store.clone(),
+            chain_metadata_store.clone(),
             slot_clock,
             genesis_state,
             genesis_block,
@@ -1,12 +1,22 @@
 mod beacon_chain;
 mod checkpoint;
 mod errors;
+pub mod eth1;
+mod events;
 pub mod initialise;
+mod persisted_beacon_chain;
+mod proposer_cache;
+mod state_cache;
 pub mod test_utils;
 
-pub use self::beacon_chain::{BeaconChain, BlockProcessingOutcome, InvalidBlock, ValidBlock};
+pub use self::beacon_chain::{
+    BeaconChain, BlockProcessingOutcome, InvalidBlock, ValidBlock, MAX_SKIP_SLOTS,
+};
 pub use self::checkpoint::CheckPoint;
 pub use self::errors::{BeaconChainError, BlockProductionError};
+pub use self::events::{BeaconChainEvent, PooledOperation};
+pub use self::persisted_beacon_chain::PersistedBeaconChain;
+pub use self::state_cache::{StateCacheStats, DEFAULT_STATE_CACHE_SIZE};
 pub use db;
 pub use fork_choice;
 pub use parking_lot;
@@ -0,0 +1,37 @@
+use ssz_derive::{Decode, Encode};
+use types::Hash256;
+
+/// The minimal description of a `BeaconChain` which is written to the database so that the chain
+/// can be resumed after a restart.
+///
+/// Blocks and states are not duplicated here; they are loaded from their own stores using the
+/// roots in this struct.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct PersistedBeaconChain {
+    /// The root of the block at the canonical head.
+    pub canonical_head_block_root: Hash256,
+    /// The root of the most recently finalized block.
+    pub finalized_head_block_root: Hash256,
+    /// The roots of all known blocks which do not (yet) have any children. Walking back from each
+    /// of these roots yields every block that fork choice is aware of.
+    pub heads: Vec<Hash256>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssz::{decode, ssz_encode};
+
+    #[test]
+    fn ssz_round_trip() {
+        let persisted = PersistedBeaconChain {
+            canonical_head_block_root: Hash256::from([0xAA; 32]),
+            finalized_head_block_root: Hash256::from([0xBB; 32]),
+            heads: vec![Hash256::from([0xAA; 32]), Hash256::from([0xCC; 32])],
+        };
+
+        let bytes = ssz_encode(&persisted);
+
+        assert_eq!(decode::<PersistedBeaconChain>(&bytes).unwrap(), persisted);
+    }
+}
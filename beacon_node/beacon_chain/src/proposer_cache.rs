@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+use types::{
+    BeaconState, BeaconStateError, ChainSpec, Epoch, Fork, Hash256, PublicKey, RelativeEpoch,
+};
+
+/// The number of epochs of proposers kept by a `ProposerCache`.
+pub const PROPOSER_CACHE_SIZE: usize = 16;
+
+/// The block proposers of every slot of an epoch on some branch of the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochProposers {
+    /// The fork with which the blocks of the epoch are signed.
+    pub fork: Fork,
+    /// The public key of the proposer of each slot of the epoch, in slot order.
+    pub pubkeys: Vec<PublicKey>,
+}
+
+impl EpochProposers {
+    /// Reads the proposers of the current epoch of `state`, which must have its current epoch
+    /// cache built.
+    pub fn from_state(state: &BeaconState, spec: &ChainSpec) -> Result<Self, BeaconStateError> {
+        let pubkeys = state
+            .current_epoch(spec)
+            .slot_iter(spec.slots_per_epoch)
+            .map(|slot| {
+                let index = state.get_beacon_proposer_index(slot, RelativeEpoch::Current, spec)?;
+                Ok(state.validator_registry[index].pubkey.clone())
+            })
+            .collect::<Result<_, BeaconStateError>>()?;
+
+        Ok(Self {
+            fork: state.fork.clone(),
+            pubkeys,
+        })
+    }
+}
+
+/// A cache of the block proposers of recent epochs, allowing block signatures to be verified
+/// without loading and transitioning the parent state of the block.
+///
+/// The proposers of an epoch are fixed by the last block before the first slot of the epoch (the
+/// "shuffling root"), so all blocks of the epoch which descend from that block share an entry.
+/// Entries are keyed by the epoch and its shuffling root, and the oldest entry is evicted first.
+pub struct ProposerCache {
+    capacity: usize,
+    proposers: HashMap<(Epoch, Hash256), EpochProposers>,
+    /// The keys of `proposers`, oldest first.
+    order: VecDeque<(Epoch, Hash256)>,
+}
+
+impl ProposerCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            proposers: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the proposers of `epoch` on the branch with the given shuffling root.
+    pub fn get(&self, epoch: Epoch, shuffling_root: Hash256) -> Option<&EpochProposers> {
+        self.proposers.get(&(epoch, shuffling_root))
+    }
+
+    /// Returns `true` if the proposers of `epoch` on the branch with the given shuffling root are
+    /// known.
+    pub fn contains(&self, epoch: Epoch, shuffling_root: Hash256) -> bool {
+        self.proposers.contains_key(&(epoch, shuffling_root))
+    }
+
+    /// Adds the proposers of `epoch`, evicting the oldest entry if the cache is full.
+    pub fn insert(&mut self, epoch: Epoch, shuffling_root: Hash256, proposers: EpochProposers) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = (epoch, shuffling_root);
+        if self.proposers.insert(key, proposers).is_none() {
+            self.order.push_back(key);
+            while self.order.len() > self.capacity {
+                if let Some(key) = self.order.pop_front() {
+                    self.proposers.remove(&key);
+                }
+            }
+        }
+    }
+}
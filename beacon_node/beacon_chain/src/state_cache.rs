@@ -0,0 +1,103 @@
+use std::collections::{HashMap, VecDeque};
+use types::{BeaconState, Hash256};
+
+/// The number of states kept by a `StateCache` unless configured otherwise.
+pub const DEFAULT_STATE_CACHE_SIZE: usize = 8;
+
+/// A count of the lookups made in a `StateCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StateCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The number of states presently held.
+    pub len: usize,
+    pub capacity: usize,
+}
+
+/// A least-recently-used cache of `BeaconState`s, keyed by state root.
+///
+/// States are expected to be inserted with all of their caches (committee, pubkey and tree hash)
+/// built, sparing the cost of decoding the state and rebuilding its caches each time it is read
+/// from the `BeaconStateStore`.
+pub struct StateCache {
+    capacity: usize,
+    states: HashMap<Hash256, BeaconState>,
+    /// The state roots of `states`, least recently used first.
+    order: VecDeque<Hash256>,
+    hits: u64,
+    misses: u64,
+}
+
+impl StateCache {
+    /// Returns a new cache holding at most `capacity` states. A capacity of zero disables the
+    /// cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            states: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns a copy of the state with the given root, marking it as the most recently used.
+    pub fn get(&mut self, state_root: &Hash256) -> Option<BeaconState> {
+        match self.states.get(state_root) {
+            Some(state) => {
+                self.hits += 1;
+                let state = state.clone();
+                self.touch(state_root);
+                Some(state)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Adds a state to the cache, evicting the least recently used state if the cache is full.
+    pub fn insert(&mut self, state_root: Hash256, state: BeaconState) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.states.insert(state_root, state).is_some() {
+            self.touch(&state_root);
+        } else {
+            self.order.push_back(state_root);
+            self.evict();
+        }
+    }
+
+    /// Changes the maximum number of states held, evicting states if required.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn stats(&self) -> StateCacheStats {
+        StateCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.states.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    fn touch(&mut self, state_root: &Hash256) {
+        if let Some(position) = self.order.iter().position(|root| root == state_root) {
+            self.order.remove(position);
+            self.order.push_back(*state_root);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(state_root) = self.order.pop_front() {
+                self.states.remove(&state_root);
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 pub use crate::{BeaconChain, BeaconChainError, CheckPoint};
 use db::{
-    stores::{BeaconBlockStore, BeaconStateStore},
+    stores::{BeaconBlockStore, BeaconStateStore, ChainMetadataStore},
     MemoryDB,
 };
 use fork_choice::BitwiseLMDGhost;
@@ -21,8 +21,9 @@ impl TestingBeaconChainBuilder {
         let db = Arc::new(MemoryDB::open());
         let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
         let state_store = Arc::new(BeaconStateStore::new(db.clone()));
+        let chain_metadata_store = Arc::new(ChainMetadataStore::new(db.clone()));
         let slot_clock = TestingSlotClock::new(spec.genesis_slot.as_u64());
-        let fork_choice = BitwiseLMDGhost::new(block_store.clone(), state_store.clone());
+        let fork_choice = BitwiseLMDGhost::new(block_store.clone());
 
         let (genesis_state, _keypairs) = self.state_builder.build();
 
@@ -33,6 +34,7 @@ impl TestingBeaconChainBuilder {
         BeaconChain::from_genesis(
             state_store.clone(),
             block_store.clone(),
+            chain_metadata_store.clone(),
             slot_clock,
             genesis_state,
             genesis_block,
@@ -2,15 +2,16 @@ use super::ValidatorHarness;
 use beacon_chain::{BeaconChain, BlockProcessingOutcome};
 pub use beacon_chain::{BeaconChainError, CheckPoint};
 use db::{
-    stores::{BeaconBlockStore, BeaconStateStore},
+    stores::{BeaconBlockStore, BeaconStateStore, ChainMetadataStore},
     MemoryDB,
 };
-use fork_choice::BitwiseLMDGhost;
+use fork_choice::{BitwiseLMDGhost, BlockTree};
 use log::debug;
 use rayon::prelude::*;
 use slot_clock::TestingSlotClock;
+use state_processing::per_slot_processing;
 use std::sync::Arc;
-use tree_hash::TreeHash;
+use tree_hash::{SignedRoot, TreeHash};
 use types::{test_utils::TestingBeaconStateBuilder, *};
 
 type TestingBeaconChain = BeaconChain<MemoryDB, TestingSlotClock, BitwiseLMDGhost<MemoryDB>>;
@@ -26,6 +27,7 @@ pub struct BeaconChainHarness {
     pub beacon_chain: Arc<TestingBeaconChain>,
     pub block_store: Arc<BeaconBlockStore<MemoryDB>>,
     pub state_store: Arc<BeaconStateStore<MemoryDB>>,
+    pub chain_metadata_store: Arc<ChainMetadataStore<MemoryDB>>,
     pub validators: Vec<ValidatorHarness>,
     pub spec: Arc<ChainSpec>,
 }
@@ -48,8 +50,9 @@ impl BeaconChainHarness {
         let db = Arc::new(MemoryDB::open());
         let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
         let state_store = Arc::new(BeaconStateStore::new(db.clone()));
+        let chain_metadata_store = Arc::new(ChainMetadataStore::new(db.clone()));
         let slot_clock = TestingSlotClock::new(spec.genesis_slot.as_u64());
-        let fork_choice = BitwiseLMDGhost::new(block_store.clone(), state_store.clone());
+        let fork_choice = BitwiseLMDGhost::new(block_store.clone());
 
         let (mut genesis_state, keypairs) = state_builder.build();
 
@@ -74,6 +77,7 @@ impl BeaconChainHarness {
             BeaconChain::from_genesis(
                 state_store.clone(),
                 block_store.clone(),
+                chain_metadata_store.clone(),
                 slot_clock,
                 genesis_state,
                 genesis_block,
@@ -102,6 +106,7 @@ impl BeaconChainHarness {
             beacon_chain,
             block_store,
             state_store,
+            chain_metadata_store,
             validators,
             spec,
         }
@@ -252,6 +257,97 @@ impl BeaconChainHarness {
         block
     }
 
+    /// Produces a block at `slot` which is a child of `parent_root`, signed by the proposer for
+    /// that slot on the branch of `parent_root`. The block is not processed.
+    ///
+    /// Unlike `produce_block`, the parent need not be the canonical head, allowing forks to be
+    /// built.
+    pub fn produce_block_on(&self, parent_root: Hash256, slot: Slot) -> BeaconBlock {
+        let mut state = self.branch_state(parent_root, slot);
+        state
+            .build_epoch_cache(RelativeEpoch::Current, &self.spec)
+            .unwrap();
+        let proposer = state
+            .get_beacon_proposer_index(slot, RelativeEpoch::Current, &self.spec)
+            .unwrap();
+        let keypair = &self.validators[proposer].keypair;
+
+        debug!(
+            "Producing block from validator #{} for slot {} on parent {}.",
+            proposer, slot, parent_root
+        );
+
+        let epoch = slot.epoch(self.spec.slots_per_epoch);
+        let randao_reveal = Signature::new(
+            &epoch.tree_hash_root(),
+            self.spec.get_domain(epoch, Domain::Randao, &state.fork),
+            &keypair.sk,
+        );
+
+        let (mut block, _state) = self
+            .beacon_chain
+            .produce_block_on(parent_root, slot, randao_reveal)
+            .unwrap();
+
+        block.signature = Signature::new(
+            &block.signed_root()[..],
+            self.spec
+                .get_domain(epoch, Domain::BeaconBlock, &state.fork),
+            &keypair.sk,
+        );
+
+        block
+    }
+
+    /// Builds a fork of `block_count` blocks descending from `parent_root`, advancing the slot
+    /// clock one slot for each block. No attestations are produced.
+    ///
+    /// Returns the roots of the new blocks, in order.
+    pub fn build_fork_on(&mut self, parent_root: Hash256, block_count: usize) -> Vec<Hash256> {
+        let mut roots = Vec::with_capacity(block_count);
+        let mut parent_root = parent_root;
+
+        for _ in 0..block_count {
+            let slot = self.increment_beacon_chain_slot();
+            let block = self.produce_block_on(parent_root, slot);
+
+            parent_root = block.block_header().canonical_root();
+            match self.beacon_chain.process_block(block) {
+                Ok(BlockProcessingOutcome::ValidBlock(_)) => {}
+                other => panic!("fork block processing failed with {:?}", other),
+            };
+            roots.push(parent_root);
+        }
+
+        roots
+    }
+
+    /// Returns the post-state of `parent_root` advanced to `slot`.
+    fn branch_state(&self, parent_root: Hash256, slot: Slot) -> BeaconState {
+        let parent = self
+            .beacon_chain
+            .get_block(&parent_root)
+            .unwrap()
+            .expect("Parent block must be known");
+        let mut state = self
+            .beacon_chain
+            .get_block_state(&parent_root, &parent)
+            .unwrap()
+            .expect("Parent state must be known");
+
+        for _ in state.slot.as_u64()..slot.as_u64() {
+            state
+                .build_epoch_cache(RelativeEpoch::NextWithoutRegistryChange, &self.spec)
+                .unwrap();
+            state
+                .build_epoch_cache(RelativeEpoch::NextWithRegistryChange, &self.spec)
+                .unwrap();
+            per_slot_processing(&mut state, &self.spec).unwrap();
+        }
+
+        state
+    }
+
     /// Signs a message using some validators secret key with the `Fork` info from the latest state
     /// of the `BeaconChain`.
     ///
@@ -343,6 +439,40 @@ impl BeaconChainHarness {
         self.beacon_chain.fork_choice().unwrap()
     }
 
+    /// Builds a new `BeaconChain` from the harness database, as if the node had been restarted.
+    ///
+    /// The returned chain does not share any in-memory state (e.g., fork choice) with
+    /// `self.beacon_chain`. The `op_pool` is only restored as it was at the last call to
+    /// `BeaconChain::persist`.
+    pub fn resume_beacon_chain(&self) -> Result<Option<TestingBeaconChain>, BeaconChainError> {
+        let fork_choice = BitwiseLMDGhost::new(self.block_store.clone());
+        let slot = self.beacon_chain.present_slot();
+
+        BeaconChain::from_store(
+            self.state_store.clone(),
+            self.block_store.clone(),
+            self.chain_metadata_store.clone(),
+            |_genesis_time| TestingSlotClock::new(slot.as_u64()),
+            (*self.spec).clone(),
+            fork_choice,
+        )
+    }
+
+    /// Returns the block tree of the fork choice, from the justified checkpoint.
+    pub fn block_tree(&self) -> Result<BlockTree, BeaconChainError> {
+        self.beacon_chain.block_tree()
+    }
+
+    /// Returns the block tree of the fork choice as JSON.
+    pub fn block_tree_json(&self) -> Result<String, BeaconChainError> {
+        Ok(serde_json::to_string_pretty(&self.block_tree()?).expect("BlockTree is serializable"))
+    }
+
+    /// Returns the block tree of the fork choice as a Graphviz DOT graph.
+    pub fn block_tree_dot(&self) -> Result<String, BeaconChainError> {
+        Ok(self.block_tree()?.to_dot())
+    }
+
     /// Dump all blocks and states from the canonical beacon chain.
     pub fn chain_dump(&self) -> Result<Vec<CheckPoint>, BeaconChainError> {
         self.beacon_chain.chain_dump()
@@ -1,18 +1,38 @@
 #![cfg(not(debug_assertions))]
 
+use beacon_chain::eth1::{Eth1Backend, MemoryEth1Backend};
+use beacon_chain::{
+    BeaconChainEvent, BlockProcessingOutcome, BlockProductionError, CheckPoint, InvalidBlock,
+    StateCacheStats, MAX_SKIP_SLOTS,
+};
 use env_logger::{Builder, Env};
+use fork_choice::{ForkChoice, Justification, JustifiedCheckpoint};
 use log::debug;
+use state_processing::per_block_processing;
+use std::sync::Arc;
 use test_harness::BeaconChainHarness;
-use types::ChainSpec;
+use types::test_utils::{TestingDepositBuilder, TestingProposerSlashingBuilder};
+use types::{BeaconBlock, ChainSpec, Domain, Epoch, Hash256, Keypair};
+
+const VALIDATOR_COUNT: usize = 8;
+
+/// Returns a harness with `VALIDATOR_COUNT` validators, using the `few_validators` spec.
+fn get_harness() -> BeaconChainHarness {
+    BeaconChainHarness::new(ChainSpec::few_validators(), VALIDATOR_COUNT)
+}
+
+/// Returns the block and state roots of each checkpoint of a chain dump.
+fn roots(dump: Vec<CheckPoint>) -> Vec<(Hash256, Hash256)> {
+    dump.iter()
+        .map(|checkpoint| (checkpoint.beacon_block_root, checkpoint.beacon_state_root))
+        .collect()
+}
 
 #[test]
 fn it_can_build_on_genesis_block() {
     Builder::from_env(Env::default().default_filter_or("info")).init();
 
-    let spec = ChainSpec::few_validators();
-    let validator_count = 8;
-
-    let mut harness = BeaconChainHarness::new(spec, validator_count as usize);
+    let mut harness = get_harness();
 
     harness.advance_chain_with_block();
 }
@@ -22,12 +42,9 @@ fn it_can_build_on_genesis_block() {
 fn it_can_produce_past_first_epoch_boundary() {
     Builder::from_env(Env::default().default_filter_or("info")).init();
 
-    let spec = ChainSpec::few_validators();
-    let validator_count = 8;
-
     debug!("Starting harness build...");
 
-    let mut harness = BeaconChainHarness::new(spec, validator_count);
+    let mut harness = get_harness();
 
     debug!("Harness built, tests starting..");
 
@@ -44,3 +61,595 @@ fn it_can_produce_past_first_epoch_boundary() {
 
     assert_eq!(dump.len() as u64, blocks + 1); // + 1 for genesis block.
 }
+
+#[test]
+fn it_can_resume_from_the_database() {
+    let mut harness = get_harness();
+
+    let blocks = harness.spec.slots_per_epoch + 1;
+
+    for _ in 0..blocks {
+        harness.advance_chain_with_block();
+    }
+
+    let resumed = harness
+        .resume_beacon_chain()
+        .expect("Resuming should not error.")
+        .expect("A persisted chain should exist.");
+
+    assert_eq!(
+        resumed.head().beacon_block_root,
+        harness.beacon_chain.head().beacon_block_root
+    );
+    assert_eq!(
+        resumed.finalized_head().beacon_block_root,
+        harness.beacon_chain.finalized_head().beacon_block_root
+    );
+    assert_eq!(
+        resumed.persisted_beacon_chain(),
+        harness.beacon_chain.persisted_beacon_chain()
+    );
+
+    assert_eq!(
+        roots(resumed.chain_dump().unwrap()),
+        roots(harness.chain_dump().unwrap())
+    );
+}
+
+#[test]
+fn it_restores_the_op_pool_after_persisting() {
+    let mut harness = get_harness();
+
+    harness.advance_chain_with_block();
+
+    let slashing = {
+        let signer = |validator_index: u64, message: &[u8], epoch: Epoch, domain: Domain| {
+            harness
+                .validator_sign(validator_index as usize, message, epoch, domain)
+                .expect("Unable to sign ProposerSlashing")
+        };
+        TestingProposerSlashingBuilder::double_vote(1, signer, &harness.spec)
+    };
+    harness.add_proposer_slashing(slashing.clone());
+
+    // Without a call to `persist`, the pool is not written to the database.
+    let resumed = harness.resume_beacon_chain().unwrap().unwrap();
+    let state = resumed.state.read().clone();
+    assert!(resumed
+        .op_pool
+        .get_slashings(&state, &harness.spec)
+        .0
+        .is_empty());
+
+    harness.beacon_chain.persist().unwrap();
+
+    let resumed = harness.resume_beacon_chain().unwrap().unwrap();
+    let state = resumed.state.read().clone();
+    assert_eq!(
+        resumed.op_pool.get_slashings(&state, &harness.spec).0,
+        vec![slashing]
+    );
+}
+
+#[test]
+fn it_can_reconstruct_frozen_states() {
+    let mut harness = get_harness();
+
+    let blocks = harness.spec.slots_per_epoch + 1;
+
+    for _ in 0..blocks {
+        harness.advance_chain_with_block();
+    }
+
+    let dump = harness.chain_dump().unwrap();
+    let roots_before = roots(dump.clone());
+
+    // Finalize the block at the first epoch boundary, freezing all states prior to it.
+    let finalized = dump[harness.spec.slots_per_epoch as usize].clone();
+    harness
+        .beacon_chain
+        .update_finalized_head(
+            finalized.beacon_block,
+            finalized.beacon_block_root,
+            finalized.beacon_state,
+            finalized.beacon_state_root,
+        )
+        .unwrap();
+
+    // Only the genesis state is kept in the cold column, the rest must be reconstructed.
+    for checkpoint in &dump[..harness.spec.slots_per_epoch as usize] {
+        assert!(!harness
+            .state_store
+            .exists(&checkpoint.beacon_state_root)
+            .unwrap());
+    }
+    assert!(harness
+        .state_store
+        .get_cold(harness.spec.genesis_slot)
+        .unwrap()
+        .is_some());
+    assert!(harness
+        .state_store
+        .exists(&finalized.beacon_state_root)
+        .unwrap());
+
+    assert_eq!(roots(harness.chain_dump().unwrap()), roots_before);
+}
+
+#[test]
+fn it_resumes_fork_choice_from_the_finalized_block() {
+    let mut harness = get_harness();
+
+    let blocks = harness.spec.slots_per_epoch + 1;
+
+    for _ in 0..blocks {
+        harness.advance_chain_with_block();
+    }
+
+    let dump = harness.chain_dump().unwrap();
+    let finalized = dump[harness.spec.slots_per_epoch as usize].clone();
+    harness
+        .beacon_chain
+        .update_finalized_head(
+            finalized.beacon_block,
+            finalized.beacon_block_root,
+            finalized.beacon_state,
+            finalized.beacon_state_root,
+        )
+        .unwrap();
+
+    // Only the blocks after the finalized block are replayed, yet the fork choice still finds
+    // the same head.
+    let resumed = harness.resume_beacon_chain().unwrap().unwrap();
+    resumed.fork_choice().unwrap();
+
+    assert_eq!(
+        resumed.head().beacon_block_root,
+        harness.beacon_chain.head().beacon_block_root
+    );
+    assert_eq!(
+        resumed.finalized_head().beacon_block_root,
+        finalized.beacon_block_root
+    );
+}
+
+#[test]
+fn it_prunes_abandoned_forks_on_finalization() {
+    let mut harness = get_harness();
+
+    harness.advance_chain_with_block();
+
+    // Produce a block which is only imported after a competing block at a later slot, forming a
+    // fork. The fork choice decides which of the two blocks the chain is built upon.
+    harness.increment_beacon_chain_slot();
+    let fork_block = harness.produce_block();
+    let competing_block = harness.advance_chain_with_block();
+    harness
+        .beacon_chain
+        .process_block(fork_block.clone())
+        .unwrap();
+
+    let heads = harness.beacon_chain.persisted_beacon_chain().heads;
+    assert!(heads.contains(&fork_block.canonical_root()));
+    assert!(heads.contains(&competing_block.canonical_root()));
+
+    for _ in 0..harness.spec.slots_per_epoch {
+        harness.advance_chain_with_block();
+    }
+
+    let dump = harness.chain_dump().unwrap();
+    let roots_before = roots(dump.clone());
+
+    let abandoned_block = if dump
+        .iter()
+        .any(|checkpoint| checkpoint.beacon_block_root == fork_block.canonical_root())
+    {
+        competing_block
+    } else {
+        fork_block
+    };
+    let abandoned_block_root = abandoned_block.canonical_root();
+
+    let finalized = dump[dump.len() - 2].clone();
+    harness
+        .beacon_chain
+        .update_finalized_head(
+            finalized.beacon_block,
+            finalized.beacon_block_root,
+            finalized.beacon_state,
+            finalized.beacon_state_root,
+        )
+        .unwrap();
+
+    assert!(!harness.block_store.exists(&abandoned_block_root).unwrap());
+    assert!(!harness
+        .state_store
+        .exists(&abandoned_block.state_root)
+        .unwrap());
+    assert_eq!(
+        harness.beacon_chain.persisted_beacon_chain().heads,
+        vec![harness.beacon_chain.head().beacon_block_root]
+    );
+
+    harness.run_fork_choice();
+
+    assert_eq!(roots(harness.chain_dump().unwrap()), roots_before);
+}
+
+#[test]
+fn it_tracks_the_justified_checkpoint() {
+    let mut harness = get_harness();
+
+    for _ in 0..harness.spec.slots_per_epoch * 3 {
+        harness.advance_chain_with_block();
+    }
+
+    let (justified_epoch, justified_root) = {
+        let state = &harness.beacon_chain.head().beacon_state;
+        (state.current_justified_epoch, state.current_justified_root)
+    };
+    assert!(justified_epoch > harness.spec.genesis_epoch);
+
+    let checkpoint = harness.beacon_chain.justified_checkpoint().clone();
+    assert_eq!(checkpoint.justification.justified_epoch, justified_epoch);
+    assert_eq!(checkpoint.root, justified_root);
+    assert_eq!(checkpoint.balances.len(), VALIDATOR_COUNT);
+
+    let resumed = harness
+        .resume_beacon_chain()
+        .expect("Resuming should not error.")
+        .expect("A persisted chain should exist.");
+
+    assert_eq!(*resumed.justified_checkpoint(), checkpoint);
+}
+
+#[test]
+fn it_ignores_forks_which_disagree_with_the_justified_checkpoint() {
+    let mut harness = get_harness();
+
+    for _ in 0..harness.spec.slots_per_epoch * 3 {
+        harness.advance_chain_with_block();
+    }
+    let canonical_head = harness.beacon_chain.head().beacon_block_root;
+
+    let checkpoint = harness.beacon_chain.justified_checkpoint().clone();
+    assert!(checkpoint.justification.justified_epoch > harness.spec.genesis_epoch);
+
+    // The balances are a snapshot of the justified state, not of the head state.
+    let justified_block = harness
+        .beacon_chain
+        .get_block(&checkpoint.root)
+        .unwrap()
+        .unwrap();
+    let justified_state = harness
+        .beacon_chain
+        .get_block_state(&checkpoint.root, &justified_block)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        checkpoint.balances,
+        JustifiedCheckpoint::new(
+            checkpoint.root,
+            checkpoint.justification,
+            &justified_state,
+            &harness.spec
+        )
+        .balances
+    );
+
+    // A fork from the justified block without attestations does not see the justification of
+    // the canonical chain.
+    let fork = harness.build_fork_on(checkpoint.root, harness.spec.slots_per_epoch as usize + 1);
+    let fork_tip = *fork.last().unwrap();
+    let fork_justification = {
+        let block = harness.beacon_chain.get_block(&fork_tip).unwrap().unwrap();
+        let state = harness
+            .beacon_chain
+            .get_block_state(&fork_tip, &block)
+            .unwrap()
+            .unwrap();
+        Justification::from(&state)
+    };
+    assert_ne!(fork_justification, checkpoint.justification);
+
+    // Every validator votes for the fork, but it is not viable.
+    let epoch = harness.epoch() + 1;
+    {
+        let mut fork_choice = harness.beacon_chain.fork_choice.write();
+        for validator_index in 0..VALIDATOR_COUNT as u64 {
+            fork_choice
+                .add_vote(validator_index, &fork_tip, epoch, &harness.spec)
+                .unwrap();
+        }
+    }
+    harness.run_fork_choice();
+
+    assert_eq!(
+        harness.beacon_chain.head().beacon_block_root,
+        canonical_head
+    );
+    assert_eq!(*harness.beacon_chain.justified_checkpoint(), checkpoint);
+
+    // The votes are weighted by the justified balances.
+    let tree = harness.block_tree().unwrap();
+    let total: u64 = checkpoint.balances.iter().sum();
+    assert!(total > 0);
+    assert_eq!(tree.nodes[0].root, checkpoint.root);
+    assert_eq!(tree.nodes[0].weight, total);
+    let tip = tree
+        .nodes
+        .iter()
+        .find(|node| node.root == fork_tip)
+        .unwrap();
+    assert_eq!(tip.weight, total);
+    assert_eq!(tip.voters.len(), VALIDATOR_COUNT);
+}
+
+#[test]
+fn it_exports_the_block_tree() {
+    let mut harness = get_harness();
+
+    let blocks = 3;
+    for _ in 0..blocks {
+        harness.advance_chain_with_block();
+    }
+
+    let tree = harness.block_tree().unwrap();
+
+    // No checkpoint has been justified, so the tree starts at genesis.
+    assert_eq!(tree.nodes.len(), blocks + 1);
+    assert_eq!(
+        tree.nodes.last().unwrap().root,
+        harness.beacon_chain.head().beacon_block_root
+    );
+    assert!(tree.nodes[0].weight > 0);
+
+    assert!(harness.block_tree_json().unwrap().contains("\"voters\""));
+    assert_eq!(
+        harness.block_tree_dot().unwrap().matches(" -> ").count(),
+        blocks
+    );
+}
+
+#[test]
+fn it_rejects_blocks_before_the_state_transition() {
+    let mut harness = get_harness();
+
+    let parent = harness.advance_chain_with_block();
+    harness.increment_beacon_chain_slot();
+    let block = harness.produce_block();
+
+    let invalid = |block: BeaconBlock| match harness.beacon_chain.process_block(block) {
+        Ok(BlockProcessingOutcome::InvalidBlock(reason)) => reason,
+        other => panic!("block should be invalid, got {:?}", other),
+    };
+
+    let mut finalized = block.clone();
+    finalized.slot = harness.spec.genesis_slot;
+    match invalid(finalized) {
+        InvalidBlock::FinalizedSlot { .. } => {}
+        other => panic!("expected FinalizedSlot, got {:?}", other),
+    }
+
+    let mut not_after_parent = block.clone();
+    not_after_parent.slot = parent.slot;
+    match invalid(not_after_parent) {
+        InvalidBlock::SlotNotAfterParent { .. } => {}
+        other => panic!("expected SlotNotAfterParent, got {:?}", other),
+    }
+
+    let mut too_many_skipped = block.clone();
+    too_many_skipped.slot = parent.slot + MAX_SKIP_SLOTS + 1;
+    harness
+        .beacon_chain
+        .slot_clock
+        .set_slot(too_many_skipped.slot.as_u64());
+    match invalid(too_many_skipped) {
+        InvalidBlock::TooManySkippedSlots { .. } => {}
+        other => panic!("expected TooManySkippedSlots, got {:?}", other),
+    }
+    harness
+        .beacon_chain
+        .slot_clock
+        .set_slot(block.slot.as_u64());
+
+    let mut bad_signature = block.clone();
+    bad_signature.signature = harness.spec.empty_signature.clone();
+    assert_eq!(
+        invalid(bad_signature),
+        InvalidBlock::ProposalSignatureInvalid
+    );
+
+    assert!(harness
+        .beacon_chain
+        .process_block(block.clone())
+        .unwrap()
+        .sucessfully_processed());
+    assert_eq!(invalid(block), InvalidBlock::BlockIsAlreadyKnown);
+}
+
+#[test]
+fn it_checks_fork_signatures_before_loading_the_parent_state() {
+    let mut harness = get_harness();
+
+    harness.advance_chain_with_block();
+    let fork_parent = harness.advance_chain_with_block().canonical_root();
+    while harness.epoch() == harness.spec.genesis_epoch {
+        harness.advance_chain_with_block();
+    }
+
+    // The first block of the fork in the new epoch caches the proposers of its branch, which
+    // differ from those of the canonical chain.
+    let fork = harness.build_fork_on(fork_parent, 1);
+    let slot = harness.increment_beacon_chain_slot();
+    let mut block = harness.produce_block_on(fork[0], slot);
+    block.signature = harness.spec.empty_signature.clone();
+
+    let lookups = |stats: StateCacheStats| stats.hits + stats.misses;
+    let before = lookups(harness.beacon_chain.state_cache_stats());
+
+    assert_eq!(
+        harness.beacon_chain.process_block(block).unwrap(),
+        BlockProcessingOutcome::InvalidBlock(InvalidBlock::ProposalSignatureInvalid)
+    );
+    assert_eq!(lookups(harness.beacon_chain.state_cache_stats()), before);
+}
+
+#[test]
+fn it_reads_parent_states_from_the_state_cache() {
+    let mut harness = get_harness();
+
+    let blocks = 4;
+    for _ in 0..blocks {
+        harness.advance_chain_with_block();
+    }
+
+    // Only the genesis state must be read from the database.
+    let stats = harness.beacon_chain.state_cache_stats();
+    assert!(stats.hits >= blocks - 1);
+    assert!(stats.misses <= 1);
+    assert!(stats.len <= stats.capacity);
+
+    harness.beacon_chain.set_state_cache_capacity(1);
+    assert_eq!(harness.beacon_chain.state_cache_stats().len, 1);
+}
+
+#[test]
+fn it_publishes_events_to_subscribers() {
+    let mut harness = get_harness();
+
+    let first = harness.beacon_chain.subscribe_events();
+    let second = harness.beacon_chain.subscribe_events();
+
+    harness.advance_chain_with_block();
+
+    let head = harness.beacon_chain.head().beacon_block_root;
+    let slot = harness.beacon_chain.head().beacon_block.slot;
+
+    for events in &[first, second] {
+        let events: Vec<BeaconChainEvent> = events.try_iter().collect();
+
+        assert!(events.contains(&BeaconChainEvent::BlockImported {
+            block_root: head,
+            slot,
+        }));
+        assert!(events.iter().any(|event| match event {
+            BeaconChainEvent::HeadChanged {
+                new_head,
+                reorg_depth,
+                ..
+            } => *new_head == head && *reorg_depth == 0,
+            _ => false,
+        }));
+    }
+}
+
+#[test]
+fn it_builds_forks_from_an_ancestor() {
+    let mut harness = get_harness();
+
+    let first = harness.advance_chain_with_block();
+    harness.advance_chain_with_block();
+    let canonical_head = harness.beacon_chain.head().beacon_block_root;
+
+    let genesis_root = first.previous_block_root;
+    let fork = harness.build_fork_on(genesis_root, 3);
+
+    // The canonical blocks have all of the votes, so the longer fork does not become the head.
+    assert_eq!(
+        harness.beacon_chain.head().beacon_block_root,
+        canonical_head
+    );
+
+    let tree = harness.block_tree().unwrap();
+    for root in &fork {
+        assert!(tree.nodes.iter().any(|node| node.root == *root));
+    }
+    let fork_start = tree.nodes.iter().find(|node| node.root == fork[0]).unwrap();
+    assert_eq!(fork_start.parent, Some(genesis_root));
+
+    let fork_tip = harness.beacon_chain.get_block(&fork[2]).unwrap().unwrap();
+    assert_eq!(
+        harness.beacon_chain.produce_block_on(
+            fork[2],
+            fork_tip.slot,
+            harness.spec.empty_signature.clone()
+        ),
+        Err(BlockProductionError::SlotNotAfterParent {
+            parent_slot: fork_tip.slot,
+            slot: fork_tip.slot,
+        })
+    );
+}
+
+#[test]
+fn it_votes_for_eth1_data_by_majority() {
+    let mut harness = get_harness();
+
+    let eth1 = Arc::new(MemoryEth1Backend::new(&harness.spec));
+    eth1.mine_blocks(5);
+    let follow_distance = 2;
+    harness
+        .beacon_chain
+        .set_eth1_backend(Box::new(eth1.clone()), follow_distance);
+
+    // Without prior votes, the block `follow_distance` behind the eth1 head is voted for.
+    let expected = eth1.block_by_number(3).unwrap().unwrap().eth1_data();
+    let block = harness.advance_chain_with_block();
+    assert_eq!(block.body.eth1_data, expected);
+
+    // The prior vote has the majority, even though the eth1 head has advanced.
+    eth1.mine_blocks(3);
+    let block = harness.advance_chain_with_block();
+    assert_eq!(block.body.eth1_data, expected);
+}
+
+#[test]
+fn it_includes_deposits_from_the_eth1_chain() {
+    let mut harness = get_harness();
+
+    let eth1 = Arc::new(MemoryEth1Backend::new(&harness.spec));
+    let follow_distance = 2;
+    harness
+        .beacon_chain
+        .set_eth1_backend(Box::new(eth1.clone()), follow_distance);
+
+    let keypairs: Vec<Keypair> = (0..2).map(|_| Keypair::random()).collect();
+    for keypair in &keypairs {
+        let mut builder =
+            TestingDepositBuilder::new(keypair.pk.clone(), harness.spec.max_deposit_amount);
+        builder.sign(keypair, harness.epoch(), &harness.fork(), &harness.spec);
+        eth1.add_deposit(builder.build().deposit_data);
+    }
+    let deposit_block = eth1.mine_block();
+    eth1.mine_blocks(follow_distance as usize);
+
+    // Stand in for a successful eth1 data vote, which takes a full voting period.
+    harness.increment_beacon_chain_slot();
+    harness.beacon_chain.state.write().latest_eth1_data = deposit_block.eth1_data();
+
+    let block = harness.produce_block();
+    assert_eq!(block.body.deposits.len(), keypairs.len());
+    for (index, deposit) in block.body.deposits.iter().enumerate() {
+        assert_eq!(deposit.index, index as u64);
+        assert_eq!(
+            deposit.deposit_data.deposit_input.pubkey,
+            keypairs[index].pk
+        );
+    }
+
+    let mut state = harness.beacon_chain.state.read().clone();
+    per_block_processing(&mut state, &block, &harness.spec).unwrap();
+
+    assert_eq!(state.deposit_index, keypairs.len() as u64);
+    assert_eq!(
+        state.validator_registry.len(),
+        VALIDATOR_COUNT + keypairs.len()
+    );
+    for keypair in &keypairs {
+        assert!(state
+            .validator_registry
+            .iter()
+            .any(|validator| validator.pubkey == keypair.pk));
+    }
+}
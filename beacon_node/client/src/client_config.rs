@@ -1,5 +1,7 @@
+use beacon_chain::DEFAULT_STATE_CACHE_SIZE;
 use clap::ArgMatches;
-use db::DBType;
+use db::stores::DEFAULT_EPOCHS_PER_COLD_STATE;
+use db::{DBType, DiskDBConfig};
 use fork_choice::ForkChoiceAlgorithm;
 use network::NetworkConfig;
 use slog::error;
@@ -12,6 +14,9 @@ use types::multiaddr::ToMultiaddr;
 use types::ChainSpec;
 use types::Multiaddr;
 
+/// The file in the datadir which holds the secret key of the node's network identity.
+const NODE_KEY_FILENAME: &str = "network/node_key";
+
 /// Stores the client configuration for this Lighthouse instance.
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
@@ -21,6 +26,14 @@ pub struct ClientConfig {
     pub fork_choice: ForkChoiceAlgorithm,
     pub db_type: DBType,
     pub db_name: PathBuf,
+    pub db_config: DiskDBConfig,
+    /// The number of epochs between each finalized state kept in the database. Must not change
+    /// for an existing database.
+    pub epochs_per_cold_state: u64,
+    /// The number of recent states kept in memory by the beacon chain.
+    pub state_cache_size: usize,
+    /// A JSON file of eth1 blocks and deposits to read eth1 data from, in place of an eth1 node.
+    pub eth1_chain_file: Option<PathBuf>,
     pub rpc_conf: rpc::RPCConfig,
     //pub ipc_conf:
 }
@@ -32,11 +45,10 @@ impl Default for ClientConfig {
             let home = dirs::home_dir().expect("Unable to determine home dir.");
             home.join(".lighthouse/")
         };
-        fs::create_dir_all(&data_dir)
-            .unwrap_or_else(|_| panic!("Unable to create {:?}", &data_dir));
 
         let default_spec = ChainSpec::lighthouse_testnet();
-        let default_net_conf = NetworkConfig::new(default_spec.boot_nodes.clone());
+        let mut default_net_conf = NetworkConfig::new(default_spec.boot_nodes.clone());
+        default_net_conf.node_key_file = Some(data_dir.join(NODE_KEY_FILENAME));
 
         Self {
             data_dir: data_dir.clone(),
@@ -49,12 +61,26 @@ impl Default for ClientConfig {
             db_type: DBType::Memory,
             // default db name for disk-based dbs
             db_name: data_dir.join("chain.db"),
+            // RocksDB tuning for disk-based dbs
+            db_config: DiskDBConfig::default(),
+            epochs_per_cold_state: DEFAULT_EPOCHS_PER_COLD_STATE,
+            state_cache_size: DEFAULT_STATE_CACHE_SIZE,
+            eth1_chain_file: None,
             rpc_conf: rpc::RPCConfig::default(),
         }
     }
 }
 
 impl ClientConfig {
+    /// Creates the `data_dir`, if it does not already exist.
+    ///
+    /// The directory is not created when the config is built, so that an existing database may
+    /// be inspected without writing to the filesystem.
+    pub fn create_data_dir(&self) -> Result<(), String> {
+        fs::create_dir_all(&self.data_dir)
+            .map_err(|e| format!("Unable to create {:?}: {}", self.data_dir, e))
+    }
+
     /// Parses the CLI arguments into a `Config` struct.
     pub fn parse_args(args: ArgMatches, log: &slog::Logger) -> Result<Self, &'static str> {
         let mut config = ClientConfig::default();
@@ -100,13 +126,103 @@ impl ClientConfig {
             }
         }
 
+        if let Some(target_peers_str) = args.value_of("target-peers") {
+            if let Ok(target_peers) = target_peers_str.parse::<usize>() {
+                config.net_conf.target_peers = target_peers;
+            } else {
+                error!(log, "Invalid target peer count"; "target-peers" => target_peers_str);
+                return Err("Invalid target peer count");
+            }
+        }
+
+        if let Some(max_peers_str) = args.value_of("max-peers") {
+            if let Ok(max_peers) = max_peers_str.parse::<usize>() {
+                config.net_conf.max_peers = max_peers;
+            } else {
+                error!(log, "Invalid maximum peer count"; "max-peers" => max_peers_str);
+                return Err("Invalid maximum peer count");
+            }
+        }
+
         /* Filesystem related arguments */
 
         // Custom datadir
         if let Some(dir) = args.value_of("datadir") {
             config.data_dir = PathBuf::from(dir.to_string());
+            config.db_name = config.data_dir.join("chain.db");
+            config.net_conf.node_key_file = Some(config.data_dir.join(NODE_KEY_FILENAME));
         };
 
+        // An explicit node key file takes precedence over the one in the datadir.
+        if let Some(path) = args.value_of("node-key-file") {
+            config.net_conf.node_key_file = Some(PathBuf::from(path.to_string()));
+        }
+
+        /* Fork choice related arguments */
+
+        if let Some(fork_choice) = args.value_of("fork-choice") {
+            config.fork_choice = match fork_choice {
+                "longest-chain" => ForkChoiceAlgorithm::LongestChain,
+                "slow" => ForkChoiceAlgorithm::SlowLMDGhost,
+                "bitwise" => ForkChoiceAlgorithm::BitwiseLMDGhost,
+                "optimized" => ForkChoiceAlgorithm::OptimizedLMDGhost,
+                "proto-array" => ForkChoiceAlgorithm::ProtoArrayLMDGhost,
+                _ => {
+                    error!(log, "Invalid fork choice algorithm"; "fork-choice" => fork_choice);
+                    return Err("Invalid fork choice algorithm");
+                }
+            };
+        }
+
+        /* Database related arguments */
+
+        if let Some(db_type) = args.value_of("db") {
+            config.db_type = match db_type {
+                "memory" => DBType::Memory,
+                "disk" => DBType::RocksDB,
+                _ => {
+                    error!(log, "Invalid database type"; "db" => db_type);
+                    return Err("Invalid database type");
+                }
+            };
+        }
+
+        if let Some(db_path) = args.value_of("db-path") {
+            config.db_name = PathBuf::from(db_path.to_string());
+        }
+
+        if let Some(options) = args.values_of("db-option") {
+            for option in options {
+                if let Err(e) = config.db_config.apply_option(option) {
+                    error!(log, "Invalid database option"; "db-option" => option, "error" => e);
+                    return Err("Invalid database option");
+                }
+            }
+        }
+
+        if let Some(epochs) = args.value_of("epochs-per-cold-state") {
+            match epochs.parse::<u64>() {
+                Ok(epochs) if epochs > 0 => config.epochs_per_cold_state = epochs,
+                _ => {
+                    error!(log, "Invalid cold state interval"; "epochs-per-cold-state" => epochs);
+                    return Err("Invalid cold state interval");
+                }
+            }
+        }
+
+        if let Some(size) = args.value_of("state-cache-size") {
+            if let Ok(size) = size.parse::<usize>() {
+                config.state_cache_size = size;
+            } else {
+                error!(log, "Invalid state cache size"; "state-cache-size" => size);
+                return Err("Invalid state cache size");
+            }
+        }
+
+        if let Some(eth1_chain_file) = args.value_of("eth1-chain-file") {
+            config.eth1_chain_file = Some(PathBuf::from(eth1_chain_file.to_string()));
+        }
+
         /* RPC related arguments */
 
         if args.is_present("rpc") {
@@ -1,7 +1,6 @@
 use crate::ClientConfig;
 use beacon_chain::{
     db::{ClientDB, DiskDB, MemoryDB},
-    fork_choice::BitwiseLMDGhost,
     initialise,
     slot_clock::{SlotClock, SystemTimeSlotClock},
     BeaconChain,
@@ -20,30 +19,41 @@ pub trait ClientTypes {
     ) -> Arc<BeaconChain<Self::DB, Self::SlotClock, Self::ForkChoice>>;
 }
 
-pub struct StandardClientType;
+/// A client which stores the beacon chain in an on-disk RocksDB database at `ClientConfig.db_name`,
+/// allowing it to be resumed after a restart.
+///
+/// The fork choice algorithm is chosen at runtime, from `ClientConfig.fork_choice`.
+pub struct DiskDBClientType;
 
-impl ClientTypes for StandardClientType {
+impl ClientTypes for DiskDBClientType {
     type DB = DiskDB;
     type SlotClock = SystemTimeSlotClock;
-    type ForkChoice = BitwiseLMDGhost<DiskDB>;
+    type ForkChoice = Box<dyn ForkChoice>;
 
     fn initialise_beacon_chain(
         config: &ClientConfig,
     ) -> Arc<BeaconChain<Self::DB, Self::SlotClock, Self::ForkChoice>> {
-        initialise::initialise_beacon_chain(&config.spec, Some(&config.db_name))
+        initialise::initialise_beacon_chain(
+            &config.spec,
+            Some(&config.db_name),
+            &config.db_config,
+            config.epochs_per_cold_state,
+            &config.fork_choice,
+        )
     }
 }
 
-pub struct TestingClientType;
+/// A client which stores the beacon chain in memory. The chain is lost when the client exits.
+pub struct MemoryDBClientType;
 
-impl ClientTypes for TestingClientType {
+impl ClientTypes for MemoryDBClientType {
     type DB = MemoryDB;
     type SlotClock = SystemTimeSlotClock;
-    type ForkChoice = BitwiseLMDGhost<MemoryDB>;
+    type ForkChoice = Box<dyn ForkChoice>;
 
     fn initialise_beacon_chain(
         config: &ClientConfig,
     ) -> Arc<BeaconChain<Self::DB, Self::SlotClock, Self::ForkChoice>> {
-        initialise::initialise_test_beacon_chain(&config.spec, None)
+        initialise::initialise_test_beacon_chain(&config.spec, None, &config.fork_choice)
     }
 }
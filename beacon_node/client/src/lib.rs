@@ -5,6 +5,7 @@ pub mod client_types;
 pub mod error;
 pub mod notifier;
 
+use beacon_chain::eth1::{MemoryEth1Backend, DEFAULT_ETH1_FOLLOW_DISTANCE};
 use beacon_chain::BeaconChain;
 pub use client_config::ClientConfig;
 pub use client_types::ClientTypes;
@@ -27,7 +28,7 @@ pub struct Client<T: ClientTypes> {
     /// Configuration for the lighthouse client.
     _config: ClientConfig,
     /// The beacon chain for the running client.
-    _beacon_chain: Arc<BeaconChain<T::DB, T::SlotClock, T::ForkChoice>>,
+    beacon_chain: Arc<BeaconChain<T::DB, T::SlotClock, T::ForkChoice>>,
     /// Reference to the network service.
     pub network: Arc<NetworkService>,
     /// Signal to terminate the RPC server.
@@ -49,6 +50,13 @@ impl<TClientType: ClientTypes> Client<TClientType> {
     ) -> error::Result<Self> {
         // generate a beacon chain
         let beacon_chain = TClientType::initialise_beacon_chain(&config);
+        beacon_chain.set_state_cache_capacity(config.state_cache_size);
+
+        if let Some(path) = &config.eth1_chain_file {
+            let backend = MemoryEth1Backend::from_file(path, &config.spec)
+                .map_err(|e| format!("Unable to load eth1 chain: {:?}", e))?;
+            beacon_chain.set_eth1_backend(Box::new(backend), DEFAULT_ETH1_FOLLOW_DISTANCE);
+        }
 
         if beacon_chain.read_slot_clock().is_none() {
             panic!("Cannot start client before genesis!")
@@ -131,7 +139,7 @@ impl<TClientType: ClientTypes> Client<TClientType> {
 
         Ok(Client {
             _config: config,
-            _beacon_chain: beacon_chain,
+            beacon_chain,
             rpc_exit_signal,
             slot_timer_exit_signal: Some(slot_timer_exit_signal),
             log,
@@ -141,6 +149,15 @@ impl<TClientType: ClientTypes> Client<TClientType> {
     }
 }
 
+impl<T: ClientTypes> Drop for Client<T> {
+    fn drop(&mut self) {
+        // Save the heads and the operation pool, so they may be restored on the next start.
+        if let Err(e) = self.beacon_chain.persist() {
+            error!(self.log, "Failed to persist beacon chain"; "error" => format!("{:?}", e));
+        }
+    }
+}
+
 fn do_state_catchup<T, U, F>(chain: &Arc<BeaconChain<T, U, F>>, log: &slog::Logger)
 where
     T: ClientDB,
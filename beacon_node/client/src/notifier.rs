@@ -1,8 +1,9 @@
 use crate::Client;
 use crate::ClientTypes;
+use beacon_chain::BeaconChainEvent;
 use exit_future::Exit;
 use futures::{Future, Stream};
-use slog::{debug, o};
+use slog::{debug, info, o};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::runtime::TaskExecutor;
@@ -14,14 +15,20 @@ pub fn run<T: ClientTypes>(client: &Client<T>, executor: TaskExecutor, exit: Exi
     // notification heartbeat
     let interval = Interval::new(Instant::now(), Duration::from_secs(5));
 
-    let _log = client.log.new(o!("Service" => "Notifier"));
+    let log = client.log.new(o!("Service" => "Notifier"));
 
     // TODO: Debugging only
     let counter = Arc::new(Mutex::new(0));
     let network = client.network.clone();
 
+    let events = client.beacon_chain.subscribe_events();
+
     // build heartbeat logic here
     let heartbeat = move |_| {
+        for event in events.try_iter() {
+            log_event(&log, event);
+        }
+
         //debug!(log, "Temp heartbeat output");
         //TODO: Remove this logic. Testing only
         let mut count = counter.lock().unwrap();
@@ -43,3 +50,38 @@ pub fn run<T: ClientTypes>(client: &Client<T>, executor: TaskExecutor, exit: Exi
 
     executor.spawn(exit.until(heartbeat_interval).map(|_| ()));
 }
+
+fn log_event(log: &slog::Logger, event: BeaconChainEvent) {
+    match event {
+        BeaconChainEvent::HeadChanged {
+            old_head,
+            new_head,
+            slot,
+            reorg_depth,
+        } if reorg_depth > 0 => info!(log, "Beacon chain re-org";
+            "slot" => slot,
+            "old_head" => format!("{}", old_head),
+            "new_head" => format!("{}", new_head),
+            "depth" => reorg_depth,
+        ),
+        BeaconChainEvent::HeadChanged { new_head, slot, .. } => info!(log, "New head";
+            "slot" => slot,
+            "head" => format!("{}", new_head),
+        ),
+        BeaconChainEvent::Finalized { block_root, slot } => info!(log, "Finalized";
+            "slot" => slot,
+            "block" => format!("{}", block_root),
+        ),
+        BeaconChainEvent::BlockImported { block_root, slot } => debug!(log, "Imported block";
+            "slot" => slot,
+            "block" => format!("{}", block_root),
+        ),
+        BeaconChainEvent::AttestationReceived(data) => debug!(log, "Received attestation";
+            "slot" => data.slot,
+            "shard" => data.shard,
+        ),
+        BeaconChainEvent::OperationPooled(operation) => debug!(log, "Pooled operation";
+            "kind" => format!("{:?}", operation),
+        ),
+    }
+}
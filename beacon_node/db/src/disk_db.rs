@@ -1,62 +1,169 @@
 extern crate rocksdb;
 
 use super::rocksdb::Error as RocksError;
-use super::rocksdb::{Options, DB};
-use super::{ClientDB, DBError, DBValue};
+use super::rocksdb::{
+    BlockBasedOptions, ColumnFamilyDescriptor, DBCompressionType, Direction, IteratorMode, Options,
+    WriteBatch, DB,
+};
+use super::stores::META_DB_COLUMN;
+use super::{schema, ColumnConfig, DBCompression, DiskDBConfig};
+use super::{ClientDB, ColumnIter, DBBatch, DBError, DBOp, DBValue};
 use std::fs;
 use std::path::Path;
 
 /// A on-disk database which implements the ClientDB trait.
 ///
-/// This implementation uses RocksDB with default options.
+/// This implementation uses RocksDB, with options for each column supplied by a `DiskDBConfig`.
 pub struct DiskDB {
     db: DB,
+    /// If `true`, all writes are rejected.
+    read_only: bool,
 }
 
 impl DiskDB {
-    /// Open the RocksDB database, optionally supplying columns if required.
+    /// Open the RocksDB database, creating it and any of the supplied `columns` which do not yet
+    /// exist.
     ///
     /// The RocksDB database will be contained in a directory titled
     /// "database" in the supplied path.
     ///
-    /// # Panics
+    /// Any columns which already exist on disk are also opened, even if they are not in
+    /// `columns`. The `META_DB_COLUMN`, which holds the schema version, is always opened.
     ///
-    /// Panics if the database is unable to be created.
-    pub fn open(path: &Path, columns: Option<&[&str]>) -> Self {
+    /// Any required schema migrations are run before returning (see the `schema` module).
+    pub fn open(path: &Path, columns: &[&str], config: &DiskDBConfig) -> Result<Self, DBError> {
         /*
          * Initialise the options
          */
-        let mut options = Options::default();
+        let mut options = column_options(&config.default_column);
         options.create_if_missing(true);
-
-        // TODO: ensure that columns are created (and remove
-        // the dead_code allow)
+        options.create_missing_column_families(true);
 
         /*
          * Initialise the path
          */
-        fs::create_dir_all(&path).unwrap_or_else(|_| panic!("Unable to create {:?}", &path));
+        fs::create_dir_all(&path)
+            .map_err(|e| DBError::new(format!("Unable to create {:?}: {:?}", &path, e)))?;
         let db_path = path.join("database");
 
+        /*
+         * Determine the columns to open
+         */
+        let mut names: Vec<String> = columns.iter().map(|col| col.to_string()).collect();
+        if !names.iter().any(|name| name == META_DB_COLUMN) {
+            names.push(META_DB_COLUMN.to_string());
+        }
+        if db_path.exists() {
+            for existing in DB::list_cf(&options, &db_path)? {
+                // The "default" column is always opened by RocksDB.
+                if existing != "default" && !names.contains(&existing) {
+                    names.push(existing);
+                }
+            }
+        }
+
+        let descriptors: Vec<ColumnFamilyDescriptor> = names
+            .iter()
+            .map(|name| {
+                ColumnFamilyDescriptor::new(name.as_str(), column_options(config.column(name)))
+            })
+            .collect();
+
         /*
          * Open the database
          */
-        let db = match columns {
-            None => DB::open(&options, db_path),
-            Some(columns) => DB::open_cf(&options, db_path, columns),
+        let db = Self {
+            db: DB::open_cf_descriptors(&options, db_path, descriptors)?,
+            read_only: false,
+        };
+
+        /*
+         * Bring the schema up to date, or fail if it is newer than this binary
+         */
+        let data_columns: Vec<&str> = names
+            .iter()
+            .map(String::as_str)
+            .filter(|name| *name != META_DB_COLUMN)
+            .collect();
+        schema::migrate(&db, &data_columns)?;
+
+        Ok(db)
+    }
+
+    /// Open an existing RocksDB database for inspection, with all of its columns.
+    ///
+    /// The database is opened in RocksDB's read-only mode: nothing is created, no migrations are
+    /// run and all writes return an error. The database may be inspected while a node is using
+    /// it, although writes made by the node after opening are not visible.
+    pub fn open_read_only(path: &Path) -> Result<Self, DBError> {
+        let db_path = path.join("database");
+        if !db_path.exists() {
+            return Err(DBError::new(format!("No database at {:?}", &path)));
         }
-        .expect("Unable to open local database");;
 
-        Self { db }
+        let options = Options::default();
+        let names: Vec<String> = DB::list_cf(&options, &db_path)?
+            .into_iter()
+            .filter(|name| name != "default")
+            .collect();
+        let columns: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        let db = Self {
+            db: DB::open_cf_for_read_only(&options, db_path, &columns, false)?,
+            read_only: true,
+        };
+
+        // An unversioned database may not have a meta column.
+        if !columns.contains(&META_DB_COLUMN) {
+            return Ok(db);
+        }
+
+        match schema::schema_version(&db)? {
+            Some(version) if version > schema::SCHEMA_VERSION => Err(DBError::new(format!(
+                "Database schema version {} is newer than the version supported by this binary ({})",
+                version,
+                schema::SCHEMA_VERSION
+            ))),
+            _ => Ok(db),
+        }
     }
 
-    /// Create a RocksDB column family. Corresponds to the
-    /// `create_cf()` function on the RocksDB API.
-    #[allow(dead_code)]
-    fn create_col(&mut self, col: &str) -> Result<(), DBError> {
-        match self.db.create_cf(col, &Options::default()) {
-            Err(e) => Err(e.into()),
-            Ok(_) => Ok(()),
+    /// Returns `true` if the database has a column named `col`.
+    ///
+    /// A database created by an older version may lack some of the present columns.
+    pub fn has_column(&self, col: &str) -> bool {
+        self.db.cf_handle(col).is_some()
+    }
+
+    /// Returns an error if the database was opened with `open_read_only`.
+    fn check_writable(&self) -> Result<(), DBError> {
+        if self.read_only {
+            Err(DBError::new("Database is read-only".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Builds the RocksDB `Options` for a column from its `ColumnConfig`.
+fn column_options(config: &ColumnConfig) -> Options {
+    let mut block_options = BlockBasedOptions::default();
+    block_options.set_lru_cache(config.block_cache_size);
+
+    let mut options = Options::default();
+    options.set_block_based_table_factory(&block_options);
+    options.set_compression_type(config.compression.into());
+    options.set_write_buffer_size(config.write_buffer_size);
+    options
+}
+
+impl From<DBCompression> for DBCompressionType {
+    fn from(compression: DBCompression) -> Self {
+        match compression {
+            DBCompression::None => DBCompressionType::None,
+            DBCompression::Snappy => DBCompressionType::Snappy,
+            DBCompression::Lz4 => DBCompressionType::Lz4,
+            DBCompression::Zstd => DBCompressionType::Zstd,
         }
     }
 }
@@ -93,6 +200,7 @@ impl ClientDB for DiskDB {
     /// Will attempt to get the `ColumnFamily` and return an Err
     /// if it fails.
     fn put(&self, col: &str, key: &[u8], val: &[u8]) -> Result<(), DBError> {
+        self.check_writable()?;
         match self.db.cf_handle(col) {
             None => Err(DBError {
                 message: "Unknown column".to_string(),
@@ -122,6 +230,7 @@ impl ClientDB for DiskDB {
     /// Will attempt to get the `ColumnFamily` and return an Err
     /// if it fails.
     fn delete(&self, col: &str, key: &[u8]) -> Result<(), DBError> {
+        self.check_writable()?;
         match self.db.cf_handle(col) {
             None => Err(DBError {
                 message: "Unknown column".to_string(),
@@ -132,6 +241,56 @@ impl ClientDB for DiskDB {
             }
         }
     }
+
+    /// Atomically apply all operations in `batch`.
+    ///
+    /// Corresponds to the `write()` method on the RocksDB API. All columns are resolved before
+    /// anything is written, so an unknown column will cause the whole batch to be rejected.
+    fn write(&self, batch: DBBatch) -> Result<(), DBError> {
+        self.check_writable()?;
+        let mut write_batch = WriteBatch::default();
+
+        for op in batch.ops() {
+            match op {
+                DBOp::Put { col, key, val } => match self.db.cf_handle(col) {
+                    None => {
+                        return Err(DBError {
+                            message: "Unknown column".to_string(),
+                        });
+                    }
+                    Some(handle) => write_batch.put_cf(handle, key, val),
+                },
+                DBOp::Delete { col, key } => match self.db.cf_handle(col) {
+                    None => {
+                        return Err(DBError {
+                            message: "Unknown column".to_string(),
+                        });
+                    }
+                    Some(handle) => write_batch.delete_cf(handle, key),
+                },
+            }
+        }
+
+        self.db.write(write_batch).map_err(|e| e.into())
+    }
+
+    /// Iterate over all keys in `col` which are greater than or equal to `key`.
+    ///
+    /// Corresponds to the `iterator_cf()` method on the RocksDB API, using the default bytewise
+    /// comparator.
+    fn iter_from<'a>(&'a self, col: &str, key: &[u8]) -> Result<ColumnIter<'a>, DBError> {
+        match self.db.cf_handle(col) {
+            None => Err(DBError {
+                message: "Unknown column".to_string(),
+            }),
+            Some(handle) => {
+                let iter = self
+                    .db
+                    .iterator_cf(handle, IteratorMode::From(key, Direction::Forward));
+                Ok(Box::new(iter.map(|(k, v)| (k.to_vec(), v.to_vec()))))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -152,13 +311,7 @@ mod tests {
         let col_name: &str = "TestColumn";
         let column_families = vec![col_name];
 
-        let mut db = DiskDB::open(&path, None);
-
-        for cf in column_families {
-            db.create_col(&cf).unwrap();
-        }
-
-        let db = Arc::new(db);
+        let db = Arc::new(DiskDB::open(&path, &column_families, &DiskDBConfig::default()).unwrap());
 
         let thread_count = 10;
         let write_count = 10;
@@ -194,4 +347,85 @@ mod tests {
         }
         fs::remove_dir_all(&path).unwrap();
     }
+
+    #[test]
+    #[ignore]
+    fn test_rocksdb_iteration_is_ordered() {
+        let pwd = env::current_dir().unwrap();
+        let path = pwd.join("testdb_iter_please_remove");
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        let col_name: &str = "TestColumn";
+
+        let db = DiskDB::open(&path, &[col_name], &DiskDBConfig::default()).unwrap();
+
+        for key in &[vec![3], vec![1, 2], vec![1], vec![2, 0], vec![1, 1]] {
+            db.put(col_name, key, &[42]).unwrap();
+        }
+
+        let keys: Vec<Vec<u8>> = db.iter_column(col_name).unwrap().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            vec![vec![1], vec![1, 1], vec![1, 2], vec![2, 0], vec![3]]
+        );
+
+        let keys: Vec<Vec<u8>> = db
+            .iter_from(col_name, &[1, 2])
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![vec![1, 2], vec![2, 0], vec![3]]);
+
+        let keys: Vec<Vec<u8>> = db
+            .iter_prefix(col_name, &[1])
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![vec![1], vec![1, 1], vec![1, 2]]);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rocksdb_creates_missing_columns() {
+        let pwd = env::current_dir().unwrap();
+        let path = pwd.join("testdb_columns_please_remove");
+        let _ = fs::remove_dir_all(&path);
+
+        let col_a: &str = "ColumnA";
+        let col_b: &str = "ColumnB";
+
+        let config = DiskDBConfig::default();
+
+        {
+            let db = DiskDB::open(&path, &[col_a], &config).unwrap();
+            // The meta column is created even though it was not requested.
+            assert_eq!(
+                schema::schema_version(&db).unwrap(),
+                Some(schema::SCHEMA_VERSION)
+            );
+            db.put(col_a, &[1], &[42]).unwrap();
+            assert!(db.put(col_b, &[1], &[42]).is_err());
+        }
+
+        // Re-opening with an additional column must create it and retain the existing data.
+        {
+            let db = DiskDB::open(&path, &[col_b], &config).unwrap();
+            assert_eq!(db.get(col_a, &[1]).unwrap(), Some(vec![42]));
+            db.put(col_b, &[1], &[42]).unwrap();
+        }
+
+        // A read-only database has all existing columns, but rejects writes.
+        {
+            let db = DiskDB::open_read_only(&path).unwrap();
+            assert_eq!(db.get(col_b, &[1]).unwrap(), Some(vec![42]));
+            assert!(db.put(col_a, &[2], &[42]).is_err());
+            assert!(db.delete(col_a, &[1]).is_err());
+            assert!(db.write(DBBatch::new()).is_err());
+        }
+
+        fs::remove_dir_all(&path).unwrap();
+    }
 }
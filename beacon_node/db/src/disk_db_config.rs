@@ -0,0 +1,178 @@
+use super::stores::{COLD_STATES_DB_COLUMN, COLUMNS, STATES_DB_COLUMN};
+use std::collections::HashMap;
+
+/// The compression algorithm applied to the blocks of a RocksDB column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DBCompression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+/// RocksDB tuning options for a single column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnConfig {
+    /// The size of the LRU cache of uncompressed blocks, in bytes.
+    pub block_cache_size: usize,
+    /// The compression applied to blocks written to disk.
+    pub compression: DBCompression,
+    /// The amount of data to build up in memory before writing to disk, in bytes.
+    pub write_buffer_size: usize,
+}
+
+impl Default for ColumnConfig {
+    /// The RocksDB defaults.
+    fn default() -> Self {
+        Self {
+            block_cache_size: 8 * 1024 * 1024,
+            compression: DBCompression::Snappy,
+            write_buffer_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// RocksDB tuning options for a `DiskDB`.
+///
+/// Any column without an entry in `columns` uses `default_column`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskDBConfig {
+    pub default_column: ColumnConfig,
+    pub columns: HashMap<String, ColumnConfig>,
+}
+
+impl DiskDBConfig {
+    /// Returns the options for the given column.
+    pub fn column(&self, col: &str) -> &ColumnConfig {
+        self.columns.get(col).unwrap_or(&self.default_column)
+    }
+
+    /// Applies an option of the form `[COLUMN.]OPTION=VALUE`.
+    ///
+    /// `OPTION` is one of `block-cache-size` or `write-buffer-size` (in bytes), or `compression`
+    /// (`none`, `snappy`, `lz4` or `zstd`). Without a `COLUMN`, the option applies to
+    /// `default_column`, and so only to the columns without their own entry.
+    pub fn apply_option(&mut self, option: &str) -> Result<(), String> {
+        let mut parts = option.splitn(2, '=');
+        let name = parts.next().unwrap_or("");
+        let value = parts
+            .next()
+            .ok_or_else(|| format!("Expected OPTION=VALUE, got {:?}", option))?;
+
+        let mut name_parts = name.rsplitn(2, '.');
+        let option_name = name_parts.next().unwrap_or("");
+        let config = match name_parts.next() {
+            None => &mut self.default_column,
+            Some(col) => {
+                if !COLUMNS.contains(&col) {
+                    return Err(format!("Unknown column {:?}", col));
+                }
+                let default_column = self.default_column.clone();
+                self.columns
+                    .entry(col.to_string())
+                    .or_insert(default_column)
+            }
+        };
+
+        let parse_size = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid size {:?}", value))
+        };
+        match option_name {
+            "block-cache-size" => config.block_cache_size = parse_size(value)?,
+            "write-buffer-size" => config.write_buffer_size = parse_size(value)?,
+            "compression" => {
+                config.compression = match value {
+                    "none" => DBCompression::None,
+                    "snappy" => DBCompression::Snappy,
+                    "lz4" => DBCompression::Lz4,
+                    "zstd" => DBCompression::Zstd,
+                    _ => return Err(format!("Unknown compression {:?}", value)),
+                }
+            }
+            _ => return Err(format!("Unknown database option {:?}", option_name)),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DiskDBConfig {
+    /// States are large and highly compressible, so they are given a larger cache and stronger
+    /// compression than the other columns. Cold states are rarely read, so they are compressed as
+    /// much as possible.
+    fn default() -> Self {
+        let mut columns = HashMap::new();
+        columns.insert(
+            STATES_DB_COLUMN.to_string(),
+            ColumnConfig {
+                block_cache_size: 64 * 1024 * 1024,
+                compression: DBCompression::Lz4,
+                ..ColumnConfig::default()
+            },
+        );
+        columns.insert(
+            COLD_STATES_DB_COLUMN.to_string(),
+            ColumnConfig {
+                compression: DBCompression::Zstd,
+                ..ColumnConfig::default()
+            },
+        );
+
+        Self {
+            default_column: ColumnConfig::default(),
+            columns,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stores::BLOCKS_DB_COLUMN;
+
+    #[test]
+    fn test_apply_column_option() {
+        let mut config = DiskDBConfig::default();
+
+        config.apply_option("blocks.compression=zstd").unwrap();
+        config.apply_option("states.block-cache-size=1024").unwrap();
+
+        assert_eq!(
+            config.column(BLOCKS_DB_COLUMN).compression,
+            DBCompression::Zstd
+        );
+        // Other options of a column keep their previous values.
+        assert_eq!(config.column(STATES_DB_COLUMN).block_cache_size, 1024);
+        assert_eq!(
+            config.column(STATES_DB_COLUMN).compression,
+            DBCompression::Lz4
+        );
+    }
+
+    #[test]
+    fn test_apply_default_column_option() {
+        let mut config = DiskDBConfig::default();
+
+        config.apply_option("write-buffer-size=4096").unwrap();
+
+        assert_eq!(config.column(BLOCKS_DB_COLUMN).write_buffer_size, 4096);
+        assert_eq!(
+            config.column(STATES_DB_COLUMN).write_buffer_size,
+            ColumnConfig::default().write_buffer_size
+        );
+    }
+
+    #[test]
+    fn test_invalid_options_are_rejected() {
+        let mut config = DiskDBConfig::default();
+
+        assert!(config.apply_option("compression").is_err());
+        assert!(config.apply_option("compression=gzip").is_err());
+        assert!(config.apply_option("nonsense.compression=lz4").is_err());
+        assert!(config.apply_option("blocks.cache=1").is_err());
+        assert!(config.apply_option("block-cache-size=lots").is_err());
+        assert_eq!(config, DiskDBConfig::default());
+    }
+}
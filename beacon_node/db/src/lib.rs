@@ -1,17 +1,19 @@
-extern crate blake2_rfc as blake2;
 extern crate bls;
 extern crate rocksdb;
 
 mod disk_db;
+mod disk_db_config;
 mod memory_db;
+pub mod schema;
 pub mod stores;
 mod traits;
 
 use self::stores::COLUMNS;
 
 pub use self::disk_db::DiskDB;
+pub use self::disk_db_config::{ColumnConfig, DBCompression, DiskDBConfig};
 pub use self::memory_db::MemoryDB;
-pub use self::traits::{ClientDB, DBError, DBValue};
+pub use self::traits::{ClientDB, ColumnIter, DBBatch, DBError, DBOp, DBValue};
 
 /// Currently available database options
 #[derive(Debug, Clone)]
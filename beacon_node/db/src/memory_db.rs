@@ -1,19 +1,20 @@
-use super::blake2::blake2b::blake2b;
 use super::COLUMNS;
-use super::{ClientDB, DBError, DBValue};
-use std::collections::{HashMap, HashSet};
+use super::{ClientDB, ColumnIter, DBBatch, DBError, DBOp, DBValue};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::RwLock;
 
-type DBHashMap = HashMap<Vec<u8>, Vec<u8>>;
-type ColumnHashSet = HashSet<String>;
+type ColumnMap = BTreeMap<Vec<u8>, Vec<u8>>;
+type DBHashMap = HashMap<String, ColumnMap>;
 
 /// An in-memory database implementing the ClientDB trait.
 ///
 /// It is not particularily optimized, it exists for ease and speed of testing. It's not expected
 /// this DB would be used outside of tests.
+///
+/// Each column is an ordered map so that iteration yields keys in the same (bytewise ascending)
+/// order as RocksDB.
 pub struct MemoryDB {
     db: RwLock<DBHashMap>,
-    known_columns: RwLock<ColumnHashSet>,
 }
 
 impl MemoryDB {
@@ -22,20 +23,19 @@ impl MemoryDB {
     /// All columns must be supplied initially, you will get an error if you try to access a column
     /// that was not declared here. This condition is enforced artificially to simulate RocksDB.
     pub fn open() -> Self {
-        let db: DBHashMap = HashMap::new();
-        let mut known_columns: ColumnHashSet = HashSet::new();
+        let mut db: DBHashMap = HashMap::new();
         for col in &COLUMNS {
-            known_columns.insert(col.to_string());
+            db.insert(col.to_string(), BTreeMap::new());
         }
         Self {
             db: RwLock::new(db),
-            known_columns: RwLock::new(known_columns),
         }
     }
 
-    /// Hashes a key and a column name in order to get a unique key for the supplied column.
-    fn get_key_for_col(col: &str, key: &[u8]) -> Vec<u8> {
-        blake2b(32, col.as_bytes(), key).as_bytes().to_vec()
+    fn unknown_column() -> DBError {
+        DBError {
+            message: "Unknown column".to_string(),
+        }
     }
 }
 
@@ -44,15 +44,10 @@ impl ClientDB for MemoryDB {
     fn get(&self, col: &str, key: &[u8]) -> Result<Option<DBValue>, DBError> {
         // Panic if the DB locks are poisoned.
         let db = self.db.read().unwrap();
-        let known_columns = self.known_columns.read().unwrap();
-
-        if known_columns.contains(&col.to_string()) {
-            let column_key = MemoryDB::get_key_for_col(col, key);
-            Ok(db.get(&column_key).and_then(|val| Some(val.clone())))
-        } else {
-            Err(DBError {
-                message: "Unknown column".to_string(),
-            })
+
+        match db.get(col) {
+            Some(column) => Ok(column.get(key).cloned()),
+            None => Err(MemoryDB::unknown_column()),
         }
     }
 
@@ -60,16 +55,13 @@ impl ClientDB for MemoryDB {
     fn put(&self, col: &str, key: &[u8], val: &[u8]) -> Result<(), DBError> {
         // Panic if the DB locks are poisoned.
         let mut db = self.db.write().unwrap();
-        let known_columns = self.known_columns.read().unwrap();
-
-        if known_columns.contains(&col.to_string()) {
-            let column_key = MemoryDB::get_key_for_col(col, key);
-            db.insert(column_key, val.to_vec());
-            Ok(())
-        } else {
-            Err(DBError {
-                message: "Unknown column".to_string(),
-            })
+
+        match db.get_mut(col) {
+            Some(column) => {
+                column.insert(key.to_vec(), val.to_vec());
+                Ok(())
+            }
+            None => Err(MemoryDB::unknown_column()),
         }
     }
 
@@ -77,15 +69,10 @@ impl ClientDB for MemoryDB {
     fn exists(&self, col: &str, key: &[u8]) -> Result<bool, DBError> {
         // Panic if the DB locks are poisoned.
         let db = self.db.read().unwrap();
-        let known_columns = self.known_columns.read().unwrap();
-
-        if known_columns.contains(&col.to_string()) {
-            let column_key = MemoryDB::get_key_for_col(col, key);
-            Ok(db.contains_key(&column_key))
-        } else {
-            Err(DBError {
-                message: "Unknown column".to_string(),
-            })
+
+        match db.get(col) {
+            Some(column) => Ok(column.contains_key(key)),
+            None => Err(MemoryDB::unknown_column()),
         }
     }
 
@@ -93,16 +80,66 @@ impl ClientDB for MemoryDB {
     fn delete(&self, col: &str, key: &[u8]) -> Result<(), DBError> {
         // Panic if the DB locks are poisoned.
         let mut db = self.db.write().unwrap();
-        let known_columns = self.known_columns.read().unwrap();
-
-        if known_columns.contains(&col.to_string()) {
-            let column_key = MemoryDB::get_key_for_col(col, key);
-            db.remove(&column_key);
-            Ok(())
-        } else {
-            Err(DBError {
-                message: "Unknown column".to_string(),
-            })
+
+        match db.get_mut(col) {
+            Some(column) => {
+                column.remove(key);
+                Ok(())
+            }
+            None => Err(MemoryDB::unknown_column()),
+        }
+    }
+
+    /// Atomically apply all operations in `batch`.
+    ///
+    /// The write lock is held for the entire batch and all columns are checked before any
+    /// operation is applied, so readers never observe a partially applied batch.
+    fn write(&self, batch: DBBatch) -> Result<(), DBError> {
+        // Panic if the DB locks are poisoned.
+        let mut db = self.db.write().unwrap();
+
+        let all_columns_known = batch.ops().iter().all(|op| match op {
+            DBOp::Put { col, .. } | DBOp::Delete { col, .. } => db.contains_key(col),
+        });
+        if !all_columns_known {
+            return Err(MemoryDB::unknown_column());
+        }
+
+        for op in batch.ops() {
+            match op {
+                DBOp::Put { col, key, val } => {
+                    if let Some(column) = db.get_mut(col) {
+                        column.insert(key.clone(), val.clone());
+                    }
+                }
+                DBOp::Delete { col, key } => {
+                    if let Some(column) = db.get_mut(col) {
+                        column.remove(key);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Iterate over all keys in `col` which are greater than or equal to `key`.
+    ///
+    /// The iterator is a snapshot of the column at the time of the call; later writes are not
+    /// reflected.
+    fn iter_from<'a>(&'a self, col: &str, key: &[u8]) -> Result<ColumnIter<'a>, DBError> {
+        // Panic if the DB locks are poisoned.
+        let db = self.db.read().unwrap();
+
+        match db.get(col) {
+            Some(column) => {
+                let items: Vec<(DBValue, DBValue)> = column
+                    .range(key.to_vec()..)
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                Ok(Box::new(items.into_iter()))
+            }
+            None => Err(MemoryDB::unknown_column()),
         }
     }
 }
@@ -133,6 +170,86 @@ mod tests {
         assert_eq!(db.get(col_a, "dogs".as_bytes()).unwrap(), None);
     }
 
+    #[test]
+    fn test_memorydb_batch_write() {
+        let col_a: &str = BLOCKS_DB_COLUMN;
+        let col_b: &str = VALIDATOR_DB_COLUMN;
+
+        let db = MemoryDB::open();
+
+        db.put(col_a, "dogs".as_bytes(), "lol".as_bytes()).unwrap();
+
+        let mut batch = DBBatch::new();
+        batch.put(col_a, "cats".as_bytes(), "meow".as_bytes());
+        batch.put(col_b, "cats".as_bytes(), "purr".as_bytes());
+        batch.delete(col_a, "dogs".as_bytes());
+        db.write(batch).unwrap();
+
+        assert_eq!(
+            db.get(col_a, "cats".as_bytes()).unwrap().unwrap(),
+            "meow".as_bytes()
+        );
+        assert_eq!(
+            db.get(col_b, "cats".as_bytes()).unwrap().unwrap(),
+            "purr".as_bytes()
+        );
+        assert_eq!(db.get(col_a, "dogs".as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_memorydb_batch_is_atomic() {
+        let col_a: &str = BLOCKS_DB_COLUMN;
+        let col_x: &str = "ColumnX";
+
+        let db = MemoryDB::open();
+
+        /*
+         * Test that a batch containing an unknown column applies none of its operations.
+         */
+        let mut batch = DBBatch::new();
+        batch.put(col_a, "cats".as_bytes(), "lol".as_bytes());
+        batch.put(col_x, "cats".as_bytes(), "lol".as_bytes());
+        assert!(db.write(batch).is_err());
+
+        assert!(!db.exists(col_a, "cats".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_memorydb_iteration_is_ordered() {
+        let col_a: &str = BLOCKS_DB_COLUMN;
+        let col_b: &str = VALIDATOR_DB_COLUMN;
+
+        let db = MemoryDB::open();
+
+        for key in &[vec![3], vec![1, 2], vec![1], vec![2, 0], vec![1, 1]] {
+            db.put(col_a, key, &[42]).unwrap();
+        }
+        db.put(col_b, &[0], &[42]).unwrap();
+
+        let keys: Vec<Vec<u8>> = db.iter_column(col_a).unwrap().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            vec![vec![1], vec![1, 1], vec![1, 2], vec![2, 0], vec![3]]
+        );
+
+        let keys: Vec<Vec<u8>> = db
+            .iter_from(col_a, &[1, 2])
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![vec![1, 2], vec![2, 0], vec![3]]);
+
+        let keys: Vec<Vec<u8>> = db
+            .iter_prefix(col_a, &[1])
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![vec![1], vec![1, 1], vec![1, 2]]);
+
+        assert_eq!(db.iter_prefix(col_a, &[4]).unwrap().count(), 0);
+        assert!(db.iter_column("ColumnX").is_err());
+    }
+
     #[test]
     fn test_memorydb_column_access() {
         let col_a: &str = BLOCKS_DB_COLUMN;
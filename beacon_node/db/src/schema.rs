@@ -0,0 +1,223 @@
+use super::stores::META_DB_COLUMN;
+use super::{ClientDB, DBError};
+use ssz::{decode, ssz_encode};
+
+/// The version of the database layout and encoding used by this binary.
+///
+/// Must be incremented (and a `Migration` added to `MIGRATIONS`) whenever a change is made which
+/// would prevent an existing database from being read, e.g., a change to a column, a key format
+/// or the SSZ encoding of a stored type.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// The key of the schema version in the `META_DB_COLUMN`.
+pub const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// A function which converts a database from version `from` to version `from + 1`.
+pub struct Migration {
+    pub from: u64,
+    pub description: &'static str,
+    pub migrate: fn(&dyn ClientDB) -> Result<(), DBError>,
+}
+
+/// All known migrations, in order.
+///
+/// Version `0` refers to databases created before the schema version was recorded. There is no
+/// migration from version `0`: those databases lack the canonical root index and the chain
+/// metadata, which cannot be rebuilt without running fork choice over every stored block. They
+/// are rejected instead (see `migrate`).
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// Reads the schema version of `db`.
+///
+/// Returns `None` if no version has been recorded.
+pub fn schema_version(db: &dyn ClientDB) -> Result<Option<u64>, DBError> {
+    match db.get(META_DB_COLUMN, SCHEMA_VERSION_KEY)? {
+        None => Ok(None),
+        Some(ssz) => {
+            let version = decode::<u64>(&ssz).map_err(|_| DBError {
+                message: "Bad schema version SSZ.".to_string(),
+            })?;
+            Ok(Some(version))
+        }
+    }
+}
+
+/// Ensures the schema of `db` matches `SCHEMA_VERSION`, running any required migrations.
+///
+/// `columns` are the data columns of `db` (i.e., excluding the `META_DB_COLUMN`). If none of them
+/// hold any data the database is new, and is marked with the present version.
+///
+/// ## Errors
+///
+/// Returns an error if the database is newer than this binary, if it is an unversioned database
+/// which holds data, or if a migration fails.
+pub fn migrate(db: &dyn ClientDB, columns: &[&str]) -> Result<(), DBError> {
+    migrate_with(db, columns, MIGRATIONS, SCHEMA_VERSION)
+}
+
+fn migrate_with(
+    db: &dyn ClientDB,
+    columns: &[&str],
+    migrations: &[Migration],
+    target: u64,
+) -> Result<(), DBError> {
+    let recorded = schema_version(db)?;
+    let mut version = match recorded {
+        Some(version) => version,
+        None if is_empty(db, columns)? => target,
+        None => 0,
+    };
+
+    if version > target {
+        return Err(DBError::new(format!(
+            "Database schema version {} is newer than the version supported by this binary ({}). \
+             Upgrade the client or use a different database.",
+            version, target
+        )));
+    }
+
+    while version < target {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| {
+                if version == 0 {
+                    DBError::new(
+                        "Database was created by a client which did not record a schema version \
+                         and cannot be upgraded. Remove the database directory and sync again."
+                            .to_string(),
+                    )
+                } else {
+                    DBError::new(format!(
+                        "No migration from database schema version {}",
+                        version
+                    ))
+                }
+            })?;
+
+        (migration.migrate)(db).map_err(|e| {
+            DBError::new(format!(
+                "Database migration from version {} failed ({}): {}",
+                version, migration.description, e.message
+            ))
+        })?;
+
+        // Record progress after each step so an interrupted upgrade resumes where it stopped.
+        version += 1;
+        db.put(META_DB_COLUMN, SCHEMA_VERSION_KEY, &ssz_encode(&version))?;
+    }
+
+    if recorded.is_none() {
+        db.put(META_DB_COLUMN, SCHEMA_VERSION_KEY, &ssz_encode(&version))?;
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if none of `columns` hold any data.
+fn is_empty(db: &dyn ClientDB, columns: &[&str]) -> Result<bool, DBError> {
+    for column in columns {
+        if db.iter_column(column)?.next().is_some() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::stores::{BLOCKS_DB_COLUMN, VALIDATOR_DB_COLUMN};
+    use super::super::MemoryDB;
+    use super::*;
+
+    /// The data columns of a client database.
+    const COLUMNS: &[&str] = &[BLOCKS_DB_COLUMN, VALIDATOR_DB_COLUMN];
+
+    #[test]
+    fn test_new_database_is_current() {
+        let db = MemoryDB::open();
+
+        assert_eq!(schema_version(&db).unwrap(), None);
+        migrate(&db, COLUMNS).unwrap();
+        assert_eq!(schema_version(&db).unwrap(), Some(SCHEMA_VERSION));
+
+        // Opening again is a no-op.
+        migrate(&db, COLUMNS).unwrap();
+        assert_eq!(schema_version(&db).unwrap(), Some(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_newer_database_is_rejected() {
+        let db = MemoryDB::open();
+
+        db.put(
+            META_DB_COLUMN,
+            SCHEMA_VERSION_KEY,
+            &ssz_encode(&(SCHEMA_VERSION + 1)),
+        )
+        .unwrap();
+
+        assert!(migrate(&db, COLUMNS).is_err());
+        assert_eq!(schema_version(&db).unwrap(), Some(SCHEMA_VERSION + 1));
+    }
+
+    #[test]
+    fn test_unversioned_database_with_data_is_rejected() {
+        let db = MemoryDB::open();
+        db.put(VALIDATOR_DB_COLUMN, b"key", b"data").unwrap();
+
+        assert!(migrate(&db, COLUMNS).is_err());
+        assert_eq!(schema_version(&db).unwrap(), None);
+    }
+
+    #[test]
+    fn test_only_the_given_columns_are_read() {
+        let db = MemoryDB::open();
+        db.put(BLOCKS_DB_COLUMN, b"block", b"data").unwrap();
+
+        // The database is empty as far as the given columns are concerned.
+        migrate(&db, &[VALIDATOR_DB_COLUMN]).unwrap();
+        assert_eq!(schema_version(&db).unwrap(), Some(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_migrations_run_in_order() {
+        fn first(db: &dyn ClientDB) -> Result<(), DBError> {
+            db.put(VALIDATOR_DB_COLUMN, b"a", b"1")
+        }
+        fn second(db: &dyn ClientDB) -> Result<(), DBError> {
+            // Must observe the result of `first`.
+            match db.get(VALIDATOR_DB_COLUMN, b"a")? {
+                Some(_) => db.put(VALIDATOR_DB_COLUMN, b"b", b"2"),
+                None => Err(DBError::new("First migration not applied".to_string())),
+            }
+        }
+        let migrations = [
+            Migration {
+                from: 1,
+                description: "second",
+                migrate: second,
+            },
+            Migration {
+                from: 0,
+                description: "first",
+                migrate: first,
+            },
+        ];
+
+        // An unversioned database with existing data is version 0.
+        let db = MemoryDB::open();
+        db.put(BLOCKS_DB_COLUMN, b"block", b"data").unwrap();
+
+        migrate_with(&db, COLUMNS, &migrations, 2).unwrap();
+
+        assert_eq!(schema_version(&db).unwrap(), Some(2));
+        assert!(db.exists(VALIDATOR_DB_COLUMN, b"b").unwrap());
+
+        // A missing migration is an error.
+        let db = MemoryDB::open();
+        db.put(BLOCKS_DB_COLUMN, b"block", b"data").unwrap();
+        assert!(migrate_with(&db, COLUMNS, &migrations[..1], 2).is_err());
+        assert_eq!(schema_version(&db).unwrap(), None);
+    }
+}
@@ -1,16 +1,10 @@
 use super::BLOCKS_DB_COLUMN as DB_COLUMN;
-use super::{ClientDB, DBError};
+use super::CANONICAL_ROOTS_DB_COLUMN;
+use super::{ClientDB, DBBatch, DBError};
 use ssz::decode;
 use std::sync::Arc;
 use types::{BeaconBlock, Hash256, Slot};
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum BeaconBlockAtSlotError {
-    UnknownBeaconBlock(Hash256),
-    InvalidBeaconBlock(Hash256),
-    DBError(String),
-}
-
 pub struct BeaconBlockStore<T>
 where
     T: ClientDB,
@@ -37,46 +31,78 @@ impl<T: ClientDB> BeaconBlockStore<T> {
             }
         }
     }
+}
 
-    /// Retrieve the block at a slot given a "head_hash" and a slot.
-    ///
-    /// A "head_hash" must be a block hash with a slot number greater than or equal to the desired
-    /// slot.
-    ///
-    /// This function will read each block down the chain until it finds a block with the given
-    /// slot number. If the slot is skipped, the function will return None.
-    ///
-    /// If a block is found, a tuple of (block_hash, serialized_block) is returned.
-    ///
-    /// Note: this function uses a loop instead of recursion as the compiler is over-strict when it
-    /// comes to recursion and the `impl Trait` pattern. See:
-    /// https://stackoverflow.com/questions/54032940/using-impl-trait-in-a-recursive-function
-    pub fn block_at_slot(
-        &self,
-        head_hash: &Hash256,
-        slot: Slot,
-    ) -> Result<Option<(Hash256, BeaconBlock)>, BeaconBlockAtSlotError> {
-        let mut current_hash = *head_hash;
-
-        loop {
-            if let Some(block) = self.get_deserialized(&current_hash)? {
-                if block.slot == slot {
-                    break Ok(Some((current_hash, block)));
-                } else if block.slot < slot {
-                    break Ok(None);
+/// Methods for the canonical `slot -> block_root` index.
+///
+/// The index holds an entry for every slot from genesis to the canonical head. Each entry is the
+/// root of the canonical block at that slot or, if the slot was skipped, the root of the most
+/// recent canonical block prior to that slot (i.e., the same semantics as
+/// `BeaconState::get_block_root`).
+///
+/// Keys are the big-endian bytes of the slot, so iteration yields entries in slot order.
+impl<T: ClientDB> BeaconBlockStore<T> {
+    fn canonical_root_key(slot: Slot) -> [u8; 8] {
+        slot.as_u64().to_be_bytes()
+    }
+
+    /// Returns the canonical block root at `slot`, if it is known.
+    pub fn canonical_root_at_slot(&self, slot: Slot) -> Result<Option<Hash256>, DBError> {
+        let key = Self::canonical_root_key(slot);
+        match self.db.get(CANONICAL_ROOTS_DB_COLUMN, &key)? {
+            Some(bytes) => {
+                if bytes.len() == 32 {
+                    Ok(Some(Hash256::from_slice(&bytes)))
                 } else {
-                    current_hash = block.previous_block_root;
+                    Err(DBError {
+                        message: "Bad canonical block root.".to_string(),
+                    })
                 }
-            } else {
-                break Err(BeaconBlockAtSlotError::UnknownBeaconBlock(current_hash));
             }
+            None => Ok(None),
         }
     }
-}
 
-impl From<DBError> for BeaconBlockAtSlotError {
-    fn from(e: DBError) -> Self {
-        BeaconBlockAtSlotError::DBError(e.message)
+    /// Iterate over the canonical `(slot, block_root)` pairs, starting at `slot`, in ascending
+    /// slot order.
+    ///
+    /// An entry which is not a valid slot and root yields an error, rather than being skipped.
+    pub fn canonical_roots_from(
+        &self,
+        slot: Slot,
+    ) -> Result<impl Iterator<Item = Result<(Slot, Hash256), DBError>> + '_, DBError> {
+        let key = Self::canonical_root_key(slot);
+        Ok(self
+            .db
+            .iter_from(CANONICAL_ROOTS_DB_COLUMN, &key)?
+            .map(|(key, root)| {
+                if key.len() != 8 || root.len() != 32 {
+                    return Err(DBError {
+                        message: "Bad canonical root index entry.".to_string(),
+                    });
+                }
+
+                let mut slot_bytes = [0; 8];
+                slot_bytes.copy_from_slice(&key);
+                Ok((
+                    Slot::from(u64::from_be_bytes(slot_bytes)),
+                    Hash256::from_slice(&root),
+                ))
+            }))
+    }
+
+    /// Queue setting the canonical block root at `slot` in `batch`.
+    pub fn batch_put_canonical_root(&self, batch: &mut DBBatch, slot: Slot, block_root: &Hash256) {
+        batch.put(
+            CANONICAL_ROOTS_DB_COLUMN,
+            &Self::canonical_root_key(slot),
+            block_root.as_bytes(),
+        )
+    }
+
+    /// Queue removing the canonical block root at `slot` in `batch`.
+    pub fn batch_delete_canonical_root(&self, batch: &mut DBBatch, slot: Slot) {
+        batch.delete(CANONICAL_ROOTS_DB_COLUMN, &Self::canonical_root_key(slot))
     }
 }
 
@@ -88,59 +114,86 @@ mod tests {
     use std::sync::Arc;
     use std::thread;
 
-    use ssz::ssz_encode;
-    use types::test_utils::{SeedableRng, TestRandom, XorShiftRng};
-    use types::BeaconBlock;
     use types::Hash256;
 
     test_crud_for_store!(BeaconBlockStore, DB_COLUMN);
 
     #[test]
-    fn head_hash_slot_too_low() {
+    fn test_canonical_roots() {
         let db = Arc::new(MemoryDB::open());
-        let bs = Arc::new(BeaconBlockStore::new(db.clone()));
-        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let bs = BeaconBlockStore::new(db.clone());
+
+        let roots: Vec<Hash256> = (0..300_u64).map(Hash256::from_low_u64_le).collect();
+
+        let mut batch = DBBatch::new();
+        for (i, root) in roots.iter().enumerate() {
+            bs.batch_put_canonical_root(&mut batch, Slot::from(i), root);
+        }
+        bs.write(batch).unwrap();
 
-        let mut block = BeaconBlock::random_for_test(&mut rng);
-        block.slot = Slot::from(10_u64);
+        assert_eq!(
+            bs.canonical_root_at_slot(Slot::from(42_u64)).unwrap(),
+            Some(roots[42])
+        );
+        assert_eq!(
+            bs.canonical_root_at_slot(Slot::from(300_u64)).unwrap(),
+            None
+        );
 
-        let block_root = block.canonical_root();
-        bs.put(&block_root, &ssz_encode(&block)).unwrap();
+        // Slot 256 must sort after slot 255, which requires big-endian keys.
+        let found: Vec<(Slot, Hash256)> = bs
+            .canonical_roots_from(Slot::from(254_u64))
+            .unwrap()
+            .take(3)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            found,
+            vec![
+                (Slot::from(254_u64), roots[254]),
+                (Slot::from(255_u64), roots[255]),
+                (Slot::from(256_u64), roots[256]),
+            ]
+        );
 
-        let result = bs.block_at_slot(&block_root, Slot::from(11_u64)).unwrap();
-        assert_eq!(result, None);
+        let mut batch = DBBatch::new();
+        bs.batch_delete_canonical_root(&mut batch, Slot::from(42_u64));
+        bs.write(batch).unwrap();
+
+        assert_eq!(bs.canonical_root_at_slot(Slot::from(42_u64)).unwrap(), None);
     }
 
     #[test]
-    fn test_invalid_block_at_slot() {
+    fn test_malformed_canonical_root() {
         let db = Arc::new(MemoryDB::open());
-        let store = BeaconBlockStore::new(db.clone());
+        let bs = BeaconBlockStore::new(db.clone());
 
-        let ssz = "definitly not a valid block".as_bytes();
-        let hash = &Hash256::from([0xAA; 32]);
+        let mut batch = DBBatch::new();
+        bs.batch_put_canonical_root(&mut batch, Slot::from(1_u64), &Hash256::from([0xAA; 32]));
+        bs.write(batch).unwrap();
+        db.put(CANONICAL_ROOTS_DB_COLUMN, &2_u64.to_be_bytes(), &[0xBB; 31])
+            .unwrap();
 
-        db.put(DB_COLUMN, hash.as_bytes(), ssz).unwrap();
+        let mut iter = bs.canonical_roots_from(Slot::from(1_u64)).unwrap();
         assert_eq!(
-            store.block_at_slot(hash, Slot::from(42_u64)),
-            Err(BeaconBlockAtSlotError::DBError(
-                "Bad BeaconBlock SSZ.".into()
-            ))
+            iter.next().unwrap().unwrap(),
+            (Slot::from(1_u64), Hash256::from([0xAA; 32]))
         );
+        assert!(iter.next().unwrap().is_err());
     }
 
     #[test]
-    fn test_unknown_block_at_slot() {
+    fn test_invalid_block() {
         let db = Arc::new(MemoryDB::open());
         let store = BeaconBlockStore::new(db.clone());
 
-        let ssz = "some bytes".as_bytes();
+        let ssz = "definitly not a valid block".as_bytes();
         let hash = &Hash256::from([0xAA; 32]);
-        let other_hash = &Hash256::from([0xBB; 32]);
 
         db.put(DB_COLUMN, hash.as_bytes(), ssz).unwrap();
         assert_eq!(
-            store.block_at_slot(other_hash, Slot::from(42_u64)),
-            Err(BeaconBlockAtSlotError::UnknownBeaconBlock(*other_hash))
+            store.get_deserialized(hash).unwrap_err().message,
+            "Bad BeaconBlock SSZ."
         );
     }
 
@@ -179,68 +232,4 @@ mod tests {
             }
         }
     }
-
-    #[test]
-    #[ignore]
-    fn test_block_at_slot() {
-        let db = Arc::new(MemoryDB::open());
-        let bs = Arc::new(BeaconBlockStore::new(db.clone()));
-        let mut rng = XorShiftRng::from_seed([42; 16]);
-
-        // Specify test block parameters.
-        let hashes = [
-            Hash256::from([0; 32]),
-            Hash256::from([1; 32]),
-            Hash256::from([2; 32]),
-            Hash256::from([3; 32]),
-            Hash256::from([4; 32]),
-        ];
-        let parent_hashes = [
-            Hash256::from([255; 32]), // Genesis block.
-            Hash256::from([0; 32]),
-            Hash256::from([1; 32]),
-            Hash256::from([2; 32]),
-            Hash256::from([3; 32]),
-        ];
-        let unknown_hash = Hash256::from([101; 32]); // different from all above
-        let slots: Vec<Slot> = vec![0, 1, 3, 4, 5].iter().map(|x| Slot::new(*x)).collect();
-
-        // Generate a vec of random blocks and store them in the DB.
-        let block_count = 5;
-        let mut blocks: Vec<BeaconBlock> = Vec::with_capacity(5);
-        for i in 0..block_count {
-            let mut block = BeaconBlock::random_for_test(&mut rng);
-
-            block.previous_block_root = parent_hashes[i];
-            block.slot = slots[i];
-
-            let ssz = ssz_encode(&block);
-            db.put(DB_COLUMN, hashes[i].as_bytes(), &ssz).unwrap();
-
-            blocks.push(block);
-        }
-
-        // Test that certain slots can be reached from certain hashes.
-        let test_cases = vec![(4, 4), (4, 3), (4, 2), (4, 1), (4, 0)];
-        for (hashes_index, slot_index) in test_cases {
-            let (matched_block_hash, block) = bs
-                .block_at_slot(&hashes[hashes_index], slots[slot_index])
-                .unwrap()
-                .unwrap();
-            assert_eq!(matched_block_hash, hashes[slot_index]);
-            assert_eq!(block.slot, slots[slot_index]);
-        }
-
-        let ssz = bs.block_at_slot(&hashes[4], Slot::new(2)).unwrap();
-        assert_eq!(ssz, None);
-
-        let ssz = bs.block_at_slot(&hashes[4], Slot::new(6)).unwrap();
-        assert_eq!(ssz, None);
-
-        let ssz = bs.block_at_slot(&unknown_hash, Slot::new(2));
-        assert_eq!(
-            ssz,
-            Err(BeaconBlockAtSlotError::UnknownBeaconBlock(unknown_hash))
-        );
-    }
 }
@@ -1,14 +1,30 @@
+use super::COLD_STATES_DB_COLUMN;
 use super::STATES_DB_COLUMN as DB_COLUMN;
-use super::{ClientDB, DBError};
+use super::{ClientDB, DBBatch, DBError};
 use ssz::decode;
 use std::sync::Arc;
-use types::{BeaconState, Hash256};
+use types::{BeaconState, Hash256, Slot};
 
+/// The default number of epochs between each state kept in the cold column.
+pub const DEFAULT_EPOCHS_PER_COLD_STATE: u64 = 32;
+
+/// Stores `BeaconState`s in two columns:
+///
+/// - "Hot": keyed by state root, holds a full state for recent (unfinalized) blocks. The `put`,
+/// `get`, `exists` and `delete` methods operate on this column.
+/// - "Cold": keyed by the slot of every `epochs_per_cold_state`th epoch boundary prior to
+/// finalization, holds a full state for the first block at or after that boundary. Any other
+/// finalized state must be reconstructed by replaying blocks on top of the nearest prior cold
+/// state.
+///
+/// The cold states are found by their boundary, so `epochs_per_cold_state` must not change for
+/// an existing database.
 pub struct BeaconStateStore<T>
 where
     T: ClientDB,
 {
     db: Arc<T>,
+    epochs_per_cold_state: u64,
 }
 
 // Implements `put`, `get`, `exists` and `delete` for the store.
@@ -16,7 +32,17 @@ impl_crud_for_store!(BeaconStateStore, DB_COLUMN);
 
 impl<T: ClientDB> BeaconStateStore<T> {
     pub fn new(db: Arc<T>) -> Self {
-        Self { db }
+        Self::with_epochs_per_cold_state(db, DEFAULT_EPOCHS_PER_COLD_STATE)
+    }
+
+    /// Create a new store which keeps a cold state every `epochs_per_cold_state` epochs.
+    ///
+    /// A value of `0` is treated as `1`.
+    pub fn with_epochs_per_cold_state(db: Arc<T>, epochs_per_cold_state: u64) -> Self {
+        Self {
+            db,
+            epochs_per_cold_state: std::cmp::max(epochs_per_cold_state, 1),
+        }
     }
 
     pub fn get_deserialized(&self, hash: &Hash256) -> Result<Option<BeaconState>, DBError> {
@@ -30,6 +56,71 @@ impl<T: ClientDB> BeaconStateStore<T> {
             }
         }
     }
+
+    /// Returns the number of slots between each cold state.
+    pub fn slots_per_cold_state(&self, slots_per_epoch: u64) -> u64 {
+        self.epochs_per_cold_state * slots_per_epoch
+    }
+
+    /// Returns `true` if the state of the block at `slot`, whose parent is at `parent_slot`,
+    /// should be kept when it is moved to the cold column.
+    ///
+    /// The first block at or after each boundary is kept, so a boundary which is a skipped slot
+    /// still has a cold state. The genesis block has no parent.
+    pub fn is_cold_state_slot(
+        &self,
+        slot: Slot,
+        parent_slot: Option<Slot>,
+        genesis_slot: Slot,
+        slots_per_epoch: u64,
+    ) -> bool {
+        if slot < genesis_slot {
+            return false;
+        }
+        match parent_slot {
+            Some(parent_slot) => {
+                parent_slot < self.cold_state_slot_at_or_before(slot, genesis_slot, slots_per_epoch)
+            }
+            None => true,
+        }
+    }
+
+    /// Returns the latest cold state boundary at or prior to `slot`.
+    ///
+    /// The state kept for the boundary is that of the first block at or after it, which is at or
+    /// prior to any block at `slot`.
+    pub fn cold_state_slot_at_or_before(
+        &self,
+        slot: Slot,
+        genesis_slot: Slot,
+        slots_per_epoch: u64,
+    ) -> Slot {
+        let period = self.slots_per_cold_state(slots_per_epoch);
+        let height = slot.as_u64().saturating_sub(genesis_slot.as_u64());
+        genesis_slot + (height - height % period)
+    }
+
+    pub fn get_cold(&self, slot: Slot) -> Result<Option<Vec<u8>>, DBError> {
+        self.db
+            .get(COLD_STATES_DB_COLUMN, &slot.as_u64().to_be_bytes())
+    }
+
+    pub fn get_cold_deserialized(&self, slot: Slot) -> Result<Option<BeaconState>, DBError> {
+        match self.get_cold(slot)? {
+            None => Ok(None),
+            Some(ssz) => {
+                let state = decode::<BeaconState>(&ssz).map_err(|_| DBError {
+                    message: "Bad Cold State SSZ.".to_string(),
+                })?;
+                Ok(Some(state))
+            }
+        }
+    }
+
+    /// Queue a `put` of the state kept for the boundary at `slot` into the cold column in `batch`.
+    pub fn batch_put_cold(&self, batch: &mut DBBatch, slot: Slot, ssz: &[u8]) {
+        batch.put(COLD_STATES_DB_COLUMN, &slot.as_u64().to_be_bytes(), ssz)
+    }
 }
 
 #[cfg(test)]
@@ -59,4 +150,64 @@ mod tests {
 
         assert_eq!(state, decoded);
     }
+
+    #[test]
+    fn test_cold_reader() {
+        let db = Arc::new(MemoryDB::open());
+        let store = BeaconStateStore::new(db.clone());
+
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let state = BeaconState::random_for_test(&mut rng);
+        let state_root = state.canonical_root();
+
+        let mut batch = DBBatch::new();
+        store.batch_put_cold(&mut batch, state.slot, &ssz_encode(&state));
+        store.write(batch).unwrap();
+
+        // Cold states are not accessible by root.
+        assert!(!store.exists(&state_root).unwrap());
+
+        let decoded = store.get_cold_deserialized(state.slot).unwrap().unwrap();
+        assert_eq!(state, decoded);
+        assert_eq!(store.get_cold(state.slot + 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_cold_state_slots() {
+        let db = Arc::new(MemoryDB::open());
+        let store = BeaconStateStore::with_epochs_per_cold_state(db.clone(), 2);
+
+        let genesis_slot = Slot::new(100);
+        let slots_per_epoch = 8;
+
+        let is_cold = |slot: u64, parent_slot: Option<u64>| {
+            store.is_cold_state_slot(
+                genesis_slot + slot,
+                parent_slot.map(|parent_slot| genesis_slot + parent_slot),
+                genesis_slot,
+                slots_per_epoch,
+            )
+        };
+
+        assert!(is_cold(0, None));
+        assert!(is_cold(16, Some(15)));
+        assert!(!is_cold(8, Some(7)));
+        assert!(!is_cold(17, Some(16)));
+        assert!(!store.is_cold_state_slot(Slot::new(0), None, genesis_slot, slots_per_epoch));
+
+        // The boundary at 16 was skipped, so the first block after it is kept instead.
+        assert!(is_cold(18, Some(14)));
+        assert!(!is_cold(19, Some(18)));
+        // A block which skips past the following boundary is kept for that boundary.
+        assert!(is_cold(33, Some(14)));
+
+        assert_eq!(
+            store.cold_state_slot_at_or_before(genesis_slot + 31, genesis_slot, slots_per_epoch),
+            genesis_slot + 16
+        );
+        assert_eq!(
+            store.cold_state_slot_at_or_before(genesis_slot + 32, genesis_slot, slots_per_epoch),
+            genesis_slot + 32
+        );
+    }
 }
@@ -0,0 +1,122 @@
+use super::META_DB_COLUMN as DB_COLUMN;
+use super::{ClientDB, DBBatch, DBError};
+use std::sync::Arc;
+
+/// The key under which the chain metadata is stored in the `META_DB_COLUMN`.
+pub const CHAIN_METADATA_KEY: &[u8] = b"chain_metadata";
+
+/// The key under which the operation pool is stored in the `META_DB_COLUMN`.
+pub const OP_POOL_KEY: &[u8] = b"op_pool";
+
+/// Stores a single, SSZ-encoded record describing the chain (e.g., the canonical and finalized
+/// heads) so that a node may resume from the database after a restart.
+///
+/// The operation pool is kept in a second record, so that databases written before it was
+/// persisted can still be read.
+///
+/// The layout of the record is owned by the `beacon_chain` crate, this store only deals in bytes.
+pub struct ChainMetadataStore<T>
+where
+    T: ClientDB,
+{
+    db: Arc<T>,
+}
+
+impl<T: ClientDB> ChainMetadataStore<T> {
+    pub fn new(db: Arc<T>) -> Self {
+        Self { db }
+    }
+
+    pub fn put(&self, ssz: &[u8]) -> Result<(), DBError> {
+        self.db.put(DB_COLUMN, CHAIN_METADATA_KEY, ssz)
+    }
+
+    pub fn get(&self) -> Result<Option<Vec<u8>>, DBError> {
+        self.db.get(DB_COLUMN, CHAIN_METADATA_KEY)
+    }
+
+    pub fn exists(&self) -> Result<bool, DBError> {
+        self.db.exists(DB_COLUMN, CHAIN_METADATA_KEY)
+    }
+
+    /// Queue a `put` in `batch`, rather than writing it to the database immediately.
+    pub fn batch_put(&self, batch: &mut DBBatch, ssz: &[u8]) {
+        batch.put(DB_COLUMN, CHAIN_METADATA_KEY, ssz)
+    }
+
+    pub fn put_op_pool(&self, ssz: &[u8]) -> Result<(), DBError> {
+        self.db.put(DB_COLUMN, OP_POOL_KEY, ssz)
+    }
+
+    pub fn get_op_pool(&self) -> Result<Option<Vec<u8>>, DBError> {
+        self.db.get(DB_COLUMN, OP_POOL_KEY)
+    }
+
+    /// Queue a `put_op_pool` in `batch`, rather than writing it to the database immediately.
+    pub fn batch_put_op_pool(&self, batch: &mut DBBatch, ssz: &[u8]) {
+        batch.put(DB_COLUMN, OP_POOL_KEY, ssz)
+    }
+
+    /// Atomically apply `batch` to the underlying database.
+    pub fn write(&self, batch: DBBatch) -> Result<(), DBError> {
+        self.db.write(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::MemoryDB;
+    use super::*;
+
+    #[test]
+    fn test_put_get() {
+        let db = Arc::new(MemoryDB::open());
+        let store = ChainMetadataStore::new(db.clone());
+
+        assert_eq!(store.get().unwrap(), None);
+        assert!(!store.exists().unwrap());
+
+        store.put("some bytes".as_bytes()).unwrap();
+
+        assert!(store.exists().unwrap());
+        assert_eq!(store.get().unwrap().unwrap(), "some bytes".as_bytes());
+        assert_eq!(
+            db.get(DB_COLUMN, CHAIN_METADATA_KEY).unwrap().unwrap(),
+            "some bytes".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_batch_put() {
+        let db = Arc::new(MemoryDB::open());
+        let store = ChainMetadataStore::new(db.clone());
+
+        let mut batch = DBBatch::new();
+        store.batch_put(&mut batch, "some bytes".as_bytes());
+        assert!(!store.exists().unwrap());
+
+        store.write(batch).unwrap();
+        assert_eq!(store.get().unwrap().unwrap(), "some bytes".as_bytes());
+    }
+
+    #[test]
+    fn test_op_pool_is_separate() {
+        let db = Arc::new(MemoryDB::open());
+        let store = ChainMetadataStore::new(db.clone());
+
+        let mut batch = DBBatch::new();
+        store.batch_put(&mut batch, "metadata".as_bytes());
+        store.batch_put_op_pool(&mut batch, "op pool".as_bytes());
+        store.write(batch).unwrap();
+
+        assert_eq!(store.get().unwrap().unwrap(), "metadata".as_bytes());
+        assert_eq!(store.get_op_pool().unwrap().unwrap(), "op pool".as_bytes());
+
+        store.put_op_pool("new op pool".as_bytes()).unwrap();
+        assert_eq!(store.get().unwrap().unwrap(), "metadata".as_bytes());
+        assert_eq!(
+            store.get_op_pool().unwrap().unwrap(),
+            "new op pool".as_bytes()
+        );
+    }
+}
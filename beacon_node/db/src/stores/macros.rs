@@ -16,6 +16,39 @@ macro_rules! impl_crud_for_store {
             pub fn delete(&self, hash: &Hash256) -> Result<(), DBError> {
                 self.db.delete($db_column, hash.as_bytes())
             }
+
+            /// Queue a `put` in `batch`, rather than writing it to the database immediately.
+            pub fn batch_put(&self, batch: &mut DBBatch, hash: &Hash256, ssz: &[u8]) {
+                batch.put($db_column, hash.as_bytes(), ssz)
+            }
+
+            /// Queue a `delete` in `batch`, rather than writing it to the database immediately.
+            pub fn batch_delete(&self, batch: &mut DBBatch, hash: &Hash256) {
+                batch.delete($db_column, hash.as_bytes())
+            }
+
+            /// Atomically apply `batch` to the underlying database.
+            pub fn write(&self, batch: DBBatch) -> Result<(), DBError> {
+                self.db.write(batch)
+            }
+
+            /// Iterate over all `(root, ssz)` pairs in the store, in ascending order of root.
+            ///
+            /// A key which is not a 32 byte root yields an error.
+            pub fn iter(
+                &self,
+            ) -> Result<impl Iterator<Item = Result<(Hash256, Vec<u8>), DBError>> + '_, DBError>
+            {
+                Ok(self.db.iter_column($db_column)?.map(|(key, ssz)| {
+                    if key.len() == 32 {
+                        Ok((Hash256::from_slice(&key), ssz))
+                    } else {
+                        Err(DBError {
+                            message: format!("Invalid {} byte key in {}", key.len(), $db_column),
+                        })
+                    }
+                }))
+            }
         }
     };
 }
@@ -99,5 +132,74 @@ macro_rules! test_crud_for_store {
             store.delete(hash).unwrap();
             assert!(!db.exists(DB_COLUMN, hash.as_bytes()).unwrap());
         }
+
+        #[test]
+        fn test_iter() {
+            let db = Arc::new(MemoryDB::open());
+            let store = $store::new(db.clone());
+
+            let ssz = "some bytes".as_bytes();
+            let hashes = vec![
+                Hash256::from([0xBB; 32]),
+                Hash256::from([0xAA; 32]),
+                Hash256::from([0xCC; 32]),
+            ];
+
+            for hash in &hashes {
+                store.put(hash, ssz).unwrap();
+            }
+
+            let found: Vec<Hash256> = store
+                .iter()
+                .unwrap()
+                .map(|entry| entry.unwrap().0)
+                .collect();
+            assert_eq!(
+                found,
+                vec![
+                    Hash256::from([0xAA; 32]),
+                    Hash256::from([0xBB; 32]),
+                    Hash256::from([0xCC; 32]),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_iter_invalid_key() {
+            let db = Arc::new(MemoryDB::open());
+            let store = $store::new(db.clone());
+
+            db.put(DB_COLUMN, &[0xAA; 8], "some bytes".as_bytes())
+                .unwrap();
+
+            let mut iter = store.iter().unwrap();
+            assert!(iter.next().unwrap().is_err());
+            assert!(iter.next().is_none());
+        }
+
+        #[test]
+        fn test_batch_put_and_delete() {
+            let db = Arc::new(MemoryDB::open());
+            let store = $store::new(db.clone());
+
+            let ssz = "some bytes".as_bytes();
+            let hash = &Hash256::from([0xAA; 32]);
+            let other_hash = &Hash256::from([0xBB; 32]);
+
+            db.put(DB_COLUMN, other_hash.as_bytes(), ssz).unwrap();
+
+            let mut batch = DBBatch::new();
+            store.batch_put(&mut batch, hash, ssz);
+            store.batch_delete(&mut batch, other_hash);
+
+            // Nothing is written until the batch is committed.
+            assert!(!db.exists(DB_COLUMN, hash.as_bytes()).unwrap());
+            assert!(db.exists(DB_COLUMN, other_hash.as_bytes()).unwrap());
+
+            store.write(batch).unwrap();
+
+            assert_eq!(db.get(DB_COLUMN, hash.as_bytes()).unwrap().unwrap(), ssz);
+            assert!(!db.exists(DB_COLUMN, other_hash.as_bytes()).unwrap());
+        }
     };
 }
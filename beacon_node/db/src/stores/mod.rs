@@ -1,25 +1,33 @@
-use super::{ClientDB, DBError};
+use super::{ClientDB, DBBatch, DBError};
 
 #[macro_use]
 mod macros;
 mod beacon_block_store;
 mod beacon_state_store;
+mod chain_metadata_store;
 mod pow_chain_store;
 mod validator_store;
 
-pub use self::beacon_block_store::{BeaconBlockAtSlotError, BeaconBlockStore};
-pub use self::beacon_state_store::BeaconStateStore;
+pub use self::beacon_block_store::BeaconBlockStore;
+pub use self::beacon_state_store::{BeaconStateStore, DEFAULT_EPOCHS_PER_COLD_STATE};
+pub use self::chain_metadata_store::{ChainMetadataStore, CHAIN_METADATA_KEY};
 pub use self::pow_chain_store::PoWChainStore;
 pub use self::validator_store::{ValidatorStore, ValidatorStoreError};
 
 pub const BLOCKS_DB_COLUMN: &str = "blocks";
+pub const CANONICAL_ROOTS_DB_COLUMN: &str = "canonical_roots";
 pub const STATES_DB_COLUMN: &str = "states";
+pub const COLD_STATES_DB_COLUMN: &str = "cold_states";
 pub const POW_CHAIN_DB_COLUMN: &str = "powchain";
 pub const VALIDATOR_DB_COLUMN: &str = "validator";
+pub const META_DB_COLUMN: &str = "meta";
 
-pub const COLUMNS: [&str; 4] = [
+pub const COLUMNS: [&str; 7] = [
     BLOCKS_DB_COLUMN,
+    CANONICAL_ROOTS_DB_COLUMN,
     STATES_DB_COLUMN,
+    COLD_STATES_DB_COLUMN,
     POW_CHAIN_DB_COLUMN,
     VALIDATOR_DB_COLUMN,
+    META_DB_COLUMN,
 ];
@@ -1,5 +1,5 @@
 use super::POW_CHAIN_DB_COLUMN as DB_COLUMN;
-use super::{ClientDB, DBError};
+use super::{ClientDB, DBBatch, DBError};
 use std::sync::Arc;
 
 pub struct PoWChainStore<T>
@@ -18,9 +18,19 @@ impl<T: ClientDB> PoWChainStore<T> {
         self.db.put(DB_COLUMN, hash, &[0])
     }
 
+    /// Queue a `put_block_hash` in `batch`, rather than writing it to the database immediately.
+    pub fn batch_put_block_hash(&self, batch: &mut DBBatch, hash: &[u8]) {
+        batch.put(DB_COLUMN, hash, &[0])
+    }
+
     pub fn block_hash_exists(&self, hash: &[u8]) -> Result<bool, DBError> {
         self.db.exists(DB_COLUMN, hash)
     }
+
+    /// Atomically apply `batch` to the underlying database.
+    pub fn write(&self, batch: DBBatch) -> Result<(), DBError> {
+        self.db.write(batch)
+    }
 }
 
 #[cfg(test)]
@@ -43,6 +53,20 @@ mod tests {
         assert!(db.exists(DB_COLUMN, hash).unwrap());
     }
 
+    #[test]
+    fn test_batch_put_block_hash() {
+        let db = Arc::new(MemoryDB::open());
+        let store = PoWChainStore::new(db.clone());
+
+        let hash = &Hash256::from([0xAA; 32]).as_bytes().to_vec();
+        let mut batch = DBBatch::new();
+        store.batch_put_block_hash(&mut batch, hash);
+        assert!(!db.exists(DB_COLUMN, hash).unwrap());
+
+        store.write(batch).unwrap();
+        assert!(db.exists(DB_COLUMN, hash).unwrap());
+    }
+
     #[test]
     fn test_block_hash_exists() {
         let db = Arc::new(MemoryDB::open());
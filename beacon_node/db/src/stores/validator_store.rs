@@ -2,7 +2,7 @@ extern crate bytes;
 
 use self::bytes::{BufMut, BytesMut};
 use super::VALIDATOR_DB_COLUMN as DB_COLUMN;
-use super::{ClientDB, DBError};
+use super::{ClientDB, DBBatch, DBError};
 use bls::PublicKey;
 use ssz::{decode, ssz_encode};
 use std::sync::Arc;
@@ -61,6 +61,24 @@ impl<T: ClientDB> ValidatorStore<T> {
             .map_err(ValidatorStoreError::from)
     }
 
+    /// Queue a `put_public_key_by_index` in `batch`, rather than writing it to the database
+    /// immediately.
+    pub fn batch_put_public_key_by_index(
+        &self,
+        batch: &mut DBBatch,
+        index: usize,
+        public_key: &PublicKey,
+    ) {
+        let key = self.get_db_key_for_index(&KeyPrefixes::PublicKey, index);
+        let val = ssz_encode(public_key);
+        batch.put(DB_COLUMN, &key[..], &val[..])
+    }
+
+    /// Atomically apply `batch` to the underlying database.
+    pub fn write(&self, batch: DBBatch) -> Result<(), ValidatorStoreError> {
+        self.db.write(batch).map_err(ValidatorStoreError::from)
+    }
+
     pub fn get_public_key_by_index(
         &self,
         index: usize,
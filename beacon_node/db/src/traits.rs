@@ -1,5 +1,8 @@
 pub type DBValue = Vec<u8>;
 
+/// An iterator over the `(key, value)` pairs of a column, in ascending (bytewise) key order.
+pub type ColumnIter<'a> = Box<dyn Iterator<Item = (DBValue, DBValue)> + 'a>;
+
 #[derive(Debug)]
 pub struct DBError {
     pub message: String,
@@ -11,6 +14,69 @@ impl DBError {
     }
 }
 
+/// A single write operation which forms part of a `DBBatch`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DBOp {
+    Put {
+        col: String,
+        key: Vec<u8>,
+        val: Vec<u8>,
+    },
+    Delete {
+        col: String,
+        key: Vec<u8>,
+    },
+}
+
+/// An ordered list of write operations which are applied to a `ClientDB` atomically; either all
+/// of the operations are committed, or none of them are.
+///
+/// Operations are applied in the order they were added, so a later `put` or `delete` on the same
+/// key will override an earlier one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DBBatch {
+    ops: Vec<DBOp>,
+}
+
+impl DBBatch {
+    /// Create a new, empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a `put` of `val` at `key` in `col`.
+    pub fn put(&mut self, col: &str, key: &[u8], val: &[u8]) {
+        self.ops.push(DBOp::Put {
+            col: col.to_string(),
+            key: key.to_vec(),
+            val: val.to_vec(),
+        });
+    }
+
+    /// Queue a `delete` of `key` in `col`.
+    pub fn delete(&mut self, col: &str, key: &[u8]) {
+        self.ops.push(DBOp::Delete {
+            col: col.to_string(),
+            key: key.to_vec(),
+        });
+    }
+
+    /// Returns the operations in this batch, in the order they will be applied.
+    pub fn ops(&self) -> &[DBOp] {
+        &self.ops
+    }
+
+    /// Returns the number of operations in the batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if there are no operations in the batch.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
 /// A generic database to be used by the "client' (i.e.,
 /// the lighthouse blockchain client).
 ///
@@ -25,4 +91,31 @@ pub trait ClientDB: Sync + Send {
     fn exists(&self, col: &str, key: &[u8]) -> Result<bool, DBError>;
 
     fn delete(&self, col: &str, key: &[u8]) -> Result<(), DBError>;
+
+    /// Atomically apply all operations in `batch`.
+    ///
+    /// If an error is returned, none of the operations have been applied.
+    fn write(&self, batch: DBBatch) -> Result<(), DBError>;
+
+    /// Iterate over all `(key, value)` pairs in `col` where the key is greater than or equal to
+    /// `key`.
+    ///
+    /// Keys are yielded in ascending bytewise order on all implementations.
+    fn iter_from<'a>(&'a self, col: &str, key: &[u8]) -> Result<ColumnIter<'a>, DBError>;
+
+    /// Iterate over all `(key, value)` pairs in `col`, in ascending bytewise key order.
+    fn iter_column<'a>(&'a self, col: &str) -> Result<ColumnIter<'a>, DBError> {
+        self.iter_from(col, &[])
+    }
+
+    /// Iterate over all `(key, value)` pairs in `col` where the key starts with `prefix`, in
+    /// ascending bytewise key order.
+    fn iter_prefix<'a>(&'a self, col: &str, prefix: &[u8]) -> Result<ColumnIter<'a>, DBError> {
+        let prefix = prefix.to_vec();
+        let iter = self
+            .iter_from(col, &prefix)?
+            .take_while(move |(key, _)| key.starts_with(&prefix));
+
+        Ok(Box::new(iter))
+    }
 }
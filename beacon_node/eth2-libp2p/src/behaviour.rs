@@ -1,3 +1,4 @@
+use crate::discovery::Discovery;
 use crate::rpc::{RPCEvent, RPCMessage, Rpc};
 use crate::NetworkConfig;
 use futures::prelude::*;
@@ -8,6 +9,7 @@ use libp2p::{
     },
     gossipsub::{Gossipsub, GossipsubEvent},
     identify::{protocol::IdentifyInfo, Identify, IdentifyEvent},
+    kad::KademliaOut,
     ping::{Ping, PingEvent},
     tokio_io::{AsyncRead, AsyncWrite},
     NetworkBehaviour, PeerId,
@@ -24,7 +26,8 @@ use types::{Topic, TopicHash};
 pub struct Behaviour<TSubstream: AsyncRead + AsyncWrite> {
     /// The routing pub-sub mechanism for eth2.
     gossipsub: Gossipsub<TSubstream>,
-    // TODO: Add Kademlia for peer discovery
+    /// Finds new peers with Kademlia until the target number of peers are connected.
+    discovery: Discovery<TSubstream>,
     /// The events generated by this behaviour to be consumed in the swarm poll.
     serenity_rpc: Rpc<TSubstream>,
     /// Allows discovery of IP addresses for peers on the network.
@@ -50,13 +53,14 @@ impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<GossipsubE
                 trace!(self.log, "Received GossipEvent"; "msg" => format!("{:?}", gs_msg));
 
                 let pubsub_message = match PubsubMessage::ssz_decode(&gs_msg.data, 0) {
-                    //TODO: Punish peer on error
                     Err(e) => {
                         warn!(
                             self.log,
                             "Received undecodable message from Peer {:?} error", gs_msg.source;
                             "error" => format!("{:?}", e)
                         );
+                        self.events
+                            .push(BehaviourEvent::InvalidGossip(gs_msg.source));
                         return;
                     }
                     Ok((msg, _index)) => msg,
@@ -82,6 +86,12 @@ impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<RPCMessage
             RPCMessage::PeerDialed(peer_id) => {
                 self.events.push(BehaviourEvent::PeerDialed(peer_id))
             }
+            RPCMessage::PeerAccepted(peer_id) => {
+                self.events.push(BehaviourEvent::PeerAccepted(peer_id))
+            }
+            RPCMessage::PeerDisconnected(peer_id) => {
+                self.events.push(BehaviourEvent::PeerDisconnected(peer_id))
+            }
             RPCMessage::RPC(peer_id, rpc_event) => {
                 self.events.push(BehaviourEvent::RPC(peer_id, rpc_event))
             }
@@ -104,6 +114,10 @@ impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<IdentifyEv
                     );
                     info.listen_addrs.truncate(20);
                 }
+                for address in &info.listen_addrs {
+                    self.discovery
+                        .add_connected_address(&peer_id, address.clone());
+                }
                 self.events
                     .push(BehaviourEvent::Identified(peer_id, Box::new(info)));
             }
@@ -113,6 +127,17 @@ impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<IdentifyEv
     }
 }
 
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<KademliaOut>
+    for Behaviour<TSubstream>
+{
+    fn inject_event(&mut self, event: KademliaOut) {
+        // Discovered peers are dialed by the discovery behaviour.
+        if let KademliaOut::Discovered { peer_id, .. } = event {
+            trace!(self.log, "Discovered peer"; "peer" => format!("{:?}", peer_id));
+        }
+    }
+}
+
 impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<PingEvent>
     for Behaviour<TSubstream>
 {
@@ -128,8 +153,9 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
         let behaviour_log = log.new(o!());
 
         Behaviour {
-            gossipsub: Gossipsub::new(local_peer_id, net_conf.gs_config.clone()),
-            serenity_rpc: Rpc::new(log),
+            gossipsub: Gossipsub::new(local_peer_id.clone(), net_conf.gs_config.clone()),
+            discovery: Discovery::new(local_peer_id, net_conf, log),
+            serenity_rpc: Rpc::new(net_conf.rpc_request_timeout, log),
             identify: Identify::new(
                 identify_config.version,
                 identify_config.user_agent,
@@ -165,6 +191,11 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
         self.serenity_rpc.send_rpc(peer_id, rpc_event);
     }
 
+    /// The number of peers which are presently connected.
+    pub fn connected_peers(&self) -> usize {
+        self.discovery.connected_peers()
+    }
+
     /// Publishes a message on the pubsub (gossipsub) behaviour.
     pub fn publish(&mut self, topics: Vec<Topic>, message: PubsubMessage) {
         let message_bytes = ssz_encode(&message);
@@ -178,7 +209,11 @@ impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
 pub enum BehaviourEvent {
     RPC(PeerId, RPCEvent),
     PeerDialed(PeerId),
+    PeerAccepted(PeerId),
+    PeerDisconnected(PeerId),
     Identified(PeerId, Box<IdentifyInfo>),
+    /// The peer published a gossip message which could not be decoded.
+    InvalidGossip(PeerId),
     // TODO: This is a stub at the moment
     GossipMessage {
         source: PeerId,
@@ -1,5 +1,7 @@
 use crate::Multiaddr;
 use libp2p::gossipsub::{GossipsubConfig, GossipsubConfigBuilder};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 /// Network configuration for lighthouse.
@@ -19,6 +21,15 @@ pub struct Config {
     pub client_version: String,
     /// List of topics to subscribe to as strings
     pub topics: Vec<String>,
+    /// The file holding the secret key of the node's identity. It is created if it does not
+    /// exist. If `None`, a new identity is generated on each start.
+    pub node_key_file: Option<PathBuf>,
+    /// The number of connected peers at which discovery stops searching for new peers.
+    pub target_peers: usize,
+    /// The maximum number of connected peers. Connections from further peers are refused.
+    pub max_peers: usize,
+    /// The time a peer has to respond to an RPC request before it times out.
+    pub rpc_request_timeout: Duration,
 }
 
 impl Default for Config {
@@ -36,6 +47,10 @@ impl Default for Config {
             boot_nodes: Vec::new(),
             client_version: version::version(),
             topics: vec![String::from("beacon_chain")],
+            node_key_file: None,
+            target_peers: 10,
+            max_peers: 25,
+            rpc_request_timeout: Duration::from_secs(30),
         }
     }
 }
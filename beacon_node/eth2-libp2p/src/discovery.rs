@@ -0,0 +1,196 @@
+//! Peer discovery over Kademlia.
+//!
+//! The routing table is filled with the peers we connect to (starting with the bootnodes) and the
+//! peers they return to our queries. While fewer than the target number of peers are connected,
+//! random queries are made and the peers they find are dialed.
+
+use crate::NetworkConfig;
+use futures::prelude::*;
+use libp2p::core::protocols_handler::ProtocolsHandler;
+use libp2p::core::swarm::{
+    ConnectedPoint, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+};
+use libp2p::kad::{Kademlia, KademliaOut};
+use libp2p::tokio_io::{AsyncRead, AsyncWrite};
+use libp2p::{Multiaddr, PeerId};
+use slog::{debug, o, warn};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::timer::Delay;
+
+/// The time between the first queries for peers.
+const INITIAL_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+/// The time between queries is doubled after each query, up to this limit.
+const MAX_QUERY_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Discovery<TSubstream> {
+    /// The Kademlia routing table and queries.
+    kademlia: Kademlia<TSubstream>,
+    /// The peers which are presently connected.
+    connected_peers: HashSet<PeerId>,
+    /// The number of connected peers at which discovery stops dialing new peers.
+    target_peers: usize,
+    /// Discovered peers which are yet to be dialed.
+    dial_queue: VecDeque<PeerId>,
+    /// The peers in the `dial_queue`, so each peer is only queued once.
+    queued_peers: HashSet<PeerId>,
+    /// Fires when the next query for peers should be made.
+    query_delay: Delay,
+    query_interval: Duration,
+    /// Logger for the discovery behaviour.
+    log: slog::Logger,
+}
+
+impl<TSubstream> Discovery<TSubstream> {
+    pub fn new(local_peer_id: PeerId, net_conf: &NetworkConfig, log: &slog::Logger) -> Self {
+        Self {
+            kademlia: Kademlia::new(local_peer_id),
+            connected_peers: HashSet::new(),
+            target_peers: net_conf.target_peers,
+            dial_queue: VecDeque::new(),
+            queued_peers: HashSet::new(),
+            query_delay: Delay::new(Instant::now()),
+            query_interval: INITIAL_QUERY_INTERVAL,
+            log: log.new(o!("Service" => "Libp2p-Discovery")),
+        }
+    }
+
+    /// Adds an address of a connected peer (e.g., learned from the identify protocol) to the
+    /// routing table.
+    pub fn add_connected_address(&mut self, peer_id: &PeerId, address: Multiaddr) {
+        self.kademlia.add_connected_address(peer_id, address);
+    }
+
+    /// The number of peers which are presently connected.
+    pub fn connected_peers(&self) -> usize {
+        self.connected_peers.len()
+    }
+
+    fn needs_peers(&self) -> bool {
+        self.connected_peers.len() < self.target_peers
+    }
+
+    /// Searches for peers close to a random id, if more peers are needed.
+    fn find_peers(&mut self) {
+        if self.needs_peers() {
+            debug!(
+                self.log,
+                "Searching for peers";
+                "connected" => self.connected_peers.len(),
+                "target" => self.target_peers
+            );
+            self.kademlia.find_node(PeerId::random());
+        }
+
+        self.query_delay.reset(Instant::now() + self.query_interval);
+        self.query_interval = std::cmp::min(self.query_interval * 2, MAX_QUERY_INTERVAL);
+    }
+
+    /// Searches for peers as soon as possible, restarting the back-off of the query interval.
+    fn find_peers_now(&mut self) {
+        self.query_interval = INITIAL_QUERY_INTERVAL;
+        self.query_delay.reset(Instant::now());
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Discovery<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = <Kademlia<TSubstream> as NetworkBehaviour>::ProtocolsHandler;
+    type OutEvent = <Kademlia<TSubstream> as NetworkBehaviour>::OutEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        NetworkBehaviour::new_handler(&mut self.kademlia)
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.kademlia.addresses_of_peer(peer_id)
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.connected_peers.insert(peer_id.clone());
+        // A new connection (e.g., to a bootnode) may know of more peers.
+        if self.needs_peers() {
+            self.find_peers_now();
+        }
+        self.kademlia.inject_connected(peer_id, endpoint)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.connected_peers.remove(peer_id);
+        self.kademlia.inject_disconnected(peer_id, endpoint)
+    }
+
+    fn inject_replaced(&mut self, peer_id: PeerId, closed: ConnectedPoint, opened: ConnectedPoint) {
+        self.kademlia.inject_replaced(peer_id, closed, opened)
+    }
+
+    fn inject_node_event(
+        &mut self,
+        peer_id: PeerId,
+        event: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
+    ) {
+        self.kademlia.inject_node_event(peer_id, event)
+    }
+
+    fn poll(
+        &mut self,
+        params: &mut PollParameters<'_>,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        loop {
+            match self.query_delay.poll() {
+                Ok(Async::Ready(_)) => self.find_peers(),
+                Ok(Async::NotReady) => break,
+                Err(e) => {
+                    warn!(self.log, "Discovery query timer failed"; "error" => format!("{:?}", e));
+                    break;
+                }
+            }
+        }
+
+        while self.needs_peers() {
+            match self.dial_queue.pop_front() {
+                Some(peer_id) => {
+                    self.queued_peers.remove(&peer_id);
+                    if !self.connected_peers.contains(&peer_id) {
+                        debug!(self.log, "Dialing discovered peer"; "peer" => format!("{:?}", peer_id));
+                        return Async::Ready(NetworkBehaviourAction::DialPeer { peer_id });
+                    }
+                }
+                None => break,
+            }
+        }
+
+        loop {
+            match self.kademlia.poll(params) {
+                Async::Ready(NetworkBehaviourAction::GenerateEvent(event)) => {
+                    if let KademliaOut::FindNodeResult { closer_peers, .. } = &event {
+                        debug!(
+                            self.log,
+                            "Discovery query found {} peers",
+                            closer_peers.len()
+                        );
+                        for peer_id in closer_peers {
+                            if !self.connected_peers.contains(peer_id)
+                                && self.queued_peers.insert(peer_id.clone())
+                            {
+                                self.dial_queue.push_back(peer_id.clone());
+                            }
+                        }
+                    }
+                    return Async::Ready(NetworkBehaviourAction::GenerateEvent(event));
+                }
+                Async::Ready(action) => return Async::Ready(action),
+                Async::NotReady => break,
+            }
+        }
+
+        Async::NotReady
+    }
+}
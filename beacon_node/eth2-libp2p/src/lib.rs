@@ -4,7 +4,9 @@
 /// This crate builds and manages the libp2p services required by the beacon node.
 pub mod behaviour;
 mod config;
+mod discovery;
 pub mod error;
+mod node_key;
 pub mod rpc;
 mod service;
 
@@ -0,0 +1,157 @@
+//! Persists the secret key of the node's libp2p identity, so its `PeerId` is stable across
+//! restarts.
+use crate::error;
+use libp2p::core::identity::{secp256k1, Keypair};
+use rand::RngCore;
+use slog::info;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// The length of a secp256k1 secret key, in bytes.
+const SECRET_KEY_LENGTH: usize = 32;
+
+/// Loads the secp256k1 node key from `path`, or generates a new key and saves it to `path` if
+/// the file does not exist.
+///
+/// The file holds the raw secret key bytes and is created readable only by its owner.
+pub fn load_or_generate(path: &Path, log: &slog::Logger) -> error::Result<Keypair> {
+    if path.exists() {
+        let keypair = load(path)?;
+        info!(log, "Loaded node key"; "path" => format!("{}", path.display()));
+        Ok(keypair)
+    } else {
+        let keypair = generate(path)?;
+        info!(log, "Generated new node key"; "path" => format!("{}", path.display()));
+        Ok(keypair)
+    }
+}
+
+fn load(path: &Path) -> error::Result<Keypair> {
+    let mut bytes = vec![];
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|e| format!("Unable to read node key {}: {:?}", path.display(), e))?;
+
+    if bytes.len() != SECRET_KEY_LENGTH {
+        return Err(format!(
+            "Node key {} has {} bytes, expected {}",
+            path.display(),
+            bytes.len(),
+            SECRET_KEY_LENGTH
+        )
+        .into());
+    }
+
+    let secret_key = secp256k1::SecretKey::from_bytes(&mut bytes)
+        .map_err(|e| format!("Invalid node key {}: {:?}", path.display(), e))?;
+
+    Ok(Keypair::Secp256k1(secp256k1::Keypair::from(secret_key)))
+}
+
+fn generate(path: &Path) -> error::Result<Keypair> {
+    // A random value is not a valid key if it exceeds the curve order, which is vanishingly
+    // unlikely.
+    let (bytes, secret_key) = loop {
+        let mut bytes = [0; SECRET_KEY_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        if let Ok(secret_key) = secp256k1::SecretKey::from_bytes(bytes.to_vec()) {
+            break (bytes, secret_key);
+        }
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Unable to create {}: {:?}", dir.display(), e))?;
+    }
+
+    create_private_file(path)
+        .and_then(|mut file| file.write_all(&bytes))
+        .map_err(|e| format!("Unable to write node key {}: {:?}", path.display(), e))?;
+
+    Ok(Keypair::Secp256k1(secp256k1::Keypair::from(secret_key)))
+}
+
+/// Creates a new file which may only be read and written by its owner.
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn null_logger() -> slog::Logger {
+        slog::Logger::root(slog::Discard, slog::o!())
+    }
+
+    /// Returns the path of a key file in an empty temporary directory unique to the test.
+    fn key_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("node_key_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("key")
+    }
+
+    #[test]
+    fn test_load_or_generate_is_stable() {
+        let path = key_path("stable");
+        let log = null_logger();
+
+        let generated = load_or_generate(&path, &log).unwrap();
+        let loaded = load_or_generate(&path, &log).unwrap();
+
+        assert_eq!(
+            generated.public().into_peer_id(),
+            loaded.public().into_peer_id()
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = key_path("private");
+
+        load_or_generate(&path, &null_logger()).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_length_is_rejected() {
+        let path = key_path("wrong_length");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(&path)
+            .unwrap()
+            .write_all(&[1; SECRET_KEY_LENGTH - 1])
+            .unwrap();
+
+        assert!(load_or_generate(&path, &null_logger()).is_err());
+        // The invalid key is not replaced.
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            SECRET_KEY_LENGTH as u64 - 1
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,7 +1,7 @@
 use ssz::{Decodable, DecodeError, Encodable, SszStream};
 /// Available RPC methods types and ids.
 use ssz_derive::{Decode, Encode};
-use types::{BeaconBlockBody, BeaconBlockHeader, Epoch, Hash256, Slot};
+use types::{BeaconBlock, BeaconBlockBody, BeaconBlockHeader, Epoch, Hash256, Slot};
 
 #[derive(Debug)]
 /// Available Serenity Libp2p RPC methods
@@ -18,6 +18,8 @@ pub enum RPCMethod {
     BeaconBlockBodies,
     /// Requests values for a merkle proof for the current blocks state root.
     BeaconChainState, // Note: experimental, not complete.
+    /// Requests the full blocks in a range of slots.
+    BeaconBlocksByRange,
     /// Unknown method received.
     Unknown,
 }
@@ -31,6 +33,7 @@ impl From<u16> for RPCMethod {
             11 => RPCMethod::BeaconBlockHeaders,
             12 => RPCMethod::BeaconBlockBodies,
             13 => RPCMethod::BeaconChainState,
+            14 => RPCMethod::BeaconBlocksByRange,
 
             _ => RPCMethod::Unknown,
         }
@@ -46,6 +49,7 @@ impl Into<u16> for RPCMethod {
             RPCMethod::BeaconBlockHeaders => 11,
             RPCMethod::BeaconBlockBodies => 12,
             RPCMethod::BeaconChainState => 13,
+            RPCMethod::BeaconBlocksByRange => 14,
             _ => 0,
         }
     }
@@ -59,6 +63,7 @@ pub enum RPCRequest {
     BeaconBlockHeaders(BeaconBlockHeadersRequest),
     BeaconBlockBodies(BeaconBlockBodiesRequest),
     BeaconChainState(BeaconChainStateRequest),
+    BeaconBlocksByRange(BeaconBlocksByRangeRequest),
 }
 
 impl RPCRequest {
@@ -70,6 +75,7 @@ impl RPCRequest {
             RPCRequest::BeaconBlockHeaders(_) => RPCMethod::BeaconBlockHeaders,
             RPCRequest::BeaconBlockBodies(_) => RPCMethod::BeaconBlockBodies,
             RPCRequest::BeaconChainState(_) => RPCMethod::BeaconChainState,
+            RPCRequest::BeaconBlocksByRange(_) => RPCMethod::BeaconBlocksByRange,
         };
         method.into()
     }
@@ -82,9 +88,18 @@ pub enum RPCResponse {
     BeaconBlockHeaders(BeaconBlockHeadersResponse),
     BeaconBlockBodies(BeaconBlockBodiesResponse),
     BeaconChainState(BeaconChainStateResponse),
+    BeaconBlocksByRange(BeaconBlocksByRangeResponse),
+    /// The request could not be fulfilled.
+    Error {
+        code: RPCErrorCode,
+        message: String,
+    },
 }
 
 impl RPCResponse {
+    /// Returns the id of the method this is a response to.
+    ///
+    /// An `Error` may respond to any method, so the id of the request must be sent with it.
     pub fn method_id(&self) -> u16 {
         let method = match self {
             RPCResponse::Hello(_) => RPCMethod::Hello,
@@ -92,6 +107,8 @@ impl RPCResponse {
             RPCResponse::BeaconBlockHeaders(_) => RPCMethod::BeaconBlockHeaders,
             RPCResponse::BeaconBlockBodies(_) => RPCMethod::BeaconBlockBodies,
             RPCResponse::BeaconChainState(_) => RPCMethod::BeaconChainState,
+            RPCResponse::BeaconBlocksByRange(_) => RPCMethod::BeaconBlocksByRange,
+            RPCResponse::Error { .. } => RPCMethod::Unknown,
         };
         method.into()
     }
@@ -124,6 +141,8 @@ pub enum GoodbyeReason {
     ClientShutdown,
     IrreleventNetwork,
     Fault,
+    /// The peer was disconnected because we are connected to the maximum number of peers.
+    TooManyPeers,
     Unknown,
 }
 
@@ -133,6 +152,8 @@ impl From<u64> for GoodbyeReason {
             1 => GoodbyeReason::ClientShutdown,
             2 => GoodbyeReason::IrreleventNetwork,
             3 => GoodbyeReason::Fault,
+            // Client-specific reasons are numbered from 128.
+            129 => GoodbyeReason::TooManyPeers,
             _ => GoodbyeReason::Unknown,
         }
     }
@@ -145,6 +166,7 @@ impl Into<u64> for GoodbyeReason {
             GoodbyeReason::ClientShutdown => 1,
             GoodbyeReason::IrreleventNetwork => 2,
             GoodbyeReason::Fault => 3,
+            GoodbyeReason::TooManyPeers => 129,
         }
     }
 }
@@ -163,6 +185,47 @@ impl Decodable for GoodbyeReason {
     }
 }
 
+/// The reason a request could not be fulfilled, sent in an `RPCResponse::Error`.
+///
+/// Note: any unknown `u16::into(n)` will resolve to `RPCErrorCode::Unknown`, which goes into
+/// `u16::max_value()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RPCErrorCode {
+    /// The request could not be decoded.
+    ParseError,
+    /// The request was decoded but is not valid (e.g., it asks for too many items).
+    InvalidRequest,
+    /// The method is not supported.
+    MethodNotFound,
+    /// The request was valid but the responder failed to fulfill it.
+    ServerError,
+    Unknown,
+}
+
+impl From<u16> for RPCErrorCode {
+    fn from(code: u16) -> RPCErrorCode {
+        match code {
+            10 => RPCErrorCode::ParseError,
+            20 => RPCErrorCode::InvalidRequest,
+            30 => RPCErrorCode::MethodNotFound,
+            40 => RPCErrorCode::ServerError,
+            _ => RPCErrorCode::Unknown,
+        }
+    }
+}
+
+impl Into<u16> for RPCErrorCode {
+    fn into(self) -> u16 {
+        match self {
+            RPCErrorCode::ParseError => 10,
+            RPCErrorCode::InvalidRequest => 20,
+            RPCErrorCode::MethodNotFound => 30,
+            RPCErrorCode::ServerError => 40,
+            RPCErrorCode::Unknown => u16::max_value(),
+        }
+    }
+}
+
 /// Request a number of beacon block roots from a peer.
 #[derive(Encode, Decode, Clone, Debug, PartialEq)]
 pub struct BeaconBlockRootsRequest {
@@ -235,6 +298,23 @@ pub struct BeaconBlockBodiesResponse {
     pub block_bodies: Vec<BeaconBlockBody>,
 }
 
+/// Request the full blocks in a range of slots from a peer.
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub struct BeaconBlocksByRangeRequest {
+    /// The slot of the first requested block.
+    pub start_slot: Slot,
+    /// The number of slots from the start slot.
+    pub count: u64,
+}
+
+/// Response containing the blocks in the requested range, in ascending slot order. Slots without
+/// a block are omitted.
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub struct BeaconBlocksByRangeResponse {
+    /// The blocks in the requested range.
+    pub blocks: Vec<BeaconBlock>,
+}
+
 /// Request values for tree hashes which yield a blocks `state_root`.
 #[derive(Encode, Decode, Clone, Debug, PartialEq)]
 pub struct BeaconChainStateRequest {
@@ -11,11 +11,17 @@ use libp2p::core::swarm::{
     ConnectedPoint, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
 };
 use libp2p::{Multiaddr, PeerId};
-pub use methods::{HelloMessage, RPCMethod, RPCRequest, RPCResponse};
+pub use methods::{HelloMessage, RPCErrorCode, RPCMethod, RPCRequest, RPCResponse};
 pub use protocol::{RPCEvent, RPCProtocol, RequestId};
-use slog::o;
+use slog::{debug, o, warn};
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Interval;
+
+/// The interval at which outstanding requests are checked for timeouts.
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 /// The network behaviour handles RPC requests/responses as specified in the Eth 2.0 phase 0
 /// specification.
@@ -23,29 +29,96 @@ use tokio::io::{AsyncRead, AsyncWrite};
 pub struct Rpc<TSubstream> {
     /// Queue of events to processed.
     events: Vec<NetworkBehaviourAction<RPCEvent, RPCMessage>>,
+    /// The requests we have sent which are awaiting a response, with their method id and the time
+    /// at which they time out.
+    pending_requests: HashMap<(PeerId, RequestId), (u16, Instant)>,
+    /// The time a peer has to respond to a request.
+    request_timeout: Duration,
+    /// Fires when the `pending_requests` should be checked for timeouts.
+    timeout_check: Interval,
     /// Pins the generic substream.
     marker: PhantomData<TSubstream>,
     /// Slog logger for RPC behaviour.
-    _log: slog::Logger,
+    log: slog::Logger,
 }
 
 impl<TSubstream> Rpc<TSubstream> {
-    pub fn new(log: &slog::Logger) -> Self {
+    pub fn new(request_timeout: Duration, log: &slog::Logger) -> Self {
         let log = log.new(o!("Service" => "Libp2p-RPC"));
         Rpc {
             events: Vec::new(),
+            pending_requests: HashMap::new(),
+            request_timeout,
+            timeout_check: Interval::new(
+                Instant::now() + TIMEOUT_CHECK_INTERVAL,
+                TIMEOUT_CHECK_INTERVAL,
+            ),
             marker: PhantomData,
-            _log: log,
+            log,
         }
     }
 
     /// Submits and RPC request.
+    ///
+    /// Requests which expect a response generate an `RPCEvent::Timeout` if the peer does not
+    /// respond in time.
     pub fn send_rpc(&mut self, peer_id: PeerId, rpc_event: RPCEvent) {
+        match &rpc_event {
+            RPCEvent::Request {
+                id,
+                method_id,
+                body,
+            } => {
+                if let RPCRequest::Goodbye(_) = body {
+                    // A goodbye is not responded to.
+                } else {
+                    self.pending_requests.insert(
+                        (peer_id.clone(), id.clone()),
+                        (*method_id, Instant::now() + self.request_timeout),
+                    );
+                }
+            }
+            RPCEvent::Response { .. } => {}
+            RPCEvent::Error { .. } | RPCEvent::Timeout { .. } => {
+                warn!(self.log, "Ignoring an RPC event which cannot be sent"; "event" => format!("{:?}", rpc_event));
+                return;
+            }
+        }
+
         self.events.push(NetworkBehaviourAction::SendEvent {
             peer_id,
             event: rpc_event,
         });
     }
+
+    /// Generates an `RPCEvent::Timeout` for each pending request which has timed out.
+    fn expire_requests(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<(PeerId, RequestId)> = self
+            .pending_requests
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for (peer_id, id) in expired {
+            if let Some((method_id, _)) =
+                self.pending_requests.remove(&(peer_id.clone(), id.clone()))
+            {
+                debug!(
+                    self.log,
+                    "RPC request timed out";
+                    "peer" => format!("{:?}", peer_id),
+                    "request_id" => format!("{:?}", id)
+                );
+                self.events
+                    .push(NetworkBehaviourAction::GenerateEvent(RPCMessage::RPC(
+                        peer_id,
+                        RPCEvent::Timeout { id, method_id },
+                    )));
+            }
+        }
+    }
 }
 
 impl<TSubstream> NetworkBehaviour for Rpc<TSubstream>
@@ -65,14 +138,23 @@ where
 
     fn inject_connected(&mut self, peer_id: PeerId, connected_point: ConnectedPoint) {
         // if initialised the connection, report this upwards to send the HELLO request
-        if let ConnectedPoint::Dialer { .. } = connected_point {
-            self.events.push(NetworkBehaviourAction::GenerateEvent(
-                RPCMessage::PeerDialed(peer_id),
-            ));
-        }
+        let event = match connected_point {
+            ConnectedPoint::Dialer { .. } => RPCMessage::PeerDialed(peer_id),
+            ConnectedPoint::Listener { .. } => RPCMessage::PeerAccepted(peer_id),
+        };
+        self.events
+            .push(NetworkBehaviourAction::GenerateEvent(event));
     }
 
-    fn inject_disconnected(&mut self, _: &PeerId, _: ConnectedPoint) {}
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        // Requests to the peer will not be responded to. The disconnection is reported instead of
+        // timing them out.
+        self.pending_requests
+            .retain(|(request_peer_id, _), _| request_peer_id != peer_id);
+        self.events.push(NetworkBehaviourAction::GenerateEvent(
+            RPCMessage::PeerDisconnected(peer_id.clone()),
+        ));
+    }
 
     fn inject_node_event(
         &mut self,
@@ -85,6 +167,25 @@ where
             OneShotEvent::Sent => return,
         };
 
+        if let RPCEvent::Response { id, .. } = &event {
+            self.pending_requests.remove(&(source.clone(), id.clone()));
+        }
+
+        // surface responses stating that the request could not be fulfilled
+        let event = match event {
+            RPCEvent::Response {
+                id,
+                method_id,
+                result: RPCResponse::Error { code, message },
+            } => RPCEvent::Error {
+                id,
+                method_id,
+                code,
+                message,
+            },
+            event => event,
+        };
+
         // send the event to the user
         self.events
             .push(NetworkBehaviourAction::GenerateEvent(RPCMessage::RPC(
@@ -101,6 +202,17 @@ where
             Self::OutEvent,
         >,
     > {
+        loop {
+            match self.timeout_check.poll() {
+                Ok(Async::Ready(_)) => self.expire_requests(),
+                Ok(Async::NotReady) => break,
+                Err(e) => {
+                    warn!(self.log, "RPC timeout timer failed"; "error" => format!("{:?}", e));
+                    break;
+                }
+            }
+        }
+
         if !self.events.is_empty() {
             return Async::Ready(self.events.remove(0));
         }
@@ -111,7 +223,11 @@ where
 /// Messages sent to the user from the RPC protocol.
 pub enum RPCMessage {
     RPC(PeerId, RPCEvent),
+    /// We initiated a connection to the peer.
     PeerDialed(PeerId),
+    /// The peer initiated a connection to us.
+    PeerAccepted(PeerId),
+    PeerDisconnected(PeerId),
 }
 
 /// Transmission between the `OneShotHandler` and the `RPCEvent`.
@@ -136,3 +252,62 @@ impl From<()> for OneShotEvent {
         OneShotEvent::Sent
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::Discard;
+
+    fn request(id: u64, body: RPCRequest) -> RPCEvent {
+        RPCEvent::Request {
+            id: RequestId::from(id),
+            method_id: body.method_id(),
+            body,
+        }
+    }
+
+    #[test]
+    fn expired_requests_time_out() {
+        let mut rpc = Rpc::<()>::new(Duration::from_secs(0), &slog::Logger::root(Discard, o!()));
+        let peer_id = PeerId::random();
+
+        rpc.send_rpc(
+            peer_id.clone(),
+            request(
+                1,
+                RPCRequest::Hello(HelloMessage {
+                    network_id: 1,
+                    latest_finalized_root: Default::default(),
+                    latest_finalized_epoch: Default::default(),
+                    best_root: Default::default(),
+                    best_slot: Default::default(),
+                }),
+            ),
+        );
+        // A goodbye is not responded to, so it never times out.
+        rpc.send_rpc(
+            peer_id.clone(),
+            request(
+                2,
+                RPCRequest::Goodbye(methods::GoodbyeReason::ClientShutdown),
+            ),
+        );
+        rpc.events.clear();
+
+        rpc.expire_requests();
+
+        assert_eq!(rpc.events.len(), 1);
+        match rpc.events.remove(0) {
+            NetworkBehaviourAction::GenerateEvent(RPCMessage::RPC(
+                timed_out,
+                RPCEvent::Timeout { id, method_id },
+            )) => {
+                assert_eq!(timed_out, peer_id);
+                assert_eq!(id, RequestId::from(1));
+                assert_eq!(method_id, Into::<u16>::into(RPCMethod::Hello));
+            }
+            _ => panic!("expected a request timeout"),
+        }
+        assert!(rpc.pending_requests.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use super::methods::*;
+use futures::future;
 use libp2p::core::{upgrade, InboundUpgrade, OutboundUpgrade, UpgradeInfo};
-use ssz::{ssz_encode, Decodable, DecodeError as SSZDecodeError, Encodable, SszStream};
+use ssz::{Decodable, DecodeError as SSZDecodeError, Encodable, SszStream};
 use std::hash::{Hash, Hasher};
 use std::io;
 use std::iter;
@@ -9,6 +10,13 @@ use tokio::io::{AsyncRead, AsyncWrite};
 /// The maximum bytes that can be sent across the RPC.
 const MAX_READ_SIZE: usize = 4_194_304; // 4M
 
+/// The kinds of message, sent as the first byte of each message.
+const RESPONSE: u8 = 0;
+const REQUEST: u8 = 1;
+/// A response to a request which could not be fulfilled, followed by the error code and message
+/// in place of the body.
+const ERROR_RESPONSE: u8 = 2;
+
 /// Implementation of the `ConnectionUpgrade` for the rpc protocol.
 
 #[derive(Debug, Clone)]
@@ -98,6 +106,19 @@ pub enum RPCEvent {
         method_id: u16, //TODO: Remove and process decoding upstream
         result: RPCResponse,
     },
+    /// The peer responded to our request with an `RPCResponse::Error`.
+    ///
+    /// Generated by the `Rpc` behaviour from the response, and never sent to a peer.
+    Error {
+        id: RequestId,
+        method_id: u16,
+        code: RPCErrorCode,
+        message: String,
+    },
+    /// The peer did not respond to our request in time.
+    ///
+    /// Generated by the `Rpc` behaviour, and never sent to a peer.
+    Timeout { id: RequestId, method_id: u16 },
 }
 
 impl UpgradeInfo for RPCEvent {
@@ -127,12 +148,12 @@ where
 
 fn decode(packet: Vec<u8>) -> Result<RPCEvent, DecodeError> {
     // decode the header of the rpc
-    // request/response
-    let (request, index) = bool::ssz_decode(&packet, 0)?;
+    // request/response/error response
+    let (kind, index) = u8::ssz_decode(&packet, 0)?;
     let (id, index) = RequestId::ssz_decode(&packet, index)?;
     let (method_id, index) = u16::ssz_decode(&packet, index)?;
 
-    if request {
+    if kind == REQUEST {
         let body = match RPCMethod::from(method_id) {
             RPCMethod::Hello => {
                 let (hello_body, _index) = HelloMessage::ssz_decode(&packet, index)?;
@@ -162,6 +183,11 @@ fn decode(packet: Vec<u8>) -> Result<RPCEvent, DecodeError> {
                     BeaconChainStateRequest::ssz_decode(&packet, index)?;
                 RPCRequest::BeaconChainState(chain_state_request)
             }
+            RPCMethod::BeaconBlocksByRange => {
+                let (blocks_by_range_request, _index) =
+                    BeaconBlocksByRangeRequest::ssz_decode(&packet, index)?;
+                RPCRequest::BeaconBlocksByRange(blocks_by_range_request)
+            }
             RPCMethod::Unknown => return Err(DecodeError::UnknownRPCMethod),
         };
 
@@ -171,14 +197,29 @@ fn decode(packet: Vec<u8>) -> Result<RPCEvent, DecodeError> {
             body,
         })
     }
+    // we have received an error response
+    else if kind == ERROR_RESPONSE {
+        let (code, index) = u16::ssz_decode(&packet, index)?;
+        let (message, _index) = <Vec<u8>>::ssz_decode(&packet, index)?;
+
+        Ok(RPCEvent::Response {
+            id,
+            method_id,
+            result: RPCResponse::Error {
+                code: RPCErrorCode::from(code),
+                message: String::from_utf8_lossy(&message).into_owned(),
+            },
+        })
+    }
     // we have received a response
-    else {
+    else if kind == RESPONSE {
         let result = match RPCMethod::from(method_id) {
             RPCMethod::Hello => {
                 let (body, _index) = HelloMessage::ssz_decode(&packet, index)?;
                 RPCResponse::Hello(body)
             }
-            RPCMethod::Goodbye => unreachable!("Should never receive a goodbye response"),
+            // A goodbye is never responded to.
+            RPCMethod::Goodbye => return Err(DecodeError::UnknownRPCMethod),
             RPCMethod::BeaconBlockRoots => {
                 let (body, _index) = BeaconBlockRootsResponse::ssz_decode(&packet, index)?;
                 RPCResponse::BeaconBlockRoots(body)
@@ -195,6 +236,10 @@ fn decode(packet: Vec<u8>) -> Result<RPCEvent, DecodeError> {
                 let (body, _index) = BeaconChainStateResponse::ssz_decode(&packet, index)?;
                 RPCResponse::BeaconChainState(body)
             }
+            RPCMethod::BeaconBlocksByRange => {
+                let (body, _index) = BeaconBlocksByRangeResponse::ssz_decode(&packet, index)?;
+                RPCResponse::BeaconBlocksByRange(body)
+            }
             RPCMethod::Unknown => return Err(DecodeError::UnknownRPCMethod),
         };
         Ok(RPCEvent::Response {
@@ -202,6 +247,8 @@ fn decode(packet: Vec<u8>) -> Result<RPCEvent, DecodeError> {
             method_id,
             result,
         })
+    } else {
+        Err(DecodeError::UnknownMessageKind)
     }
 }
 
@@ -211,24 +258,36 @@ where
 {
     type Output = ();
     type Error = io::Error;
-    type Future = upgrade::WriteOne<upgrade::Negotiated<TSocket>>;
+    type Future = future::Either<
+        upgrade::WriteOne<upgrade::Negotiated<TSocket>>,
+        future::FutureResult<(), io::Error>,
+    >;
 
     #[inline]
     fn upgrade_outbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
-        let bytes = ssz_encode(&self);
-        upgrade::write_one(socket, bytes)
+        match self.encode() {
+            Some(bytes) => future::Either::A(upgrade::write_one(socket, bytes)),
+            None => future::Either::B(future::err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "RPC event cannot be sent to a peer",
+            ))),
+        }
     }
 }
 
-impl Encodable for RPCEvent {
-    fn ssz_append(&self, s: &mut SszStream) {
+impl RPCEvent {
+    /// Encodes the event to be sent to a peer.
+    ///
+    /// Returns `None` for an `Error` or `Timeout`, which are only generated locally.
+    fn encode(&self) -> Option<Vec<u8>> {
+        let mut s = SszStream::new();
         match self {
             RPCEvent::Request {
                 id,
                 method_id,
                 body,
             } => {
-                s.append(&true);
+                s.append(&REQUEST);
                 s.append(id);
                 s.append(method_id);
                 match body {
@@ -250,6 +309,9 @@ impl Encodable for RPCEvent {
                     RPCRequest::BeaconChainState(body) => {
                         s.append(body);
                     }
+                    RPCRequest::BeaconBlocksByRange(body) => {
+                        s.append(body);
+                    }
                 }
             }
             RPCEvent::Response {
@@ -257,7 +319,10 @@ impl Encodable for RPCEvent {
                 method_id,
                 result,
             } => {
-                s.append(&false);
+                match result {
+                    RPCResponse::Error { .. } => s.append(&ERROR_RESPONSE),
+                    _ => s.append(&RESPONSE),
+                };
                 s.append(id);
                 s.append(method_id);
                 match result {
@@ -276,9 +341,18 @@ impl Encodable for RPCEvent {
                     RPCResponse::BeaconChainState(response) => {
                         s.append(response);
                     }
+                    RPCResponse::BeaconBlocksByRange(response) => {
+                        s.append(response);
+                    }
+                    RPCResponse::Error { code, message } => {
+                        s.append(&Into::<u16>::into(code.clone()));
+                        s.append(&message.as_bytes().to_vec());
+                    }
                 }
             }
+            RPCEvent::Error { .. } | RPCEvent::Timeout { .. } => return None,
         }
+        Some(s.drain())
     }
 }
 
@@ -287,6 +361,7 @@ pub enum DecodeError {
     ReadError(upgrade::ReadOneError),
     SSZDecodeError(ssz::DecodeError),
     UnknownRPCMethod,
+    UnknownMessageKind,
 }
 
 impl From<upgrade::ReadOneError> for DecodeError {
@@ -302,3 +377,96 @@ impl From<ssz::DecodeError> for DecodeError {
         DecodeError::SSZDecodeError(err)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ssz::ssz_encode;
+
+    #[test]
+    fn error_response_round_trip() {
+        let original = RPCEvent::Response {
+            id: RequestId::from(7),
+            method_id: RPCMethod::BeaconBlockRoots.into(),
+            result: RPCResponse::Error {
+                code: RPCErrorCode::ServerError,
+                message: "block roots unavailable".to_string(),
+            },
+        };
+
+        match decode(original.encode().unwrap()).unwrap() {
+            RPCEvent::Response {
+                id,
+                method_id,
+                result: RPCResponse::Error { code, message },
+            } => {
+                assert_eq!(id, RequestId::from(7));
+                assert_eq!(method_id, 10);
+                assert_eq!(code, RPCErrorCode::ServerError);
+                assert_eq!(message, "block roots unavailable");
+            }
+            other => panic!("expected an error response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn success_response_has_no_code() {
+        let hello = HelloMessage {
+            network_id: 1,
+            latest_finalized_root: Default::default(),
+            latest_finalized_epoch: Default::default(),
+            best_root: Default::default(),
+            best_slot: Default::default(),
+        };
+        let original = RPCEvent::Response {
+            id: RequestId::from(1),
+            method_id: RPCMethod::Hello.into(),
+            result: RPCResponse::Hello(hello.clone()),
+        };
+
+        // The body directly follows the header of a response.
+        let encoded = original.encode().unwrap();
+        let mut expected = ssz_encode(&RESPONSE);
+        expected.append(&mut ssz_encode(&RequestId::from(1)));
+        expected.append(&mut ssz_encode(&Into::<u16>::into(RPCMethod::Hello)));
+        expected.append(&mut ssz_encode(&hello));
+        assert_eq!(encoded, expected);
+
+        match decode(encoded).unwrap() {
+            RPCEvent::Response {
+                result: RPCResponse::Hello(hello),
+                ..
+            } => assert_eq!(hello.network_id, 1),
+            other => panic!("expected a hello response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn goodbye_response_is_rejected() {
+        let mut packet = ssz_encode(&RESPONSE);
+        packet.append(&mut ssz_encode(&RequestId::from(1)));
+        packet.append(&mut ssz_encode(&Into::<u16>::into(RPCMethod::Goodbye)));
+
+        match decode(packet) {
+            Err(DecodeError::UnknownRPCMethod) => {}
+            other => panic!("expected the response to be rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn local_events_are_not_encoded() {
+        let timeout = RPCEvent::Timeout {
+            id: RequestId::from(1),
+            method_id: RPCMethod::Hello.into(),
+        };
+        let error = RPCEvent::Error {
+            id: RequestId::from(1),
+            method_id: RPCMethod::Hello.into(),
+            code: RPCErrorCode::ServerError,
+            message: String::new(),
+        };
+
+        assert!(timeout.encode().is_none());
+        assert!(error.encode().is_none());
+    }
+}
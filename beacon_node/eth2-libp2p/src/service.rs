@@ -1,6 +1,7 @@
 use crate::behaviour::{Behaviour, BehaviourEvent, PubsubMessage};
 use crate::error;
 use crate::multiaddr::Protocol;
+use crate::node_key;
 use crate::rpc::RPCEvent;
 use crate::NetworkConfig;
 use futures::prelude::*;
@@ -39,12 +40,14 @@ impl Service {
 
         // TODO: Currently using secp256k1 key pairs. Wire protocol specifies RSA. Waiting for this
         // PR to be merged to generate RSA keys: https://github.com/briansmith/ring/pull/733
-        // TODO: Save and recover node key from disk
-        let local_private_key = identity::Keypair::generate_secp256k1();
+        let local_private_key = match &config.node_key_file {
+            Some(path) => node_key::load_or_generate(path, &log)?,
+            None => identity::Keypair::generate_secp256k1(),
+        };
 
         let local_public_key = local_private_key.public();
         let local_peer_id = PeerId::from(local_private_key.public());
-        info!(log, "Local peer id: {:?}", local_peer_id);
+        info!(log, "Local peer id: {}", local_peer_id.to_base58());
 
         let mut swarm = {
             // Set up the transport
@@ -99,6 +102,24 @@ impl Service {
     }
 }
 
+impl Service {
+    /// Closes the connection to the peer. The peer may reconnect.
+    pub fn disconnect_peer(&mut self, peer_id: PeerId) {
+        Swarm::ban_peer_id(&mut self.swarm, peer_id.clone());
+        Swarm::unban_peer_id(&mut self.swarm, peer_id);
+    }
+
+    /// Closes the connection to the peer and refuses any further connections with it, until it is
+    /// unbanned.
+    pub fn ban_peer(&mut self, peer_id: PeerId) {
+        Swarm::ban_peer_id(&mut self.swarm, peer_id);
+    }
+
+    pub fn unban_peer(&mut self, peer_id: PeerId) {
+        Swarm::unban_peer_id(&mut self.swarm, peer_id);
+    }
+}
+
 impl Stream for Service {
     type Item = Libp2pEvent;
     type Error = crate::error::Error;
@@ -129,6 +150,15 @@ impl Stream for Service {
                     BehaviourEvent::PeerDialed(peer_id) => {
                         return Ok(Async::Ready(Some(Libp2pEvent::PeerDialed(peer_id))));
                     }
+                    BehaviourEvent::PeerAccepted(peer_id) => {
+                        return Ok(Async::Ready(Some(Libp2pEvent::PeerAccepted(peer_id))));
+                    }
+                    BehaviourEvent::PeerDisconnected(peer_id) => {
+                        return Ok(Async::Ready(Some(Libp2pEvent::PeerDisconnected(peer_id))));
+                    }
+                    BehaviourEvent::InvalidGossip(peer_id) => {
+                        return Ok(Async::Ready(Some(Libp2pEvent::InvalidGossip(peer_id))));
+                    }
                     BehaviourEvent::Identified(peer_id, info) => {
                         return Ok(Async::Ready(Some(Libp2pEvent::Identified(peer_id, info))));
                     }
@@ -181,6 +211,12 @@ pub enum Libp2pEvent {
     RPC(PeerId, RPCEvent),
     /// Initiated the connection to a new peer.
     PeerDialed(PeerId),
+    /// Accepted a connection from a new peer.
+    PeerAccepted(PeerId),
+    /// The connection to a peer was closed.
+    PeerDisconnected(PeerId),
+    /// A peer published a gossip message which could not be decoded.
+    InvalidGossip(PeerId),
     /// Received information about a peer on the network.
     Identified(PeerId, Box<IdentifyInfo>),
     /// Received pubsub message.
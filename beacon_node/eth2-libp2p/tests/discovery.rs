@@ -0,0 +1,54 @@
+use eth2_libp2p::{Multiaddr, NetworkConfig, Service};
+use futures::prelude::*;
+use slog::{o, Discard, Logger};
+use std::time::Duration;
+use tokio::prelude::FutureExt;
+use tokio::runtime::current_thread::Runtime;
+
+/// Returns a config for a node listening on `port` of localhost.
+fn localhost_config(port: u16, boot_nodes: Vec<Multiaddr>) -> NetworkConfig {
+    let mut config = NetworkConfig::new(boot_nodes);
+    config.listen_port = port;
+    config.listen_addresses = vec![localhost_address(port)];
+    config.target_peers = 2;
+    config
+}
+
+fn localhost_address(port: u16) -> Multiaddr {
+    format!("/ip4/127.0.0.1/tcp/{}", port)
+        .parse()
+        .expect("is a correct multi-address")
+}
+
+#[test]
+fn nodes_discover_peers_from_a_bootnode() {
+    let log = Logger::root(Discard, o!());
+
+    // Only the bootnode is known to the other nodes, which must discover each other through it.
+    let bootnode_address = localhost_address(19_100);
+    let mut nodes: Vec<Service> = vec![
+        localhost_config(19_100, vec![]),
+        localhost_config(19_101, vec![bootnode_address.clone()]),
+        localhost_config(19_102, vec![bootnode_address]),
+    ]
+    .into_iter()
+    .map(|config| Service::new(config, log.clone()).expect("should start node"))
+    .collect();
+
+    let all_connected = futures::future::poll_fn(move || -> Poll<(), ()> {
+        for node in &mut nodes {
+            while let Ok(Async::Ready(Some(_))) = node.poll() {}
+        }
+
+        if nodes.iter().all(|node| node.swarm.connected_peers() == 2) {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    });
+
+    let mut runtime = Runtime::new().unwrap();
+    runtime
+        .block_on(all_connected.timeout(Duration::from_secs(30)))
+        .expect("each node should connect to both other nodes");
+}
@@ -59,6 +59,14 @@ pub trait BeaconChain: Send + Sync {
     fn get_block_bodies(&self, roots: &[Hash256])
         -> Result<Vec<BeaconBlockBody>, BeaconChainError>;
 
+    /// Returns the canonical blocks with slots in `start_slot..start_slot + count`, in ascending
+    /// slot order. Slots beyond the head are ignored.
+    fn get_blocks_by_range(
+        &self,
+        start_slot: Slot,
+        count: u64,
+    ) -> Result<Vec<BeaconBlock>, BeaconChainError>;
+
     fn is_new_block_root(&self, beacon_block_root: &Hash256) -> Result<bool, BeaconChainError>;
 }
 
@@ -157,6 +165,30 @@ where
         self.get_block_bodies(roots)
     }
 
+    fn get_blocks_by_range(
+        &self,
+        start_slot: Slot,
+        count: u64,
+    ) -> Result<Vec<BeaconBlock>, BeaconChainError> {
+        // Roots are only returned up to the canonical head.
+        let mut roots = self.get_block_roots(start_slot, count as usize, 0)?;
+        // A skipped slot has the root of the block before it.
+        roots.dedup();
+
+        let mut blocks = Vec::with_capacity(roots.len());
+        for root in roots {
+            let block = self
+                .get_block(&root)?
+                .ok_or_else(|| BeaconChainError::MissingBeaconBlock(root))?;
+            // The first root may belong to a block from before the range.
+            if block.slot >= start_slot {
+                blocks.push(block);
+            }
+        }
+
+        Ok(blocks)
+    }
+
     fn is_new_block_root(&self, beacon_block_root: &Hash256) -> Result<bool, BeaconChainError> {
         self.is_new_block_root(beacon_block_root)
     }
@@ -2,6 +2,7 @@
 pub mod beacon_chain;
 pub mod error;
 pub mod message_handler;
+pub mod peer_manager;
 pub mod service;
 pub mod sync;
 
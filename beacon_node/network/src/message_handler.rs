@@ -1,21 +1,23 @@
 use crate::beacon_chain::BeaconChain;
 use crate::error;
+use crate::peer_manager::{PeerAction, PeerManager};
 use crate::service::{NetworkMessage, OutgoingMessage};
 use crate::sync::SimpleSync;
-use crossbeam_channel::{unbounded as channel, Sender};
+use beacon_chain::parking_lot::RwLock;
+use crossbeam_channel::{unbounded as channel, RecvTimeoutError, Sender};
 use eth2_libp2p::{
     behaviour::PubsubMessage,
-    rpc::{methods::GoodbyeReason, RPCRequest, RPCResponse, RequestId},
+    rpc::{methods::GoodbyeReason, RPCErrorCode, RPCMethod, RPCRequest, RPCResponse, RequestId},
     PeerId, RPCEvent,
 };
 use futures::future;
 use slog::{debug, warn};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::Duration;
 
-/// Timeout for RPC requests.
-// const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// The interval at which expired bans are checked for.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 /// Timeout before banning a peer for non-identification.
 // const HELLO_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -36,12 +38,16 @@ pub struct MessageHandler {
 pub enum HandlerMessage {
     /// We have initiated a connection to a new peer.
     PeerDialed(PeerId),
+    /// A new peer has initiated a connection to us.
+    PeerAccepted(PeerId),
     /// Peer has disconnected,
     PeerDisconnected(PeerId),
     /// An RPC response/request has been received.
     RPC(PeerId, RPCEvent),
     /// A gossip message has been received.
     PubsubMessage(PeerId, Box<PubsubMessage>),
+    /// A gossip message which could not be decoded has been received.
+    InvalidGossip(PeerId),
 }
 
 impl MessageHandler {
@@ -49,6 +55,7 @@ impl MessageHandler {
     pub fn spawn(
         beacon_chain: Arc<BeaconChain>,
         network_send: crossbeam_channel::Sender<NetworkMessage>,
+        peer_manager: Arc<RwLock<PeerManager>>,
         executor: &tokio::runtime::TaskExecutor,
         log: slog::Logger,
     ) -> error::Result<Sender<HandlerMessage>> {
@@ -63,7 +70,7 @@ impl MessageHandler {
         let mut handler = MessageHandler {
             _chain: beacon_chain.clone(),
             sync,
-            network_context: NetworkContext::new(network_send, log.clone()),
+            network_context: NetworkContext::new(network_send, peer_manager, log.clone()),
             log: log.clone(),
         };
 
@@ -71,9 +78,15 @@ impl MessageHandler {
         // TODO: Handle manual termination of thread
         executor.spawn(future::poll_fn(move || -> Result<_, _> {
             loop {
-                handler.handle_message(handler_recv.recv().map_err(|_| {
-                    debug!(log, "Network message handler terminated.");
-                })?);
+                match handler_recv.recv_timeout(HEARTBEAT_INTERVAL) {
+                    Ok(message) => handler.handle_message(message),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => {
+                        debug!(log, "Network message handler terminated.");
+                        return Err(());
+                    }
+                }
+                handler.network_context.heartbeat();
             }
         }));
 
@@ -85,7 +98,17 @@ impl MessageHandler {
         match message {
             // we have initiated a connection to a peer
             HandlerMessage::PeerDialed(peer_id) => {
-                self.sync.on_connect(peer_id, &mut self.network_context);
+                if self.network_context.on_connect(peer_id.clone()) {
+                    self.sync.on_connect(peer_id, &mut self.network_context);
+                }
+            }
+            // a peer has initiated a connection to us, it will say hello
+            HandlerMessage::PeerAccepted(peer_id) => {
+                self.network_context.on_connect(peer_id);
+            }
+            HandlerMessage::PeerDisconnected(peer_id) => {
+                self.network_context.on_disconnect(&peer_id);
+                self.sync.on_disconnect(&peer_id, &mut self.network_context);
             }
             // we have received an RPC message request/response
             HandlerMessage::RPC(peer_id, rpc_event) => {
@@ -95,8 +118,10 @@ impl MessageHandler {
             HandlerMessage::PubsubMessage(peer_id, gossip) => {
                 self.handle_gossip(peer_id, *gossip);
             }
-            //TODO: Handle all messages
-            _ => {}
+            HandlerMessage::InvalidGossip(peer_id) => {
+                self.network_context
+                    .report_peer(peer_id, PeerAction::InvalidMessage);
+            }
         }
     }
 
@@ -108,6 +133,10 @@ impl MessageHandler {
             RPCEvent::Request { id, body, .. // TODO: Clean up RPC Message types, have a cleaner type by this point.
             } => self.handle_rpc_request(peer_id, id, body),
             RPCEvent::Response { id, result, .. } => self.handle_rpc_response(peer_id, id, result),
+            RPCEvent::Error {
+                id, code, message, ..
+            } => self.handle_rpc_error(peer_id, id, code, message),
+            RPCEvent::Timeout { id, .. } => self.handle_rpc_timeout(peer_id, id),
         }
     }
 
@@ -140,22 +169,39 @@ impl MessageHandler {
                 request,
                 &mut self.network_context,
             ),
+            RPCRequest::BeaconBlocksByRange(request) => {
+                self.sync.on_beacon_blocks_by_range_request(
+                    peer_id,
+                    request_id,
+                    request,
+                    &mut self.network_context,
+                )
+            }
             RPCRequest::BeaconChainState(_) => {
                 // We do not implement this endpoint, it is not required and will only likely be
                 // useful for light-client support in later phases.
                 warn!(self.log, "BeaconChainState RPC call is not supported.");
+                self.network_context.send_rpc_error_response(
+                    peer_id,
+                    request_id,
+                    RPCMethod::BeaconChainState,
+                    RPCErrorCode::MethodNotFound,
+                    "BeaconChainState is not supported".to_string(),
+                );
             }
         }
     }
 
     /// An RPC response has been received from the network.
-    // we match on id and ignore responses past the timeout.
+    ///
+    /// Responses which do not match an outstanding request (e.g., those which arrive after the
+    /// request timed out) are ignored. The peer is credited for a useful response by the sync
+    /// handlers, once the response has been processed successfully.
     fn handle_rpc_response(&mut self, peer_id: PeerId, id: RequestId, response: RPCResponse) {
         // if response id is not related to a request, ignore (likely RPC timeout)
         if self
             .network_context
-            .outstanding_outgoing_request_ids
-            .remove(&(peer_id.clone(), id.clone()))
+            .take_outstanding_request(&peer_id, &id)
             .is_none()
         {
             warn!(
@@ -172,25 +218,23 @@ impl MessageHandler {
                 self.sync
                     .on_hello_response(peer_id, hello_message, &mut self.network_context);
             }
-            RPCResponse::BeaconBlockRoots(response) => {
-                self.sync.on_beacon_block_roots_response(
+            RPCResponse::BeaconBlocksByRange(response) => {
+                self.sync.on_beacon_blocks_by_range_response(
                     peer_id,
+                    id,
                     response,
                     &mut self.network_context,
                 );
             }
-            RPCResponse::BeaconBlockHeaders(response) => {
-                self.sync.on_beacon_block_headers_response(
-                    peer_id,
-                    response,
-                    &mut self.network_context,
-                );
-            }
-            RPCResponse::BeaconBlockBodies(response) => {
-                self.sync.on_beacon_block_bodies_response(
-                    peer_id,
-                    response,
-                    &mut self.network_context,
+            RPCResponse::BeaconBlockRoots(_)
+            | RPCResponse::BeaconBlockHeaders(_)
+            | RPCResponse::BeaconBlockBodies(_) => {
+                // Sync downloads blocks by range, so these are only served, never requested.
+                warn!(
+                    self.log,
+                    "Unsolicited block response";
+                    "peer" => format!("{:?}", peer_id),
+                    "method" => format!("{:?}", RPCMethod::from(response.method_id())),
                 );
             }
             RPCResponse::BeaconChainState(_) => {
@@ -201,9 +245,57 @@ impl MessageHandler {
                 // beacon state RPC request.
                 warn!(self.log, "BeaconChainState RPC call is not supported.");
             }
+            RPCResponse::Error { .. } => {
+                // The RPC behaviour surfaces error responses as an `RPCEvent::Error`.
+                warn!(self.log, "Unexpected RPC error response"; "peer" => format!("{:?}", peer_id));
+            }
         };
     }
 
+    /// The peer responded to one of our requests with an error.
+    ///
+    /// The peer is not penalized, as it may simply be unable to serve the request, but the request
+    /// is retried with another peer.
+    fn handle_rpc_error(
+        &mut self,
+        peer_id: PeerId,
+        id: RequestId,
+        code: RPCErrorCode,
+        message: String,
+    ) {
+        warn!(
+            self.log,
+            "RPC error response";
+            "peer" => format!("{:?}", peer_id),
+            "request_id" => format!("{:?}", id),
+            "code" => format!("{:?}", code),
+            "message" => message
+        );
+
+        if let Some(request) = self.network_context.take_outstanding_request(&peer_id, &id) {
+            self.sync
+                .on_request_failure(peer_id, id, request, &mut self.network_context);
+        }
+    }
+
+    /// The peer did not respond to one of our requests in time.
+    ///
+    /// The peer is penalized and the request is retried with another peer.
+    fn handle_rpc_timeout(&mut self, peer_id: PeerId, id: RequestId) {
+        if let Some(request) = self.network_context.take_outstanding_request(&peer_id, &id) {
+            debug!(
+                self.log,
+                "RPC request timed out";
+                "peer" => format!("{:?}", peer_id),
+                "request_id" => format!("{:?}", id)
+            );
+            self.network_context
+                .report_peer(peer_id.clone(), PeerAction::RequestTimeout);
+            self.sync
+                .on_request_failure(peer_id, id, request, &mut self.network_context);
+        }
+    }
+
     /// Handle RPC messages
     fn handle_gossip(&mut self, peer_id: PeerId, gossip_message: PubsubMessage) {
         match gossip_message {
@@ -223,43 +315,103 @@ impl MessageHandler {
 pub struct NetworkContext {
     /// The network channel to relay messages to the Network service.
     network_send: crossbeam_channel::Sender<NetworkMessage>,
-    /// A mapping of peers and the RPC id we have sent an RPC request to.
-    outstanding_outgoing_request_ids: HashMap<(PeerId, RequestId), Instant>,
+    /// The RPC requests we have sent to peers which are awaiting a response.
+    outstanding_outgoing_requests: HashMap<(PeerId, RequestId), RPCRequest>,
     /// Stores the next `RequestId` we should include on an outgoing `RPCRequest` to a `PeerId`.
     outgoing_request_ids: HashMap<PeerId, RequestId>,
+    /// Scores, bans and limits the connected peers.
+    peer_manager: Arc<RwLock<PeerManager>>,
     /// The `MessageHandler` logger.
     log: slog::Logger,
 }
 
 impl NetworkContext {
-    pub fn new(network_send: crossbeam_channel::Sender<NetworkMessage>, log: slog::Logger) -> Self {
+    pub fn new(
+        network_send: crossbeam_channel::Sender<NetworkMessage>,
+        peer_manager: Arc<RwLock<PeerManager>>,
+        log: slog::Logger,
+    ) -> Self {
         Self {
             network_send,
-            outstanding_outgoing_request_ids: HashMap::new(),
+            outstanding_outgoing_requests: HashMap::new(),
             outgoing_request_ids: HashMap::new(),
+            peer_manager,
             log,
         }
     }
 
+    /// Registers a new connection with the peer manager, disconnecting the peer if it is refused.
+    ///
+    /// Returns `true` if the peer may remain connected.
+    pub fn on_connect(&mut self, peer_id: PeerId) -> bool {
+        let result = self.peer_manager.write().connect(&peer_id);
+        match result {
+            Ok(()) => true,
+            Err(reason) => {
+                self.disconnect(peer_id, reason);
+                false
+            }
+        }
+    }
+
+    pub fn on_disconnect(&mut self, peer_id: &PeerId) {
+        self.peer_manager.write().disconnect(peer_id);
+        self.outstanding_outgoing_requests
+            .retain(|(request_peer_id, _), _| request_peer_id != peer_id);
+        self.outgoing_request_ids.remove(peer_id);
+    }
+
+    /// Adjusts the score of the peer, banning it if its score falls too low.
+    pub fn report_peer(&mut self, peer_id: PeerId, action: PeerAction) {
+        let ban = self.peer_manager.write().report(&peer_id, action);
+        if ban {
+            self.send_rpc_request(peer_id.clone(), RPCRequest::Goodbye(GoodbyeReason::Fault));
+            self.send_network_message(NetworkMessage::Ban(peer_id));
+        }
+    }
+
+    /// Says goodbye to the peer and closes the connection.
     pub fn disconnect(&mut self, peer_id: PeerId, reason: GoodbyeReason) {
-        self.send_rpc_request(peer_id, RPCRequest::Goodbye(reason))
-        // TODO: disconnect peers.
+        self.send_rpc_request(peer_id.clone(), RPCRequest::Goodbye(reason));
+        self.send_network_message(NetworkMessage::Disconnect(peer_id));
     }
 
-    pub fn send_rpc_request(&mut self, peer_id: PeerId, rpc_request: RPCRequest) {
+    /// Decays peer scores and lifts expired bans.
+    pub fn heartbeat(&mut self) {
+        let unbanned = {
+            let mut peer_manager = self.peer_manager.write();
+            peer_manager.decay_scores();
+            peer_manager.expire_bans()
+        };
+        for peer_id in unbanned {
+            self.send_network_message(NetworkMessage::Unban(peer_id));
+        }
+    }
+
+    /// Sends the request to the peer, returning the id which its response will carry.
+    pub fn send_rpc_request(&mut self, peer_id: PeerId, rpc_request: RPCRequest) -> RequestId {
         let id = self.generate_request_id(&peer_id);
 
-        self.outstanding_outgoing_request_ids
-            .insert((peer_id.clone(), id.clone()), Instant::now());
+        // A goodbye is not responded to.
+        let expects_response = match rpc_request {
+            RPCRequest::Goodbye(_) => false,
+            _ => true,
+        };
+        if expects_response {
+            self.outstanding_outgoing_requests
+                .insert((peer_id.clone(), id.clone()), rpc_request.clone());
+        }
 
         self.send_rpc_event(
             peer_id,
             RPCEvent::Request {
-                id,
+                id: id.clone(),
                 method_id: rpc_request.method_id(),
                 body: rpc_request,
             },
         );
+
+        id
     }
 
     pub fn send_rpc_response(
@@ -278,20 +430,48 @@ impl NetworkContext {
         );
     }
 
+    /// Responds to a request with an `RPCResponse::Error`.
+    pub fn send_rpc_error_response(
+        &mut self,
+        peer_id: PeerId,
+        request_id: RequestId,
+        method: RPCMethod,
+        code: RPCErrorCode,
+        message: String,
+    ) {
+        self.send_rpc_event(
+            peer_id,
+            RPCEvent::Response {
+                id: request_id,
+                method_id: method.into(),
+                result: RPCResponse::Error { code, message },
+            },
+        );
+    }
+
+    /// Removes and returns the request with the given id, if it is awaiting a response from the
+    /// peer.
+    pub fn take_outstanding_request(
+        &mut self,
+        peer_id: &PeerId,
+        request_id: &RequestId,
+    ) -> Option<RPCRequest> {
+        self.outstanding_outgoing_requests
+            .remove(&(peer_id.clone(), request_id.clone()))
+    }
+
     fn send_rpc_event(&self, peer_id: PeerId, rpc_event: RPCEvent) {
         self.send(peer_id, OutgoingMessage::RPC(rpc_event))
     }
 
     fn send(&self, peer_id: PeerId, outgoing_message: OutgoingMessage) {
+        self.send_network_message(NetworkMessage::Send(peer_id, outgoing_message))
+    }
+
+    fn send_network_message(&self, message: NetworkMessage) {
         self.network_send
-            .send(NetworkMessage::Send(peer_id, outgoing_message))
-            .unwrap_or_else(|_| {
-                warn!(
-                    self.log,
-                    "Could not send RPC message to the network service"
-                )
-            });
-        //
+            .send(message)
+            .unwrap_or_else(|_| warn!(self.log, "Could not send message to the network service"));
     }
 
     /// Returns the next `RequestId` for sending an `RPCRequest` to the `peer_id`.
@@ -0,0 +1,397 @@
+use crate::NetworkConfig;
+use eth2_libp2p::rpc::methods::GoodbyeReason;
+use eth2_libp2p::PeerId;
+use slog::{debug, info, warn};
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// The score of a peer we have not yet judged.
+const DEFAULT_SCORE: i64 = 0;
+/// Scores are capped, so a long history of good behaviour cannot outweigh recent faults.
+const MAX_SCORE: i64 = 100;
+const MIN_SCORE: i64 = -100;
+/// A peer whose score falls to this value is disconnected and banned.
+const BAN_THRESHOLD: i64 = -50;
+/// The time for which a banned peer may not reconnect.
+const BAN_DURATION: Duration = Duration::from_secs(30 * 60);
+/// Scores which are not banned move one point towards the default each interval, so old
+/// behaviour is eventually forgotten.
+const SCORE_DECAY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Behaviour of a peer which affects its score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeerAction {
+    /// The peer sent us a block which is invalid.
+    InvalidBlock,
+    /// The peer sent us a message which could not be decoded.
+    InvalidMessage,
+    /// The peer did not respond to an RPC request in time.
+    RequestTimeout,
+    /// The peer responded to an RPC request we made, and the response was processed successfully.
+    UsefulResponse,
+}
+
+impl PeerAction {
+    /// The change to a peer's score caused by this action.
+    fn score_change(self) -> i64 {
+        match self {
+            PeerAction::InvalidBlock => -50,
+            PeerAction::InvalidMessage => -20,
+            PeerAction::RequestTimeout => -10,
+            PeerAction::UsefulResponse => 5,
+        }
+    }
+}
+
+/// The score and history of a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats {
+    pub score: i64,
+    /// `None` if the peer is not connected.
+    pub connected_since: Option<Instant>,
+    /// `Some` until the time at which the peer's ban is lifted.
+    pub banned_until: Option<Instant>,
+    pub invalid_blocks: u64,
+    pub invalid_messages: u64,
+    pub request_timeouts: u64,
+    pub useful_responses: u64,
+}
+
+impl Default for PeerStats {
+    fn default() -> Self {
+        Self {
+            score: DEFAULT_SCORE,
+            connected_since: None,
+            banned_until: None,
+            invalid_blocks: 0,
+            invalid_messages: 0,
+            request_timeouts: 0,
+            useful_responses: 0,
+        }
+    }
+}
+
+impl PeerStats {
+    pub fn is_connected(&self) -> bool {
+        self.connected_since.is_some()
+    }
+
+    pub fn is_banned(&self) -> bool {
+        self.banned_until.is_some()
+    }
+}
+
+/// Scores peers by their behaviour, banning those which misbehave, and limits the number of
+/// connected peers.
+///
+/// The `PeerManager` only makes decisions; the caller is responsible for disconnecting, banning
+/// and unbanning peers on the network.
+pub struct PeerManager {
+    peers: HashMap<PeerId, PeerStats>,
+    /// The number of connected peers we aim for.
+    target_peers: usize,
+    /// Connections beyond this number of peers are refused.
+    max_peers: usize,
+    /// The time up to which scores have been decayed.
+    last_decay: Instant,
+    log: slog::Logger,
+}
+
+impl PeerManager {
+    pub fn new(config: &NetworkConfig, log: slog::Logger) -> Self {
+        Self {
+            peers: HashMap::new(),
+            target_peers: config.target_peers,
+            max_peers: max(config.max_peers, config.target_peers),
+            last_decay: Instant::now(),
+            log,
+        }
+    }
+
+    /// Records a new connection to the peer.
+    ///
+    /// Returns `Err` with the reason the peer should be disconnected if we are connected to the
+    /// maximum number of peers, or the peer is banned.
+    pub fn connect(&mut self, peer_id: &PeerId) -> Result<(), GoodbyeReason> {
+        if self.peer_stats(peer_id).map_or(false, PeerStats::is_banned) {
+            warn!(self.log, "Banned peer connected"; "peer" => format!("{:?}", peer_id));
+            return Err(GoodbyeReason::Fault);
+        }
+
+        if self.connected_peers() >= self.max_peers {
+            debug!(
+                self.log, "Refusing peer";
+                "peer" => format!("{:?}", peer_id),
+                "connected_peers" => self.connected_peers(),
+            );
+            return Err(GoodbyeReason::TooManyPeers);
+        }
+
+        self.peers
+            .entry(peer_id.clone())
+            .or_insert_with(PeerStats::default)
+            .connected_since = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Records that the connection to the peer was closed.
+    pub fn disconnect(&mut self, peer_id: &PeerId) {
+        if let Some(stats) = self.peers.get_mut(peer_id) {
+            stats.connected_since = None;
+        }
+        self.prune(peer_id);
+    }
+
+    /// Adjusts the peer's score for the action.
+    ///
+    /// Returns `true` if the peer's score has fallen to the ban threshold, in which case the peer
+    /// is now banned and should be disconnected.
+    pub fn report(&mut self, peer_id: &PeerId, action: PeerAction) -> bool {
+        let stats = self
+            .peers
+            .entry(peer_id.clone())
+            .or_insert_with(PeerStats::default);
+
+        match action {
+            PeerAction::InvalidBlock => stats.invalid_blocks += 1,
+            PeerAction::InvalidMessage => stats.invalid_messages += 1,
+            PeerAction::RequestTimeout => stats.request_timeouts += 1,
+            PeerAction::UsefulResponse => stats.useful_responses += 1,
+        }
+        stats.score = max(
+            MIN_SCORE,
+            min(MAX_SCORE, stats.score + action.score_change()),
+        );
+
+        let ban = stats.score <= BAN_THRESHOLD && !stats.is_banned();
+        if ban {
+            stats.banned_until = Some(Instant::now() + BAN_DURATION);
+            info!(
+                self.log, "Banning peer";
+                "peer" => format!("{:?}", peer_id),
+                "score" => stats.score,
+                "action" => format!("{:?}", action),
+            );
+        }
+
+        // Reports about peers we are not connected to (e.g., the authors of gossip) only matter
+        // if they result in a ban.
+        self.prune(peer_id);
+
+        ban
+    }
+
+    /// Lifts the bans which have expired, resetting the score of each peer.
+    ///
+    /// Returns the peers which are no longer banned.
+    pub fn expire_bans(&mut self) -> Vec<PeerId> {
+        let now = Instant::now();
+        let expired: Vec<PeerId> = self
+            .peers
+            .iter()
+            .filter(|(_, stats)| stats.banned_until.map_or(false, |until| until <= now))
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+
+        for peer_id in &expired {
+            if let Some(stats) = self.peers.get_mut(peer_id) {
+                stats.banned_until = None;
+                stats.score = DEFAULT_SCORE;
+            }
+            self.prune(peer_id);
+        }
+
+        expired
+    }
+
+    /// Decays the scores of peers which are not banned by a point for each `SCORE_DECAY_INTERVAL`
+    /// since the last decay.
+    pub fn decay_scores(&mut self) {
+        let elapsed = Instant::now() - self.last_decay;
+        let points = elapsed.as_secs() / SCORE_DECAY_INTERVAL.as_secs();
+
+        if points > 0 {
+            self.last_decay += SCORE_DECAY_INTERVAL * points as u32;
+            self.decay(points as i64);
+        }
+    }
+
+    /// Moves the scores of peers which are not banned `points` towards the default, forgetting
+    /// the peers which may now be pruned.
+    fn decay(&mut self, points: i64) {
+        for stats in self.peers.values_mut().filter(|stats| !stats.is_banned()) {
+            stats.score = if stats.score > DEFAULT_SCORE {
+                max(DEFAULT_SCORE, stats.score - points)
+            } else {
+                min(DEFAULT_SCORE, stats.score + points)
+            };
+        }
+
+        self.peers.retain(|_, stats| !is_prunable(stats));
+    }
+
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.peer_stats(peer_id).map_or(false, PeerStats::is_banned)
+    }
+
+    /// Returns `true` if fewer than the target number of peers are connected.
+    pub fn needs_peers(&self) -> bool {
+        self.connected_peers() < self.target_peers
+    }
+
+    /// The number of peers which are presently connected.
+    pub fn connected_peers(&self) -> usize {
+        self.peers
+            .values()
+            .filter(|stats| stats.is_connected())
+            .count()
+    }
+
+    pub fn peer_stats(&self, peer_id: &PeerId) -> Option<&PeerStats> {
+        self.peers.get(peer_id)
+    }
+
+    /// Returns the stats of all connected or banned peers.
+    pub fn all_peer_stats(&self) -> Vec<(PeerId, PeerStats)> {
+        self.peers
+            .iter()
+            .map(|(peer_id, stats)| (peer_id.clone(), stats.clone()))
+            .collect()
+    }
+
+    /// Forgets a peer which is neither connected nor banned, so the peer table does not grow
+    /// without bound.
+    ///
+    /// Peers with a score below the default are remembered until it decays, so a peer cannot
+    /// clear its faults by reconnecting.
+    fn prune(&mut self, peer_id: &PeerId) {
+        if self.peers.get(peer_id).map_or(false, is_prunable) {
+            self.peers.remove(peer_id);
+        }
+    }
+}
+
+/// Returns `true` if the peer is neither connected, banned, nor has a score below the default.
+fn is_prunable(stats: &PeerStats) -> bool {
+    !stats.is_connected() && !stats.is_banned() && stats.score >= DEFAULT_SCORE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{o, Discard, Logger};
+
+    fn peer_manager(target_peers: usize, max_peers: usize) -> PeerManager {
+        let mut config = NetworkConfig::default();
+        config.target_peers = target_peers;
+        config.max_peers = max_peers;
+        PeerManager::new(&config, Logger::root(Discard, o!()))
+    }
+
+    #[test]
+    fn refuses_peers_beyond_the_maximum() {
+        let mut manager = peer_manager(1, 2);
+
+        assert!(manager.connect(&PeerId::random()).is_ok());
+        assert!(!manager.needs_peers());
+        assert!(manager.connect(&PeerId::random()).is_ok());
+
+        match manager.connect(&PeerId::random()) {
+            Err(GoodbyeReason::TooManyPeers) => {}
+            other => panic!("expected the peer to be refused, got {:?}", other),
+        }
+        assert_eq!(manager.connected_peers(), 2);
+    }
+
+    #[test]
+    fn bans_peers_below_the_threshold() {
+        let mut manager = peer_manager(10, 20);
+        let peer_id = PeerId::random();
+        manager.connect(&peer_id).unwrap();
+
+        assert!(!manager.report(&peer_id, PeerAction::InvalidMessage));
+        assert!(!manager.report(&peer_id, PeerAction::InvalidMessage));
+        assert!(manager.report(&peer_id, PeerAction::InvalidMessage));
+        assert!(manager.is_banned(&peer_id));
+
+        // A banned peer is only reported as banned once.
+        assert!(!manager.report(&peer_id, PeerAction::InvalidBlock));
+
+        // Banned peers are remembered after they disconnect, and may not reconnect.
+        manager.disconnect(&peer_id);
+        assert!(manager.connect(&peer_id).is_err());
+
+        let stats = manager.peer_stats(&peer_id).unwrap();
+        assert_eq!(stats.invalid_messages, 3);
+        assert_eq!(stats.invalid_blocks, 1);
+        assert_eq!(stats.score, -100);
+    }
+
+    #[test]
+    fn useful_responses_offset_faults() {
+        let mut manager = peer_manager(10, 20);
+        let peer_id = PeerId::random();
+        manager.connect(&peer_id).unwrap();
+
+        for _ in 0..4 {
+            manager.report(&peer_id, PeerAction::UsefulResponse);
+        }
+        assert!(!manager.report(&peer_id, PeerAction::InvalidBlock));
+        assert_eq!(manager.peer_stats(&peer_id).unwrap().score, -30);
+    }
+
+    #[test]
+    fn forgets_disconnected_peers_in_good_standing() {
+        let mut manager = peer_manager(10, 20);
+        let peer_id = PeerId::random();
+        manager.connect(&peer_id).unwrap();
+        manager.disconnect(&peer_id);
+
+        assert!(manager.peer_stats(&peer_id).is_none());
+        assert!(manager.expire_bans().is_empty());
+    }
+
+    #[test]
+    fn remembers_the_faults_of_disconnected_peers() {
+        let mut manager = peer_manager(10, 20);
+        let peer_id = PeerId::random();
+        manager.connect(&peer_id).unwrap();
+        manager.report(&peer_id, PeerAction::RequestTimeout);
+        manager.disconnect(&peer_id);
+
+        assert_eq!(manager.peer_stats(&peer_id).unwrap().score, -10);
+
+        // Reconnecting does not reset the score.
+        manager.connect(&peer_id).unwrap();
+        manager.report(&peer_id, PeerAction::RequestTimeout);
+        assert_eq!(manager.peer_stats(&peer_id).unwrap().score, -20);
+    }
+
+    #[test]
+    fn scores_decay_towards_the_default() {
+        let mut manager = peer_manager(10, 20);
+        let faulty = PeerId::random();
+        let useful = PeerId::random();
+        let banned = PeerId::random();
+        for peer_id in &[&faulty, &useful, &banned] {
+            manager.connect(peer_id).unwrap();
+        }
+
+        manager.report(&faulty, PeerAction::RequestTimeout);
+        manager.report(&useful, PeerAction::UsefulResponse);
+        manager.report(&banned, PeerAction::InvalidBlock);
+        manager.disconnect(&faulty);
+
+        manager.decay(4);
+        assert_eq!(manager.peer_stats(&faulty).unwrap().score, -6);
+        assert_eq!(manager.peer_stats(&useful).unwrap().score, 1);
+        assert_eq!(manager.peer_stats(&banned).unwrap().score, -50);
+
+        // Once its faults have decayed, a disconnected peer is forgotten.
+        manager.decay(6);
+        assert!(manager.peer_stats(&faulty).is_none());
+        assert_eq!(manager.peer_stats(&useful).unwrap().score, 0);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::beacon_chain::BeaconChain;
 use crate::error;
 use crate::message_handler::{HandlerMessage, MessageHandler};
+use crate::peer_manager::{PeerManager, PeerStats};
 use crate::NetworkConfig;
+use beacon_chain::parking_lot::RwLock;
 use crossbeam_channel::{unbounded as channel, Sender, TryRecvError};
 use eth2_libp2p::Service as LibP2PService;
 use eth2_libp2p::{Libp2pEvent, PeerId};
@@ -19,6 +21,8 @@ pub struct Service {
     //libp2p_service: Arc<Mutex<LibP2PService>>,
     _libp2p_exit: oneshot::Sender<()>,
     network_send: crossbeam_channel::Sender<NetworkMessage>,
+    /// Scores, bans and limits the connected peers.
+    peer_manager: Arc<RwLock<PeerManager>>,
     //message_handler: MessageHandler,
     //message_handler_send: Sender<HandlerMessage>,
 }
@@ -32,11 +36,16 @@ impl Service {
     ) -> error::Result<(Arc<Self>, Sender<NetworkMessage>)> {
         // build the network channel
         let (network_send, network_recv) = channel::<NetworkMessage>();
+        let peer_manager = Arc::new(RwLock::new(PeerManager::new(
+            config,
+            log.new(o!("Service" => "PeerManager")),
+        )));
         // launch message handler thread
         let message_handler_log = log.new(o!("Service" => "MessageHandler"));
         let message_handler_send = MessageHandler::spawn(
             beacon_chain,
             network_send.clone(),
+            peer_manager.clone(),
             executor,
             message_handler_log,
         )?;
@@ -56,11 +65,22 @@ impl Service {
         let network_service = Service {
             _libp2p_exit: libp2p_exit,
             network_send: network_send.clone(),
+            peer_manager,
         };
 
         Ok((Arc::new(network_service), network_send))
     }
 
+    /// Returns the score and history of each connected or banned peer.
+    pub fn peer_stats(&self) -> Vec<(PeerId, PeerStats)> {
+        self.peer_manager.read().all_peer_stats()
+    }
+
+    /// The number of peers which are presently connected.
+    pub fn connected_peers(&self) -> usize {
+        self.peer_manager.read().connected_peers()
+    }
+
     // TODO: Testing only
     pub fn send_message(&self) {
         self.network_send
@@ -124,6 +144,23 @@ fn network_service(
                             .send(HandlerMessage::PeerDialed(peer_id))
                             .map_err(|_| "failed to send rpc to handler")?;
                     }
+                    Libp2pEvent::PeerAccepted(peer_id) => {
+                        debug!(log, "Peer Accepted: {:?}", peer_id);
+                        message_handler_send
+                            .send(HandlerMessage::PeerAccepted(peer_id))
+                            .map_err(|_| "failed to send peer connection to handler")?;
+                    }
+                    Libp2pEvent::PeerDisconnected(peer_id) => {
+                        debug!(log, "Peer Disconnected: {:?}", peer_id);
+                        message_handler_send
+                            .send(HandlerMessage::PeerDisconnected(peer_id))
+                            .map_err(|_| "failed to send peer disconnection to handler")?;
+                    }
+                    Libp2pEvent::InvalidGossip(peer_id) => {
+                        message_handler_send
+                            .send(HandlerMessage::InvalidGossip(peer_id))
+                            .map_err(|_| "failed to send invalid gossip to handler")?;
+                    }
                     Libp2pEvent::Identified(peer_id, info) => {
                         debug!(
                             log,
@@ -167,6 +204,18 @@ fn network_service(
                     debug!(log, "Sending pubsub message on topics {:?}", topics);
                     libp2p_service.swarm.publish(topics, *message);
                 }
+                Ok(NetworkMessage::Disconnect(peer_id)) => {
+                    debug!(log, "Disconnecting peer {:?}", peer_id);
+                    libp2p_service.disconnect_peer(peer_id);
+                }
+                Ok(NetworkMessage::Ban(peer_id)) => {
+                    info!(log, "Banning peer {:?}", peer_id);
+                    libp2p_service.ban_peer(peer_id);
+                }
+                Ok(NetworkMessage::Unban(peer_id)) => {
+                    debug!(log, "Unbanning peer {:?}", peer_id);
+                    libp2p_service.unban_peer(peer_id);
+                }
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => {
                     return Err(eth2_libp2p::error::Error::from(
@@ -190,6 +239,12 @@ pub enum NetworkMessage {
         topics: Vec<Topic>,
         message: Box<PubsubMessage>,
     },
+    /// Close the connection to a peer.
+    Disconnect(PeerId),
+    /// Close the connection to a peer and refuse further connections from it.
+    Ban(PeerId),
+    /// Allow a banned peer to connect again.
+    Unban(PeerId),
 }
 
 /// Type of outgoing messages that can be sent through the network service.
@@ -1,5 +1,4 @@
 use crate::beacon_chain::BeaconChain;
-use eth2_libp2p::rpc::methods::*;
 use eth2_libp2p::PeerId;
 use slog::{debug, error};
 use std::sync::Arc;
@@ -7,21 +6,13 @@ use std::time::{Duration, Instant};
 use tree_hash::TreeHash;
 use types::{BeaconBlock, BeaconBlockBody, BeaconBlockHeader, Hash256, Slot};
 
-/// Provides a queue for fully and partially built `BeaconBlock`s.
+/// Provides a queue for `BeaconBlock`s which could not yet be processed (e.g., gossiped blocks
+/// whose parent is unknown, or which are slightly ahead of our slot clock).
 ///
-/// The queue is fundamentally a `Vec<PartialBeaconBlock>` where no two items have the same
-/// `item.block_root`. This struct it backed by a `Vec` not a `HashMap` for the following two
-/// reasons:
-///
-/// - When we receive a `BeaconBlockBody`, the only way we can find it's matching
-/// `BeaconBlockHeader` is to find a header such that `header.beacon_block_body ==
-/// tree_hash_root(body)`. Therefore, if we used a `HashMap` we would need to use the root of
-/// `BeaconBlockBody` as the key.
-/// - It is possible for multiple distinct blocks to have identical `BeaconBlockBodies`. Therefore
-/// we cannot use a `HashMap` keyed by the root of `BeaconBlockBody`.
+/// Blocks downloaded during sync are imported by `RangeSync` and do not pass through this queue.
 pub struct ImportQueue {
     pub chain: Arc<BeaconChain>,
-    /// Partially imported blocks, keyed by the root of `BeaconBlockBody`.
+    /// Blocks awaiting import. No two items have the same `block_root`.
     pub partials: Vec<PartialBeaconBlock>,
     /// Time before a queue entry is considered state.
     pub stale_time: Duration,
@@ -113,139 +104,12 @@ impl ImportQueue {
             })
     }
 
-    /// Adds the `block_roots` to the partials queue.
-    ///
-    /// If a `block_root` is not in the queue and has not been processed by the chain it is added
-    /// to the queue and it's block root is included in the output.
-    pub fn enqueue_block_roots(
-        &mut self,
-        block_roots: &[BlockRootSlot],
-        sender: PeerId,
-    ) -> Vec<BlockRootSlot> {
-        let new_roots: Vec<BlockRootSlot> = block_roots
-            .iter()
-            // Ignore any roots already processed by the chain.
-            .filter(|brs| self.chain_has_not_seen_block(&brs.block_root))
-            // Ignore any roots already stored in the queue.
-            .filter(|brs| !self.partials.iter().any(|p| p.block_root == brs.block_root))
-            .cloned()
-            .collect();
-
-        new_roots.iter().for_each(|brs| {
-            self.partials.push(PartialBeaconBlock {
-                slot: brs.slot,
-                block_root: brs.block_root,
-                sender: sender.clone(),
-                header: None,
-                body: None,
-                inserted: Instant::now(),
-            })
-        });
-
-        new_roots
-    }
-
-    /// Adds the `headers` to the `partials` queue. Returns a list of `Hash256` block roots for
-    /// which we should use to request `BeaconBlockBodies`.
-    ///
-    /// If a `header` is not in the queue and has not been processed by the chain it is added to
-    /// the queue and it's block root is included in the output.
-    ///
-    /// If a `header` is already in the queue, but not yet processed by the chain the block root is
-    /// included in the output and the `inserted` time for the partial record is set to
-    /// `Instant::now()`. Updating the `inserted` time stops the partial from becoming stale.
-    ///
-    /// Presently the queue enforces that a `BeaconBlockHeader` _must_ be received before its
-    /// `BeaconBlockBody`. This is not a natural requirement and we could enhance the queue to lift
-    /// this restraint.
-    pub fn enqueue_headers(
-        &mut self,
-        headers: Vec<BeaconBlockHeader>,
-        sender: PeerId,
-    ) -> Vec<Hash256> {
-        let mut required_bodies: Vec<Hash256> = vec![];
-
-        for header in headers {
-            let block_root = Hash256::from_slice(&header.tree_hash_root()[..]);
-
-            if self.chain_has_not_seen_block(&block_root) {
-                self.insert_header(block_root, header, sender.clone());
-                required_bodies.push(block_root)
-            }
-        }
-
-        required_bodies
-    }
-
-    /// If there is a matching `header` for this `body`, adds it to the queue.
-    ///
-    /// If there is no `header` for the `body`, the body is simply discarded.
-    pub fn enqueue_bodies(&mut self, bodies: Vec<BeaconBlockBody>, sender: PeerId) {
-        for body in bodies {
-            self.insert_body(body, sender.clone());
-        }
-    }
-
     pub fn enqueue_full_blocks(&mut self, blocks: Vec<BeaconBlock>, sender: PeerId) {
         for block in blocks {
             self.insert_full_block(block, sender.clone());
         }
     }
 
-    /// Inserts a header to the queue.
-    ///
-    /// If the header already exists, the `inserted` time is set to `now` and not other
-    /// modifications are made.
-    fn insert_header(&mut self, block_root: Hash256, header: BeaconBlockHeader, sender: PeerId) {
-        if let Some(i) = self
-            .partials
-            .iter()
-            .position(|p| p.block_root == block_root)
-        {
-            // Case 1: there already exists a partial with a matching block root.
-            //
-            // The `inserted` time is set to now and the header is replaced, regardless of whether
-            // it existed or not.
-            self.partials[i].header = Some(header);
-            self.partials[i].inserted = Instant::now();
-        } else {
-            // Case 2: there was no partial with a matching block root.
-            //
-            // A new partial is added. This case permits adding a header without already known the
-            // root -- this is not possible in the wire protocol however we support it anyway.
-            self.partials.push(PartialBeaconBlock {
-                slot: header.slot,
-                block_root,
-                header: Some(header),
-                body: None,
-                inserted: Instant::now(),
-                sender,
-            })
-        }
-    }
-
-    /// Updates an existing partial with the `body`.
-    ///
-    /// If there is no header for the `body`, the body is simply discarded.
-    ///
-    /// If the body already existed, the `inserted` time is set to `now`.
-    fn insert_body(&mut self, body: BeaconBlockBody, sender: PeerId) {
-        let body_root = Hash256::from_slice(&body.tree_hash_root()[..]);
-
-        self.partials.iter_mut().for_each(|mut p| {
-            if let Some(header) = &mut p.header {
-                if body_root == header.block_body_root {
-                    p.inserted = Instant::now();
-
-                    if p.body.is_none() {
-                        p.body = Some(body.clone());
-                        p.sender = sender.clone();
-                    }
-                }
-            }
-        });
-    }
-
     /// Updates an existing `partial` with the completed block, or adds a new (complete) partial.
     ///
     /// If the partial already existed, the `inserted` time is set to `now`.
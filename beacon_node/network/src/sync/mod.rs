@@ -1,4 +1,5 @@
 mod import_queue;
+mod range_sync;
 /// Syncing for lighthouse.
 ///
 /// Stores the various syncing methods for the beacon chain.
@@ -0,0 +1,833 @@
+use crate::beacon_chain::BeaconChain;
+use crate::message_handler::NetworkContext;
+use crate::peer_manager::PeerAction;
+use eth2_libp2p::rpc::methods::BeaconBlocksByRangeRequest;
+use eth2_libp2p::rpc::{RPCRequest, RequestId};
+use eth2_libp2p::PeerId;
+use slog::{debug, error, info, warn};
+use std::cmp::{max, min};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use types::{BeaconBlock, Slot};
+
+/// The number of slots requested from a peer in a single `BeaconBlocksByRange` request.
+const BLOCKS_PER_BATCH: u64 = 64;
+
+/// The maximum number of batches which may be downloading or awaiting import at once. Limits the
+/// number of blocks held in memory.
+const MAX_PENDING_BATCHES: usize = 8;
+
+/// The number of times a batch may fail to download or import before sync is abandoned.
+const MAX_BATCH_ATTEMPTS: u8 = 5;
+
+/// The progress of a single batch.
+#[derive(Debug)]
+enum BatchState {
+    /// The batch is waiting for an idle peer to download it from.
+    AwaitingDownload,
+    /// The batch has been requested from the peer.
+    Downloading {
+        peer_id: PeerId,
+        request_id: RequestId,
+    },
+    /// The batch has been downloaded and is waiting for the preceding batches to be imported.
+    Downloaded {
+        blocks: Vec<BeaconBlock>,
+        peer_id: PeerId,
+    },
+}
+
+/// A contiguous range of slots which is downloaded with a single request.
+#[derive(Debug)]
+struct Batch {
+    start_slot: Slot,
+    count: u64,
+    state: BatchState,
+    /// The number of times the batch has failed to download or import.
+    attempts: u8,
+    /// The peer which most recently failed to provide the batch, which is avoided when the batch
+    /// is requested again.
+    failed_peer: Option<PeerId>,
+}
+
+impl Batch {
+    fn new(start_slot: Slot, count: u64) -> Self {
+        Self {
+            start_slot,
+            count,
+            state: BatchState::AwaitingDownload,
+            attempts: 0,
+            failed_peer: None,
+        }
+    }
+
+    /// The slot after the last slot in the batch.
+    fn end_slot(&self) -> Slot {
+        self.start_slot + self.count
+    }
+
+    fn is_downloading_from(&self, peer_id: &PeerId) -> bool {
+        match &self.state {
+            BatchState::Downloading {
+                peer_id: batch_peer_id,
+                ..
+            } => batch_peer_id == peer_id,
+            _ => false,
+        }
+    }
+
+    fn is_request(&self, peer_id: &PeerId, request_id: &RequestId) -> bool {
+        match &self.state {
+            BatchState::Downloading {
+                peer_id: batch_peer_id,
+                request_id: batch_request_id,
+            } => batch_peer_id == peer_id && batch_request_id == request_id,
+            _ => false,
+        }
+    }
+
+    /// Returns the batch to the download queue after the peer failed to provide it.
+    fn failed(&mut self, peer_id: PeerId) {
+        self.state = BatchState::AwaitingDownload;
+        self.attempts += 1;
+        self.failed_peer = Some(peer_id);
+    }
+
+    /// Returns `true` if each block is within the batch and has a higher slot than the block
+    /// before it.
+    fn contains_ascending(&self, blocks: &[BeaconBlock]) -> bool {
+        let in_range = blocks
+            .iter()
+            .all(|block| block.slot >= self.start_slot && block.slot < self.end_slot());
+        let ascending = blocks.windows(2).all(|pair| pair[0].slot < pair[1].slot);
+
+        in_range && ascending
+    }
+}
+
+/// Downloads the blocks between our head and the best slot of our peers.
+///
+/// The range of slots is split into batches of `BLOCKS_PER_BATCH` slots, which are requested from
+/// multiple peers in parallel. Batches may be downloaded in any order, but are always imported in
+/// ascending slot order. A batch which fails to download, does not descend from the batch before
+/// it, or contains an invalid block, is requested again, preferably from another peer.
+///
+/// A batch may be empty if all of its slots were skipped, so an empty batch is only requested
+/// again if the first batch with blocks after it does not descend from the chain.
+pub struct RangeSync {
+    /// A reference to the underlying beacon chain.
+    chain: Arc<BeaconChain>,
+    /// The best slot of each peer we may download from.
+    peers: HashMap<PeerId, Slot>,
+    /// The number of slots in each batch.
+    blocks_per_batch: u64,
+    /// Batches which are being downloaded or awaiting import, in ascending slot order.
+    batches: VecDeque<Batch>,
+    /// The empty batches imported since the last batch with blocks, with the peers which
+    /// provided them. They are requested again if the next batch with blocks does not descend
+    /// from the chain.
+    empty_batches: Vec<(Batch, PeerId)>,
+    /// The start slot of the next batch to be created.
+    next_batch_start: Slot,
+    /// The highest slot to download.
+    target_slot: Slot,
+    /// Sync logger.
+    log: slog::Logger,
+}
+
+impl RangeSync {
+    pub fn new(chain: Arc<BeaconChain>, log: slog::Logger) -> Self {
+        let best_slot = chain.best_slot();
+        Self {
+            chain,
+            peers: HashMap::new(),
+            blocks_per_batch: BLOCKS_PER_BATCH,
+            batches: VecDeque::new(),
+            empty_batches: vec![],
+            next_batch_start: best_slot + 1,
+            target_slot: best_slot,
+            log,
+        }
+    }
+
+    /// Returns `true` if there are batches being downloaded or awaiting import.
+    pub fn is_syncing(&self) -> bool {
+        !self.batches.is_empty()
+    }
+
+    /// Adds (or updates) a peer which has blocks beyond our head, and begins downloading them.
+    pub fn add_peer(&mut self, peer_id: PeerId, best_slot: Slot, network: &mut NetworkContext) {
+        self.peers.insert(peer_id, best_slot);
+
+        if !self.is_syncing() {
+            // Blocks may have been imported from gossip since sync last ran.
+            self.next_batch_start = max(self.next_batch_start, self.chain.best_slot() + 1);
+        }
+
+        if best_slot > self.target_slot {
+            info!(
+                self.log, "RangeSync";
+                "msg" => "new sync target",
+                "target_slot" => best_slot,
+                "local_slot" => self.chain.best_slot(),
+            );
+            self.target_slot = best_slot;
+        }
+
+        self.request_batches(network);
+    }
+
+    /// Stops downloading from the peer, returning its batches to the download queue.
+    pub fn remove_peer(&mut self, peer_id: &PeerId, network: &mut NetworkContext) {
+        if self.peers.remove(peer_id).is_none() {
+            return;
+        }
+
+        for batch in self.batches.iter_mut() {
+            if batch.is_downloading_from(peer_id) {
+                batch.state = BatchState::AwaitingDownload;
+            }
+        }
+
+        self.request_batches(network);
+    }
+
+    /// Handles the blocks received in response to a batch request, importing all batches which
+    /// are ready.
+    pub fn on_blocks_by_range_response(
+        &mut self,
+        peer_id: PeerId,
+        request_id: RequestId,
+        blocks: Vec<BeaconBlock>,
+        network: &mut NetworkContext,
+    ) {
+        let index = match self
+            .batches
+            .iter()
+            .position(|batch| batch.is_request(&peer_id, &request_id))
+        {
+            Some(index) => index,
+            None => {
+                debug!(
+                    self.log, "RangeSync";
+                    "msg" => "response for unknown batch",
+                    "peer" => format!("{:?}", peer_id),
+                );
+                return;
+            }
+        };
+
+        let batch = &self.batches[index];
+        debug!(
+            self.log, "RangeSync";
+            "msg" => "batch downloaded",
+            "start_slot" => batch.start_slot,
+            "blocks" => blocks.len(),
+            "peer" => format!("{:?}", peer_id),
+        );
+
+        let fault = if !batch.contains_ascending(&blocks) {
+            Some("peer returned blocks outside of the batch or out of order")
+        } else if self.descends_from_previous_batch(index, &blocks) == Some(false) {
+            Some("peer returned blocks which do not descend from the previous batch")
+        } else {
+            None
+        };
+
+        match fault {
+            None => self.batches[index].state = BatchState::Downloaded { blocks, peer_id },
+            Some(msg) => {
+                warn!(
+                    self.log, "RangeSync";
+                    "msg" => msg,
+                    "start_slot" => self.batches[index].start_slot,
+                    "peer" => format!("{:?}", peer_id),
+                );
+                network.report_peer(peer_id.clone(), PeerAction::InvalidMessage);
+                self.batch_failed(index, peer_id);
+            }
+        }
+
+        self.import_batches(network);
+        self.request_batches(network);
+    }
+
+    /// Handles a batch request which failed, either because the peer did not respond in time or
+    /// responded with an error. The batch is requested again.
+    pub fn on_request_failure(
+        &mut self,
+        peer_id: PeerId,
+        request_id: RequestId,
+        network: &mut NetworkContext,
+    ) {
+        if let Some(index) = self
+            .batches
+            .iter()
+            .position(|batch| batch.is_request(&peer_id, &request_id))
+        {
+            debug!(
+                self.log, "RangeSync";
+                "msg" => "batch request failed",
+                "start_slot" => self.batches[index].start_slot,
+                "peer" => format!("{:?}", peer_id),
+            );
+            self.batch_failed(index, peer_id);
+        }
+
+        self.request_batches(network);
+    }
+
+    /// Returns `Some(true)` if the first of `blocks`, which were downloaded for the batch at
+    /// `index`, descends from the blocks before the batch.
+    ///
+    /// The parent of the first block must either be known to the chain or be the last block of
+    /// the preceding batch. Returns `None` if it cannot yet be checked, because the preceding
+    /// batch has not been downloaded or is empty (in which case it is checked on import).
+    fn descends_from_previous_batch(&self, index: usize, blocks: &[BeaconBlock]) -> Option<bool> {
+        let parent_root = match blocks.first() {
+            Some(block) => block.previous_block_root,
+            None => return Some(true),
+        };
+
+        if let Ok(false) = self.chain.is_new_block_root(&parent_root) {
+            return Some(true);
+        }
+
+        let previous = index.checked_sub(1).and_then(|i| self.batches.get(i));
+        match previous.map(|batch| &batch.state) {
+            Some(BatchState::Downloaded { blocks, .. }) => blocks
+                .last()
+                .map(|last| last.canonical_root() == parent_root),
+            Some(_) => None,
+            // An empty batch imported before this one may have withheld the parent.
+            None if !self.empty_batches.is_empty() => None,
+            None => Some(false),
+        }
+    }
+
+    /// Imports the downloaded batches at the front of the queue, stopping at the first batch
+    /// which has not been downloaded.
+    fn import_batches(&mut self, network: &mut NetworkContext) {
+        let mut imported = 0;
+        let mut popped = false;
+
+        while let Some(batch) = self.batches.front_mut() {
+            let (blocks, peer_id) =
+                match std::mem::replace(&mut batch.state, BatchState::AwaitingDownload) {
+                    BatchState::Downloaded { blocks, peer_id } => (blocks, peer_id),
+                    state => {
+                        batch.state = state;
+                        break;
+                    }
+                };
+
+            if blocks.is_empty() {
+                // Whether the batch withheld blocks is only known once the next batch with
+                // blocks is imported.
+                if let Some(batch) = self.batches.pop_front() {
+                    self.empty_batches.push((batch, peer_id));
+                }
+                popped = true;
+                continue;
+            }
+
+            // The batch may have been accepted before the batch preceding it was downloaded, so
+            // the parent of its first block is checked again now that the preceding batches are
+            // imported.
+            let unknown_parent = self
+                .chain
+                .is_new_block_root(&blocks[0].previous_block_root)
+                .unwrap_or(false);
+            if unknown_parent {
+                // An empty batch before this one may have withheld the parent. Each is downloaded
+                // again once, from another peer if possible, before this batch is blamed.
+                let retry_empty = !self.empty_batches.is_empty()
+                    && self
+                        .empty_batches
+                        .iter()
+                        .all(|(empty, _)| empty.attempts == 0);
+                if retry_empty {
+                    // The batch is kept until the empty batches before it are downloaded again.
+                    batch.state = BatchState::Downloaded { blocks, peer_id };
+                    self.retry_empty_batches(network);
+                    break;
+                }
+
+                warn!(
+                    self.log, "RangeSync";
+                    "msg" => "peer returned blocks which do not descend from the previous batch",
+                    "start_slot" => batch.start_slot,
+                    "peer" => format!("{:?}", peer_id),
+                );
+                network.report_peer(peer_id.clone(), PeerAction::InvalidMessage);
+                self.batch_failed(0, peer_id);
+                break;
+            }
+
+            let mut failed = false;
+            for block in blocks {
+                match self.chain.process_block(block) {
+                    Ok(outcome) => {
+                        if outcome.sucessfully_processed() {
+                            imported += 1;
+                            continue;
+                        }
+
+                        warn!(
+                            self.log, "RangeSync";
+                            "msg" => "unable to import block",
+                            "outcome" => format!("{:?}", outcome),
+                            "peer" => format!("{:?}", peer_id),
+                        );
+                        if outcome.is_invalid() {
+                            network.report_peer(peer_id.clone(), PeerAction::InvalidBlock);
+                        }
+                    }
+                    Err(e) => {
+                        error!(self.log, "BlockProcessingError"; "error" => format!("{:?}", e));
+                    }
+                }
+                failed = true;
+                break;
+            }
+
+            if failed {
+                // Blocks from the batch which were imported are skipped as already known when the
+                // batch is downloaded again.
+                self.batch_failed(0, peer_id);
+                break;
+            }
+
+            // The batch was imported, so the response which provided it was useful, as were the
+            // empty batches before it.
+            for (_, empty_peer_id) in self.empty_batches.drain(..) {
+                network.report_peer(empty_peer_id, PeerAction::UsefulResponse);
+            }
+            network.report_peer(peer_id, PeerAction::UsefulResponse);
+
+            self.batches.pop_front();
+            popped = true;
+        }
+
+        if popped && !self.is_syncing() {
+            // There is no later batch to contradict the empty batches.
+            self.empty_batches.clear();
+            if self.next_batch_start > self.target_slot {
+                info!(self.log, "RangeSync"; "msg" => "sync complete", "target_slot" => self.target_slot);
+            }
+        }
+
+        if imported > 0 {
+            info!(
+                self.log, "RangeSync";
+                "msg" => format!("imported {} blocks", imported),
+                "local_slot" => self.chain.best_slot(),
+                "target_slot" => self.target_slot,
+            );
+        }
+    }
+
+    /// Returns the empty batches which were imported since the last batch with blocks to the
+    /// front of the queue, as the batch after them does not descend from the chain.
+    fn retry_empty_batches(&mut self, network: &mut NetworkContext) {
+        while let Some((mut batch, peer_id)) = self.empty_batches.pop() {
+            warn!(
+                self.log, "RangeSync";
+                "msg" => "peer returned an empty batch which the next batch does not descend from",
+                "start_slot" => batch.start_slot,
+                "peer" => format!("{:?}", peer_id),
+            );
+            network.report_peer(peer_id.clone(), PeerAction::InvalidMessage);
+            batch.failed(peer_id);
+            self.batches.push_front(batch);
+            if self.batches[0].attempts >= MAX_BATCH_ATTEMPTS {
+                self.abandon(0);
+                return;
+            }
+        }
+    }
+
+    /// Returns the batch at `index` to the download queue after the peer failed to provide it,
+    /// abandoning sync if the batch has failed too many times.
+    fn batch_failed(&mut self, index: usize, peer_id: PeerId) {
+        self.batches[index].failed(peer_id);
+        if self.batches[index].attempts >= MAX_BATCH_ATTEMPTS {
+            self.abandon(index);
+        }
+    }
+
+    /// Abandons sync because the batch at `index` failed too many times.
+    fn abandon(&mut self, index: usize) {
+        warn!(
+            self.log, "RangeSync";
+            "msg" => "abandoning sync, batch failed too many times",
+            "start_slot" => self.batches[index].start_slot,
+        );
+        self.reset();
+    }
+
+    /// Creates batches up to the target slot, and requests each batch awaiting download from an
+    /// idle peer.
+    fn request_batches(&mut self, network: &mut NetworkContext) {
+        while self.batches.len() < MAX_PENDING_BATCHES && self.next_batch_start <= self.target_slot
+        {
+            let count = min(
+                self.blocks_per_batch,
+                (self.target_slot - self.next_batch_start).as_u64() + 1,
+            );
+            self.batches
+                .push_back(Batch::new(self.next_batch_start, count));
+            self.next_batch_start += count;
+        }
+
+        for i in 0..self.batches.len() {
+            if let BatchState::AwaitingDownload = self.batches[i].state {
+                let peer_id = match self.idle_peer_for(&self.batches[i]) {
+                    Some(peer_id) => peer_id,
+                    None => continue,
+                };
+
+                let batch = &mut self.batches[i];
+                debug!(
+                    self.log, "RPCRequest(BeaconBlocksByRange)";
+                    "start_slot" => batch.start_slot,
+                    "count" => batch.count,
+                    "peer" => format!("{:?}", peer_id),
+                );
+                let request_id = network.send_rpc_request(
+                    peer_id.clone(),
+                    RPCRequest::BeaconBlocksByRange(BeaconBlocksByRangeRequest {
+                        start_slot: batch.start_slot,
+                        count: batch.count,
+                    }),
+                );
+                batch.state = BatchState::Downloading {
+                    peer_id,
+                    request_id,
+                };
+            }
+        }
+    }
+
+    /// Returns a peer which has the start of the batch and is not downloading another batch,
+    /// preferring a peer which has not failed to provide the batch.
+    fn idle_peer_for(&self, batch: &Batch) -> Option<PeerId> {
+        let idle_peers = self
+            .peers
+            .iter()
+            .filter(|(_, best_slot)| **best_slot >= batch.start_slot)
+            .map(|(peer_id, _)| peer_id)
+            .filter(|peer_id| !self.batches.iter().any(|b| b.is_downloading_from(peer_id)));
+
+        let mut fallback = None;
+        for peer_id in idle_peers {
+            if batch.failed_peer.as_ref() == Some(peer_id) {
+                fallback = Some(peer_id.clone());
+            } else {
+                return Some(peer_id.clone());
+            }
+        }
+
+        fallback
+    }
+
+    /// Drops all batches, stopping sync until a peer is added.
+    fn reset(&mut self) {
+        self.batches.clear();
+        self.empty_batches.clear();
+        self.next_batch_start = self.chain.best_slot() + 1;
+        self.target_slot = self.chain.best_slot();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer_manager::PeerManager;
+    use crate::service::{NetworkMessage, OutgoingMessage};
+    use crate::NetworkConfig;
+    use beacon_chain::parking_lot::RwLock;
+    use crossbeam_channel::{unbounded, Receiver};
+    use eth2_libp2p::RPCEvent;
+    use slog::{o, Discard, Logger};
+    use test_harness::BeaconChainHarness;
+    use types::{test_utils::TestingBeaconStateBuilder, ChainSpec, Hash256};
+
+    const VALIDATOR_COUNT: usize = 8;
+
+    /// A node syncing from the chain of `remote` with two peers, `peers[0]` and `peers[1]`, which
+    /// both serve `remote`.
+    struct Rig {
+        sync: RangeSync,
+        network: NetworkContext,
+        network_recv: Receiver<NetworkMessage>,
+        peer_manager: Arc<RwLock<PeerManager>>,
+        local: BeaconChainHarness,
+        remote: BeaconChainHarness,
+        peers: Vec<PeerId>,
+    }
+
+    impl Rig {
+        /// Builds `blocks` blocks on the remote chain, which is downloaded in batches of
+        /// `blocks_per_batch` slots.
+        fn new(blocks: usize, blocks_per_batch: u64) -> Self {
+            Self::with_slots(&vec![true; blocks], blocks_per_batch)
+        }
+
+        /// Builds the remote chain with a block in each slot where `slots` is `true`, skipping
+        /// the other slots.
+        fn with_slots(slots: &[bool], blocks_per_batch: u64) -> Self {
+            let spec = ChainSpec::few_validators();
+            let state_builder = TestingBeaconStateBuilder::from_default_keypairs_file_if_exists(
+                VALIDATOR_COUNT,
+                &spec,
+            );
+            let mut local =
+                BeaconChainHarness::from_beacon_state_builder(state_builder.clone(), spec.clone());
+            let mut remote = BeaconChainHarness::from_beacon_state_builder(state_builder, spec);
+
+            for has_block in slots {
+                if *has_block {
+                    remote.advance_chain_with_block();
+                } else {
+                    remote.increment_beacon_chain_slot();
+                }
+                local.increment_beacon_chain_slot();
+            }
+            remote.run_fork_choice();
+
+            let log = Logger::root(Discard, o!());
+            let (network_send, network_recv) = unbounded();
+            let peer_manager = Arc::new(RwLock::new(PeerManager::new(
+                &NetworkConfig::default(),
+                log.clone(),
+            )));
+            let network = NetworkContext::new(network_send, peer_manager.clone(), log.clone());
+
+            let peers = vec![PeerId::random(), PeerId::random()];
+            for peer_id in &peers {
+                assert!(peer_manager.write().connect(peer_id).is_ok());
+            }
+
+            let mut sync = RangeSync::new(local.beacon_chain.clone(), log);
+            sync.blocks_per_batch = blocks_per_batch;
+
+            Self {
+                sync,
+                network,
+                network_recv,
+                peer_manager,
+                local,
+                remote,
+                peers,
+            }
+        }
+
+        /// Adds the peer with the best slot of the remote chain.
+        fn add_peer(&mut self, peer: usize) {
+            let best_slot = self.remote.beacon_chain.best_slot();
+            self.sync
+                .add_peer(self.peers[peer].clone(), best_slot, &mut self.network);
+        }
+
+        /// Returns the next request sent by the node, along with the index of the peer it was
+        /// sent to.
+        fn next_request(&self) -> (usize, RequestId, BeaconBlocksByRangeRequest) {
+            match self.network_recv.try_recv().expect("no request was sent") {
+                NetworkMessage::Send(
+                    peer_id,
+                    OutgoingMessage::RPC(RPCEvent::Request {
+                        id,
+                        body: RPCRequest::BeaconBlocksByRange(request),
+                        ..
+                    }),
+                ) => {
+                    let peer = self.peers.iter().position(|p| *p == peer_id).unwrap();
+                    (peer, id, request)
+                }
+                other => panic!("expected a blocks by range request, got {:?}", other),
+            }
+        }
+
+        fn assert_no_requests(&self) {
+            assert!(self.network_recv.try_recv().is_err());
+        }
+
+        /// The blocks of the remote chain which answer the request.
+        fn blocks_for(&self, request: &BeaconBlocksByRangeRequest) -> Vec<BeaconBlock> {
+            self.remote
+                .beacon_chain
+                .get_blocks_by_range(request.start_slot, request.count)
+                .unwrap()
+        }
+
+        fn respond(&mut self, peer: usize, id: RequestId, blocks: Vec<BeaconBlock>) {
+            self.sync.on_blocks_by_range_response(
+                self.peers[peer].clone(),
+                id,
+                blocks,
+                &mut self.network,
+            );
+        }
+
+        fn has_block(&self, block_root: Hash256) -> bool {
+            !self
+                .local
+                .beacon_chain
+                .is_new_block_root(&block_root)
+                .unwrap()
+        }
+
+        fn is_synced(&self) -> bool {
+            self.has_block(self.remote.beacon_chain.best_block_root())
+        }
+
+        fn invalid_messages(&self, peer: usize) -> u64 {
+            self.peer_manager
+                .read()
+                .peer_stats(&self.peers[peer])
+                .map_or(0, |stats| stats.invalid_messages)
+        }
+    }
+
+    #[test]
+    fn imports_batches_in_order_when_downloaded_out_of_order() {
+        let mut rig = Rig::new(6, 2);
+        let start_slot = rig.local.beacon_chain.best_slot() + 1;
+
+        rig.add_peer(0);
+        let (peer_a, id_a, first) = rig.next_request();
+        assert_eq!(peer_a, 0);
+        assert_eq!(first.start_slot, start_slot);
+        assert_eq!(first.count, 2);
+
+        rig.add_peer(1);
+        let (peer_b, id_b, second) = rig.next_request();
+        assert_eq!(peer_b, 1);
+        assert_eq!(second.start_slot, start_slot + 2);
+        rig.assert_no_requests();
+
+        // The second batch cannot be imported before the first.
+        let second_blocks = rig.blocks_for(&second);
+        let second_root = second_blocks[0].canonical_root();
+        rig.respond(peer_b, id_b, second_blocks);
+        assert!(!rig.has_block(second_root));
+
+        // The peer which provided the second batch is given the third.
+        let (peer_b, id_c, third) = rig.next_request();
+        assert_eq!(peer_b, 1);
+        assert_eq!(third.start_slot, start_slot + 4);
+
+        let first_blocks = rig.blocks_for(&first);
+        rig.respond(peer_a, id_a, first_blocks);
+        assert!(rig.has_block(second_root));
+        assert!(!rig.is_synced());
+
+        let third_blocks = rig.blocks_for(&third);
+        rig.respond(peer_b, id_c, third_blocks);
+        assert!(rig.is_synced());
+        assert!(!rig.sync.is_syncing());
+        assert_eq!(rig.invalid_messages(0), 0);
+        assert_eq!(rig.invalid_messages(1), 0);
+    }
+
+    #[test]
+    fn accepts_an_empty_batch_of_skipped_slots() {
+        let mut rig = Rig::with_slots(&[true, true, false, false, true, true], 2);
+
+        rig.add_peer(0);
+        let (peer_a, id_a, first) = rig.next_request();
+        rig.add_peer(1);
+        let (peer_b, id_b, second) = rig.next_request();
+
+        let second_blocks = rig.blocks_for(&second);
+        assert!(second_blocks.is_empty());
+        rig.respond(peer_b, id_b, second_blocks);
+        let (peer_b, id_c, third) = rig.next_request();
+
+        let first_blocks = rig.blocks_for(&first);
+        rig.respond(peer_a, id_a, first_blocks);
+        let third_blocks = rig.blocks_for(&third);
+        rig.respond(peer_b, id_c, third_blocks);
+
+        assert!(rig.is_synced());
+        assert!(!rig.sync.is_syncing());
+        rig.assert_no_requests();
+        assert_eq!(rig.invalid_messages(0), 0);
+        assert_eq!(rig.invalid_messages(1), 0);
+    }
+
+    #[test]
+    fn requests_a_withheld_batch_from_another_peer() {
+        let mut rig = Rig::new(4, 2);
+
+        rig.add_peer(0);
+        let (peer_a, id_a, first) = rig.next_request();
+        rig.add_peer(1);
+        let (peer_b, id_b, second) = rig.next_request();
+
+        let second_blocks = rig.blocks_for(&second);
+        rig.respond(peer_b, id_b, second_blocks);
+        rig.assert_no_requests();
+
+        // The empty batch is accepted, but the next batch does not descend from the chain
+        // without it, so it is requested again.
+        rig.respond(peer_a, id_a, vec![]);
+        assert_eq!(rig.invalid_messages(peer_a), 1);
+        assert_eq!(rig.invalid_messages(peer_b), 0);
+
+        let (peer, id, retry) = rig.next_request();
+        assert_eq!(peer, peer_b);
+        assert_eq!(retry.start_slot, first.start_slot);
+
+        let first_blocks = rig.blocks_for(&retry);
+        rig.respond(peer, id, first_blocks);
+        assert!(rig.is_synced());
+        assert!(!rig.sync.is_syncing());
+    }
+
+    #[test]
+    fn rejects_a_batch_which_does_not_descend_from_the_previous_batch() {
+        let mut rig = Rig::new(4, 2);
+
+        rig.add_peer(0);
+        let (peer_a, id_a, first) = rig.next_request();
+        rig.add_peer(1);
+        let (peer_b, id_b, second) = rig.next_request();
+
+        let first_blocks = rig.blocks_for(&first);
+        rig.respond(peer_a, id_a, first_blocks);
+        rig.assert_no_requests();
+
+        let mut second_blocks = rig.blocks_for(&second);
+        second_blocks[0].previous_block_root = Hash256::from([42; 32]);
+        rig.respond(peer_b, id_b, second_blocks);
+        assert_eq!(rig.invalid_messages(peer_b), 1);
+
+        // The batch is requested again, from the other peer.
+        let (peer, id, retry) = rig.next_request();
+        assert_eq!(peer, peer_a);
+        assert_eq!(retry.start_slot, second.start_slot);
+
+        let second_blocks = rig.blocks_for(&retry);
+        rig.respond(peer, id, second_blocks);
+        assert!(rig.is_synced());
+        assert!(!rig.sync.is_syncing());
+    }
+
+    #[test]
+    fn abandons_a_batch_which_fails_too_often() {
+        let mut rig = Rig::new(2, 2);
+
+        rig.add_peer(0);
+        for _ in 0..MAX_BATCH_ATTEMPTS {
+            let (peer, id, request) = rig.next_request();
+            assert_eq!(request.count, 2);
+            rig.sync
+                .on_request_failure(rig.peers[peer].clone(), id, &mut rig.network);
+        }
+
+        assert!(!rig.sync.is_syncing());
+        rig.assert_no_requests();
+    }
+}
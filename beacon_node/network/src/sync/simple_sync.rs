@@ -1,8 +1,10 @@
 use super::import_queue::ImportQueue;
+use super::range_sync::RangeSync;
 use crate::beacon_chain::{BeaconChain, BlockProcessingOutcome, InvalidBlock};
 use crate::message_handler::NetworkContext;
+use crate::peer_manager::PeerAction;
 use eth2_libp2p::rpc::methods::*;
-use eth2_libp2p::rpc::{RPCRequest, RPCResponse, RequestId};
+use eth2_libp2p::rpc::{RPCErrorCode, RPCMethod, RPCRequest, RPCResponse, RequestId};
 use eth2_libp2p::PeerId;
 use slog::{debug, error, info, o, warn};
 use std::collections::HashMap;
@@ -17,6 +19,9 @@ const SLOT_IMPORT_TOLERANCE: u64 = 100;
 /// The amount of seconds a block (or partial block) may exist in the import queue.
 const QUEUE_STALE_SECS: u64 = 600;
 
+/// The maximum number of slots a peer may request in a single `BeaconBlocksByRange` request.
+const MAX_BLOCKS_BY_RANGE_COUNT: u64 = 1024;
+
 /// If a block is more than `FUTURE_SLOT_TOLERANCE` slots ahead of our slot clock, we drop it.
 /// Otherwise we queue it.
 const FUTURE_SLOT_TOLERANCE: u64 = 1;
@@ -110,6 +115,8 @@ pub struct SimpleSync {
     known_peers: HashMap<PeerId, PeerSyncInfo>,
     /// A queue to allow importing of blocks
     import_queue: ImportQueue,
+    /// Downloads blocks from peers which are ahead of us.
+    range_sync: RangeSync,
     /// The current state of the syncing protocol.
     state: SyncState,
     /// Sync logger.
@@ -129,6 +136,7 @@ impl SimpleSync {
             chain: beacon_chain.clone(),
             known_peers: HashMap::new(),
             import_queue,
+            range_sync: RangeSync::new(beacon_chain.clone(), sync_logger.clone()),
             state: SyncState::Idle,
             log: sync_logger,
         }
@@ -147,6 +155,14 @@ impl SimpleSync {
         self.known_peers.remove(&peer_id);
     }
 
+    /// Handle the disconnection of a peer.
+    ///
+    /// Removes the peer from `known_peers` and stops downloading blocks from it.
+    pub fn on_disconnect(&mut self, peer_id: &PeerId, network: &mut NetworkContext) {
+        self.known_peers.remove(peer_id);
+        self.range_sync.remove_peer(peer_id, network);
+    }
+
     /// Handle the connection of a new peer.
     ///
     /// Sends a `Hello` message to the peer.
@@ -188,7 +204,9 @@ impl SimpleSync {
         debug!(self.log, "HelloResponse"; "peer" => format!("{:?}", peer_id));
 
         // Process the hello message, without sending back another hello.
-        self.process_hello(peer_id, hello, network);
+        if self.process_hello(peer_id.clone(), hello, network) {
+            network.report_peer(peer_id, PeerAction::UsefulResponse);
+        }
     }
 
     /// Returns a `PeerStatus` for some peer.
@@ -238,20 +256,19 @@ impl SimpleSync {
 
     /// Process a `Hello` message, requesting new blocks if appropriate.
     ///
-    /// Disconnects the peer if required.
+    /// Disconnects the peer if required, returning `true` if the handshake succeeded.
     fn process_hello(
         &mut self,
         peer_id: PeerId,
         hello: HelloMessage,
         network: &mut NetworkContext,
-    ) {
-        let spec = self.chain.get_spec();
-
+    ) -> bool {
         let remote = PeerSyncInfo::from(hello);
         let local = PeerSyncInfo::from(&self.chain);
         let remote_status = self.peer_status(remote);
+        let handshake = remote_status.should_handshake();
 
-        if remote_status.should_handshake() {
+        if handshake {
             info!(self.log, "HandshakeSuccess"; "peer" => format!("{:?}", peer_id));
             self.known_peers.insert(peer_id.clone(), remote);
         } else {
@@ -263,39 +280,23 @@ impl SimpleSync {
             network.disconnect(peer_id.clone(), GoodbyeReason::IrreleventNetwork);
         }
 
-        // If required, send additional requests.
+        // If required, download the blocks we are missing.
         match remote_status {
-            PeerStatus::HigherFinalizedEpoch => {
-                let start_slot = remote
-                    .latest_finalized_epoch
-                    .start_slot(spec.slots_per_epoch);
-                let required_slots = start_slot - local.best_slot;
-
-                self.request_block_roots(
-                    peer_id,
-                    BeaconBlockRootsRequest {
-                        start_slot,
-                        count: required_slots.into(),
-                    },
-                    network,
-                );
-            }
-            PeerStatus::HigherBestSlot => {
+            PeerStatus::HigherFinalizedEpoch | PeerStatus::HigherBestSlot => {
                 let required_slots = remote.best_slot - local.best_slot;
+                if self.state == SyncState::Idle && required_slots > SLOT_IMPORT_TOLERANCE {
+                    debug!(self.log, "Entering downloading sync state.");
+                    self.state = SyncState::Downloading;
+                }
 
-                self.request_block_roots(
-                    peer_id,
-                    BeaconBlockRootsRequest {
-                        start_slot: local.best_slot + 1,
-                        count: required_slots.into(),
-                    },
-                    network,
-                );
+                self.range_sync.add_peer(peer_id, remote.best_slot, network);
             }
             PeerStatus::FinalizedEpochNotInChain => {}
             PeerStatus::DifferentNetworkId => {}
             PeerStatus::NotInteresting => {}
         }
+
+        handshake
     }
 
     /// Handle a `BeaconBlockRoots` request from the peer.
@@ -319,13 +320,19 @@ impl SimpleSync {
         {
             Ok(roots) => roots,
             Err(e) => {
-                // TODO: return RPC error.
                 warn!(
                     self.log,
                     "RPCRequest"; "peer" => format!("{:?}", peer_id),
                     "req" => "BeaconBlockRoots",
                     "error" => format!("{:?}", e)
                 );
+                network.send_rpc_error_response(
+                    peer_id,
+                    request_id,
+                    RPCMethod::BeaconBlockRoots,
+                    RPCErrorCode::ServerError,
+                    format!("Unable to read block roots: {:?}", e),
+                );
                 return;
             }
         };
@@ -346,69 +353,6 @@ impl SimpleSync {
         )
     }
 
-    /// Handle a `BeaconBlockRoots` response from the peer.
-    pub fn on_beacon_block_roots_response(
-        &mut self,
-        peer_id: PeerId,
-        res: BeaconBlockRootsResponse,
-        network: &mut NetworkContext,
-    ) {
-        debug!(
-            self.log,
-            "BlockRootsResponse";
-            "peer" => format!("{:?}", peer_id),
-            "count" => res.roots.len(),
-        );
-
-        if res.roots.is_empty() {
-            warn!(
-                self.log,
-                "Peer returned empty block roots response";
-                "peer_id" => format!("{:?}", peer_id)
-            );
-            return;
-        }
-
-        // The wire protocol specifies that slots must be in ascending order.
-        if !res.slots_are_ascending() {
-            warn!(
-                self.log,
-                "Peer returned block roots response with bad slot ordering";
-                "peer_id" => format!("{:?}", peer_id)
-            );
-            return;
-        }
-
-        let new_roots = self
-            .import_queue
-            .enqueue_block_roots(&res.roots, peer_id.clone());
-
-        // No new roots means nothing to do.
-        //
-        // This check protects against future panics.
-        if new_roots.is_empty() {
-            return;
-        }
-
-        // Determine the first (earliest) and last (latest) `BlockRootSlot` items.
-        //
-        // This logic relies upon slots to be in ascending order, which is enforced earlier.
-        let first = new_roots.first().expect("Non-empty list must have first");
-        let last = new_roots.last().expect("Non-empty list must have last");
-
-        // Request all headers between the earliest and latest new `BlockRootSlot` items.
-        self.request_block_headers(
-            peer_id,
-            BeaconBlockHeadersRequest {
-                start_root: first.block_root,
-                start_slot: first.slot,
-                max_headers: (last.slot - first.slot + 1).as_u64(),
-                skip_slots: 0,
-            },
-            network,
-        )
-    }
-
     /// Handle a `BeaconBlockHeaders` request from the peer.
     pub fn on_beacon_block_headers_request(
         &mut self,
@@ -431,13 +375,19 @@ impl SimpleSync {
         ) {
             Ok(headers) => headers,
             Err(e) => {
-                // TODO: return RPC error.
                 warn!(
                     self.log,
                     "RPCRequest"; "peer" => format!("{:?}", peer_id),
                     "req" => "BeaconBlockHeaders",
                     "error" => format!("{:?}", e)
                 );
+                network.send_rpc_error_response(
+                    peer_id,
+                    request_id,
+                    RPCMethod::BeaconBlockHeaders,
+                    RPCErrorCode::ServerError,
+                    format!("Unable to read block headers: {:?}", e),
+                );
                 return;
             }
         };
@@ -449,62 +399,94 @@ impl SimpleSync {
         )
     }
 
-    /// Handle a `BeaconBlockHeaders` response from the peer.
-    pub fn on_beacon_block_headers_response(
+    /// Handle a `BeaconBlockBodies` request from the peer.
+    pub fn on_beacon_block_bodies_request(
         &mut self,
         peer_id: PeerId,
-        res: BeaconBlockHeadersResponse,
+        request_id: RequestId,
+        req: BeaconBlockBodiesRequest,
         network: &mut NetworkContext,
     ) {
         debug!(
             self.log,
-            "BlockHeadersResponse";
+            "BlockBodiesRequest";
             "peer" => format!("{:?}", peer_id),
-            "count" => res.headers.len(),
+            "count" => req.block_roots.len(),
         );
 
-        if res.headers.is_empty() {
-            warn!(
-                self.log,
-                "Peer returned empty block headers response. PeerId: {:?}", peer_id
-            );
-            return;
-        }
-
-        // Enqueue the headers, obtaining a list of the roots of the headers which were newly added
-        // to the queue.
-        let block_roots = self
-            .import_queue
-            .enqueue_headers(res.headers, peer_id.clone());
+        let block_bodies = match self.chain.get_block_bodies(&req.block_roots) {
+            Ok(bodies) => bodies,
+            Err(e) => {
+                warn!(
+                    self.log,
+                    "RPCRequest"; "peer" => format!("{:?}", peer_id),
+                    "req" => "BeaconBlockBodies",
+                    "error" => format!("{:?}", e)
+                );
+                network.send_rpc_error_response(
+                    peer_id,
+                    request_id,
+                    RPCMethod::BeaconBlockBodies,
+                    RPCErrorCode::ServerError,
+                    format!("Unable to read block bodies: {:?}", e),
+                );
+                return;
+            }
+        };
 
-        self.request_block_bodies(peer_id, BeaconBlockBodiesRequest { block_roots }, network);
+        network.send_rpc_response(
+            peer_id,
+            request_id,
+            RPCResponse::BeaconBlockBodies(BeaconBlockBodiesResponse { block_bodies }),
+        )
     }
 
-    /// Handle a `BeaconBlockBodies` request from the peer.
-    pub fn on_beacon_block_bodies_request(
+    /// Handle a `BeaconBlocksByRange` request from the peer.
+    pub fn on_beacon_blocks_by_range_request(
         &mut self,
         peer_id: PeerId,
         request_id: RequestId,
-        req: BeaconBlockBodiesRequest,
+        req: BeaconBlocksByRangeRequest,
         network: &mut NetworkContext,
     ) {
         debug!(
             self.log,
-            "BlockBodiesRequest";
+            "BlocksByRangeRequest";
             "peer" => format!("{:?}", peer_id),
-            "count" => req.block_roots.len(),
+            "start_slot" => req.start_slot,
+            "count" => req.count,
         );
 
-        let block_bodies = match self.chain.get_block_bodies(&req.block_roots) {
-            Ok(bodies) => bodies,
+        if req.count > MAX_BLOCKS_BY_RANGE_COUNT {
+            network.send_rpc_error_response(
+                peer_id,
+                request_id,
+                RPCMethod::BeaconBlocksByRange,
+                RPCErrorCode::InvalidRequest,
+                format!(
+                    "Requested {} slots, the maximum is {}",
+                    req.count, MAX_BLOCKS_BY_RANGE_COUNT
+                ),
+            );
+            return;
+        }
+
+        let blocks = match self.chain.get_blocks_by_range(req.start_slot, req.count) {
+            Ok(blocks) => blocks,
             Err(e) => {
-                // TODO: return RPC error.
                 warn!(
                     self.log,
                     "RPCRequest"; "peer" => format!("{:?}", peer_id),
-                    "req" => "BeaconBlockBodies",
+                    "req" => "BeaconBlocksByRange",
                     "error" => format!("{:?}", e)
                 );
+                network.send_rpc_error_response(
+                    peer_id,
+                    request_id,
+                    RPCMethod::BeaconBlocksByRange,
+                    RPCErrorCode::ServerError,
+                    format!("Unable to read blocks: {:?}", e),
+                );
                 return;
             }
         };
@@ -512,34 +494,65 @@ impl SimpleSync {
         network.send_rpc_response(
             peer_id,
             request_id,
-            RPCResponse::BeaconBlockBodies(BeaconBlockBodiesResponse { block_bodies }),
+            RPCResponse::BeaconBlocksByRange(BeaconBlocksByRangeResponse { blocks }),
         )
     }
 
-    /// Handle a `BeaconBlockBodies` response from the peer.
-    pub fn on_beacon_block_bodies_response(
+    /// Handle a `BeaconBlocksByRange` response from the peer.
+    pub fn on_beacon_blocks_by_range_response(
         &mut self,
         peer_id: PeerId,
-        res: BeaconBlockBodiesResponse,
+        request_id: RequestId,
+        res: BeaconBlocksByRangeResponse,
         network: &mut NetworkContext,
     ) {
         debug!(
             self.log,
-            "BlockBodiesResponse";
+            "BlocksByRangeResponse";
             "peer" => format!("{:?}", peer_id),
-            "count" => res.block_bodies.len(),
+            "count" => res.blocks.len(),
         );
 
-        self.import_queue
-            .enqueue_bodies(res.block_bodies, peer_id.clone());
+        self.range_sync
+            .on_blocks_by_range_response(peer_id, request_id, res.blocks, network);
+
+        if self.state == SyncState::Downloading && !self.range_sync.is_syncing() {
+            debug!(self.log, "Entering idle sync state.");
+            self.state = SyncState::Idle;
+        }
 
         // Clear out old entries
         self.import_queue.remove_stale();
 
-        // Import blocks, if possible.
+        // Queued gossip blocks may now have a known parent.
         self.process_import_queue(network);
     }
 
+    /// Handle a request to a peer which failed, either because the peer did not respond in time
+    /// or because it responded with an error.
+    ///
+    /// Block requests are made again, preferably to another peer.
+    pub fn on_request_failure(
+        &mut self,
+        peer_id: PeerId,
+        request_id: RequestId,
+        request: RPCRequest,
+        network: &mut NetworkContext,
+    ) {
+        match request {
+            RPCRequest::BeaconBlocksByRange(_) => self
+                .range_sync
+                .on_request_failure(peer_id, request_id, network),
+            // A hello is specific to the peer, and the other requests are not made by sync.
+            _ => debug!(
+                self.log,
+                "RPCRequestFailed";
+                "peer" => format!("{:?}", peer_id),
+                "method" => format!("{:?}", RPCMethod::from(request.method_id())),
+            ),
+        }
+    }
+
     /// Process a gossip message declaring a new block.
     ///
     /// Returns a `bool` which, if `true`, indicates we should forward the block to our peers.
@@ -645,8 +658,8 @@ impl SimpleSync {
                         "outcome" => format!("{:?}", outcome),
                         "peer" => format!("{:?}", peer_id),
                     );
-                    // Disconnect the peer
-                    network.disconnect(peer_id, GoodbyeReason::Fault);
+                    // Penalize the peer, which disconnects it if it has misbehaved enough.
+                    network.report_peer(peer_id, PeerAction::InvalidBlock);
                     // Do not forward the block to peers.
                     false
                 } else if outcome.sucessfully_processed() {
@@ -728,7 +741,9 @@ impl SimpleSync {
                             "sender_peer_id" => format!("{:?}", sender),
                             "reason" => format!("{:?}", outcome),
                         );
-                        network.disconnect(sender, GoodbyeReason::Fault);
+                        // The block will never become valid, so stop retrying it.
+                        self.import_queue.remove(block_root);
+                        network.report_peer(sender, PeerAction::InvalidBlock);
                         break;
                     }
 
@@ -764,64 +779,6 @@ impl SimpleSync {
         }
     }
 
-    /// Request some `BeaconBlockRoots` from the remote peer.
-    fn request_block_roots(
-        &mut self,
-        peer_id: PeerId,
-        req: BeaconBlockRootsRequest,
-        network: &mut NetworkContext,
-    ) {
-        // Potentially set state to sync.
-        if self.state == SyncState::Idle && req.count > SLOT_IMPORT_TOLERANCE {
-            debug!(self.log, "Entering downloading sync state.");
-            self.state = SyncState::Downloading;
-        }
-
-        debug!(
-            self.log,
-            "RPCRequest(BeaconBlockRoots)";
-            "count" => req.count,
-            "peer" => format!("{:?}", peer_id)
-        );
-
-        // TODO: handle count > max count.
-        network.send_rpc_request(peer_id.clone(), RPCRequest::BeaconBlockRoots(req));
-    }
-
-    /// Request some `BeaconBlockHeaders` from the remote peer.
-    fn request_block_headers(
-        &mut self,
-        peer_id: PeerId,
-        req: BeaconBlockHeadersRequest,
-        network: &mut NetworkContext,
-    ) {
-        debug!(
-            self.log,
-            "RPCRequest(BeaconBlockHeaders)";
-            "max_headers" => req.max_headers,
-            "peer" => format!("{:?}", peer_id)
-        );
-
-        network.send_rpc_request(peer_id.clone(), RPCRequest::BeaconBlockHeaders(req));
-    }
-
-    /// Request some `BeaconBlockBodies` from the remote peer.
-    fn request_block_bodies(
-        &mut self,
-        peer_id: PeerId,
-        req: BeaconBlockBodiesRequest,
-        network: &mut NetworkContext,
-    ) {
-        debug!(
-            self.log,
-            "RPCRequest(BeaconBlockBodies)";
-            "count" => req.block_roots.len(),
-            "peer" => format!("{:?}", peer_id)
-        );
-
-        network.send_rpc_request(peer_id.clone(), RPCRequest::BeaconBlockBodies(req));
-    }
-
     /// Returns `true` if `self.chain` has not yet processed this block.
     pub fn chain_has_seen_block(&self, block_root: &Hash256) -> bool {
         !self
@@ -1,13 +1,17 @@
+use beacon_chain::parking_lot::RwLock;
 use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 use eth2_libp2p::rpc::methods::*;
 use eth2_libp2p::rpc::{RPCMethod, RPCRequest, RPCResponse, RequestId};
 use eth2_libp2p::{PeerId, RPCEvent};
 use network::beacon_chain::BeaconChain as NetworkBeaconChain;
 use network::message_handler::{HandlerMessage, MessageHandler};
+use network::peer_manager::PeerManager;
 use network::service::{NetworkMessage, OutgoingMessage};
+use network::NetworkConfig;
 use sloggers::terminal::{Destination, TerminalLoggerBuilder};
 use sloggers::types::Severity;
 use sloggers::Build;
+use std::sync::Arc;
 use std::time::Duration;
 use test_harness::BeaconChainHarness;
 use tokio::runtime::TaskExecutor;
@@ -32,9 +36,11 @@ impl SyncNode {
         let harness = BeaconChainHarness::from_beacon_state_builder(state_builder, spec.clone());
 
         let (network_sender, network_receiver) = unbounded();
+        let peer_manager = PeerManager::new(&NetworkConfig::default(), logger.clone());
         let message_handler_sender = MessageHandler::spawn(
             harness.beacon_chain.clone(),
             network_sender,
+            Arc::new(RwLock::new(peer_manager)),
             executor,
             logger,
         )
@@ -111,57 +117,21 @@ impl SyncNode {
         }
     }
 
-    fn tee_block_root_request(&mut self, node: &SyncNode) -> BeaconBlockRootsRequest {
+    fn tee_blocks_by_range_request(&mut self, node: &SyncNode) -> BeaconBlocksByRangeRequest {
         let msg = self.tee_rpc_request(node);
 
         match msg {
-            RPCRequest::BeaconBlockRoots(data) => data,
-            _ => panic!("tee_block_root_request got: {:?}", msg),
+            RPCRequest::BeaconBlocksByRange(data) => data,
+            _ => panic!("tee_blocks_by_range_request got: {:?}", msg),
         }
     }
 
-    fn tee_block_root_response(&mut self, node: &SyncNode) -> BeaconBlockRootsResponse {
+    fn tee_blocks_by_range_response(&mut self, node: &SyncNode) -> BeaconBlocksByRangeResponse {
         let msg = self.tee_rpc_response(node);
 
         match msg {
-            RPCResponse::BeaconBlockRoots(data) => data,
-            _ => panic!("tee_block_root_response got: {:?}", msg),
-        }
-    }
-
-    fn tee_block_header_request(&mut self, node: &SyncNode) -> BeaconBlockHeadersRequest {
-        let msg = self.tee_rpc_request(node);
-
-        match msg {
-            RPCRequest::BeaconBlockHeaders(data) => data,
-            _ => panic!("tee_block_header_request got: {:?}", msg),
-        }
-    }
-
-    fn tee_block_header_response(&mut self, node: &SyncNode) -> BeaconBlockHeadersResponse {
-        let msg = self.tee_rpc_response(node);
-
-        match msg {
-            RPCResponse::BeaconBlockHeaders(data) => data,
-            _ => panic!("tee_block_header_response got: {:?}", msg),
-        }
-    }
-
-    fn tee_block_body_request(&mut self, node: &SyncNode) -> BeaconBlockBodiesRequest {
-        let msg = self.tee_rpc_request(node);
-
-        match msg {
-            RPCRequest::BeaconBlockBodies(data) => data,
-            _ => panic!("tee_block_body_request got: {:?}", msg),
-        }
-    }
-
-    fn tee_block_body_response(&mut self, node: &SyncNode) -> BeaconBlockBodiesResponse {
-        let msg = self.tee_rpc_response(node);
-
-        match msg {
-            RPCResponse::BeaconBlockBodies(data) => data,
-            _ => panic!("tee_block_body_response got: {:?}", msg),
+            RPCResponse::BeaconBlocksByRange(data) => data,
+            _ => panic!("tee_blocks_by_range_response got: {:?}", msg),
         }
     }
 
@@ -197,30 +167,12 @@ impl SyncNode {
         }
     }
 
-    pub fn get_block_root_request(&self) -> BeaconBlockRootsRequest {
-        let request = self.recv_rpc_request().expect("No block root request");
-
-        match request {
-            RPCRequest::BeaconBlockRoots(request) => request,
-            _ => panic!("Did not get block root request"),
-        }
-    }
-
-    pub fn get_block_headers_request(&self) -> BeaconBlockHeadersRequest {
-        let request = self.recv_rpc_request().expect("No block headers request");
+    pub fn get_blocks_by_range_request(&self) -> BeaconBlocksByRangeRequest {
+        let request = self.recv_rpc_request().expect("No blocks by range request");
 
         match request {
-            RPCRequest::BeaconBlockHeaders(request) => request,
-            _ => panic!("Did not get block headers request"),
-        }
-    }
-
-    pub fn get_block_bodies_request(&self) -> BeaconBlockBodiesRequest {
-        let request = self.recv_rpc_request().expect("No block bodies request");
-
-        match request {
-            RPCRequest::BeaconBlockBodies(request) => request,
-            _ => panic!("Did not get block bodies request"),
+            RPCRequest::BeaconBlocksByRange(request) => request,
+            _ => panic!("Did not get blocks by range request"),
         }
     }
 
@@ -307,89 +259,18 @@ impl SyncMaster {
         }
     }
 
-    pub fn respond_to_block_roots_request(
+    pub fn respond_to_blocks_by_range_request(
         &mut self,
         node: &SyncNode,
-        request: BeaconBlockRootsRequest,
+        request: BeaconBlocksByRangeRequest,
     ) {
-        let roots = self
+        let blocks = self
             .harness
             .beacon_chain
-            .get_block_roots(request.start_slot, request.count as usize, 0)
-            .expect("Beacon chain did not give block roots")
-            .iter()
-            .enumerate()
-            .map(|(i, root)| BlockRootSlot {
-                block_root: *root,
-                slot: Slot::from(i) + request.start_slot,
-            })
-            .collect();
-
-        let response = RPCResponse::BeaconBlockRoots(BeaconBlockRootsResponse { roots });
-        self.send_rpc_response(node, response)
-    }
-
-    pub fn respond_to_block_headers_request(
-        &mut self,
-        node: &SyncNode,
-        request: BeaconBlockHeadersRequest,
-    ) {
-        let roots = self
-            .harness
-            .beacon_chain
-            .get_block_roots(
-                request.start_slot,
-                request.max_headers as usize,
-                request.skip_slots as usize,
-            )
+            .get_blocks_by_range(request.start_slot, request.count)
             .expect("Beacon chain did not give blocks");
 
-        if roots.is_empty() {
-            panic!("Roots was empty when trying to get headers.")
-        }
-
-        assert_eq!(
-            roots[0], request.start_root,
-            "Got the wrong start root when getting headers"
-        );
-
-        let headers: Vec<BeaconBlockHeader> = roots
-            .iter()
-            .map(|root| {
-                let block = self
-                    .harness
-                    .beacon_chain
-                    .get_block(root)
-                    .expect("Failed to load block")
-                    .expect("Block did not exist");
-                block.block_header()
-            })
-            .collect();
-
-        let response = RPCResponse::BeaconBlockHeaders(BeaconBlockHeadersResponse { headers });
-        self.send_rpc_response(node, response)
-    }
-
-    pub fn respond_to_block_bodies_request(
-        &mut self,
-        node: &SyncNode,
-        request: BeaconBlockBodiesRequest,
-    ) {
-        let block_bodies: Vec<BeaconBlockBody> = request
-            .block_roots
-            .iter()
-            .map(|root| {
-                let block = self
-                    .harness
-                    .beacon_chain
-                    .get_block(root)
-                    .expect("Failed to load block")
-                    .expect("Block did not exist");
-                block.body
-            })
-            .collect();
-
-        let response = RPCResponse::BeaconBlockBodies(BeaconBlockBodiesResponse { block_bodies });
+        let response = RPCResponse::BeaconBlocksByRange(BeaconBlocksByRangeResponse { blocks });
         self.send_rpc_response(node, response)
     }
 
@@ -469,23 +350,11 @@ fn sync_node_with_master() {
 
     master.do_hello_with(&nodes[0]);
 
-    let roots_request = nodes[0].get_block_root_request();
-    assert_eq!(roots_request.start_slot, original_node_slot + 1);
-    assert_eq!(roots_request.count, 2);
-
-    master.respond_to_block_roots_request(&nodes[0], roots_request);
-
-    let headers_request = nodes[0].get_block_headers_request();
-    assert_eq!(headers_request.start_slot, original_node_slot + 1);
-    assert_eq!(headers_request.max_headers, 2);
-    assert_eq!(headers_request.skip_slots, 0);
-
-    master.respond_to_block_headers_request(&nodes[0], headers_request);
+    let range_request = nodes[0].get_blocks_by_range_request();
+    assert_eq!(range_request.start_slot, original_node_slot + 1);
+    assert_eq!(range_request.count, 2);
 
-    let bodies_request = nodes[0].get_block_bodies_request();
-    assert_eq!(bodies_request.block_roots.len(), 2);
-
-    master.respond_to_block_bodies_request(&nodes[0], bodies_request);
+    master.respond_to_blocks_by_range_request(&nodes[0], range_request);
 
     std::thread::sleep(Duration::from_millis(10000));
     runtime.shutdown_now();
@@ -528,20 +397,11 @@ fn sync_two_nodes() {
     // A says hello back.
     node_a.tee_hello_response(&node_b);
 
-    // B requests block roots from A.
-    node_b.tee_block_root_request(&node_a);
-    // A provides block roots to A.
-    node_a.tee_block_root_response(&node_b);
-
-    // B requests block headers from A.
-    node_b.tee_block_header_request(&node_a);
-    // A provides block headers to B.
-    node_a.tee_block_header_response(&node_b);
-
-    // B requests block bodies from A.
-    node_b.tee_block_body_request(&node_a);
-    // A provides block bodies to B.
-    node_a.tee_block_body_response(&node_b);
+    // B requests the blocks it is missing from A.
+    node_b.tee_blocks_by_range_request(&node_a);
+    // A provides the blocks to B.
+    let response = node_a.tee_blocks_by_range_response(&node_b);
+    assert_eq!(response.blocks.len(), blocks);
 
     std::thread::sleep(Duration::from_secs(20));
 
@@ -1,13 +1,14 @@
 use beacon_chain::BeaconChain as RawBeaconChain;
 use beacon_chain::{
     db::ClientDB,
-    fork_choice::ForkChoice,
+    fork_choice::{BlockTree, ForkChoice},
     parking_lot::{RwLockReadGuard, RwLockWriteGuard},
     slot_clock::SlotClock,
     types::{BeaconState, ChainSpec, Signature},
-    AttestationValidationError, BlockProductionError,
+    AttestationValidationError, BeaconChainEvent, BlockProductionError,
 };
 pub use beacon_chain::{BeaconChainError, BlockProcessingOutcome};
+use crossbeam_channel::Receiver;
 use types::{Attestation, AttestationData, BeaconBlock};
 
 /// The RPC's API to the beacon chain.
@@ -32,6 +33,10 @@ pub trait BeaconChain: Send + Sync {
         &self,
         attestation: Attestation,
     ) -> Result<(), AttestationValidationError>;
+
+    fn block_tree(&self) -> Result<BlockTree, BeaconChainError>;
+
+    fn subscribe_events(&self) -> Receiver<BeaconChainEvent>;
 }
 
 impl<T, U, F> BeaconChain for RawBeaconChain<T, U, F>
@@ -76,4 +81,12 @@ where
     ) -> Result<(), AttestationValidationError> {
         self.process_attestation(attestation)
     }
+
+    fn block_tree(&self) -> Result<BlockTree, BeaconChainError> {
+        self.block_tree()
+    }
+
+    fn subscribe_events(&self) -> Receiver<BeaconChainEvent> {
+        self.subscribe_events()
+    }
 }
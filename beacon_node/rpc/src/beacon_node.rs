@@ -1,10 +1,25 @@
 use crate::beacon_chain::BeaconChain;
-use futures::Future;
-use grpcio::{RpcContext, UnarySink};
-use protos::services::{Empty, Fork, NodeInfoResponse};
+use beacon_chain::{BeaconChainEvent, PooledOperation};
+use crossbeam_channel::{RecvTimeoutError, TryRecvError};
+use futures::sync::mpsc;
+use futures::{Future, Sink, Stream};
+use grpcio::{RpcContext, ServerStreamingSink, UnarySink, WriteFlags};
+use protos::services::{
+    AttestationData as AttestationDataProto, BeaconChainEvent as BeaconChainEventProto,
+    BlockImported, Empty, Finalized, Fork, HeadChanged, NodeInfoResponse, OperationPooled,
+    OperationPooled_Operation,
+};
 use protos::services_grpc::BeaconNodeService;
-use slog::{trace, warn};
+use slog::{debug, trace, warn};
+use ssz::ssz_encode;
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The number of events buffered for an event stream client before its stream is ended.
+const EVENT_BUFFER_SIZE: usize = 1_024;
+/// The interval at which an event stream's thread checks whether the client has gone.
+const EVENT_STREAM_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Clone)]
 pub struct BeaconNodeServiceInstance {
@@ -44,4 +59,111 @@ impl BeaconNodeService for BeaconNodeServiceInstance {
             .map_err(move |e| warn!(error_log, "failed to reply {:?}", e));
         ctx.spawn(f)
     }
+
+    /// Streams every `BeaconChainEvent` published after the request, until the client
+    /// disconnects. The stream is ended if the client falls `EVENT_BUFFER_SIZE` events behind.
+    fn stream_events(
+        &mut self,
+        ctx: RpcContext,
+        _req: Empty,
+        sink: ServerStreamingSink<BeaconChainEventProto>,
+    ) {
+        trace!(self.log, "Event stream requested via RPC");
+
+        let events = self.chain.subscribe_events();
+
+        // The beacon chain publishes events on a blocking channel, forward them onto a futures
+        // channel. `stream_alive` is dropped when the stream to the client ends, upon which the
+        // thread exits without waiting for another event.
+        let (mut tx, rx) = mpsc::channel(EVENT_BUFFER_SIZE);
+        let (stream_alive, stream_ended) = crossbeam_channel::bounded::<()>(0);
+        let log = self.log.clone();
+        thread::spawn(move || loop {
+            match events.recv_timeout(EVENT_STREAM_CHECK_INTERVAL) {
+                Ok(event) => match tx.try_send(event_to_proto(event)) {
+                    Ok(()) => {}
+                    Err(ref e) if e.is_full() => {
+                        warn!(
+                            log,
+                            "Event stream client is not keeping up, ending the stream"
+                        );
+                        break;
+                    }
+                    Err(_) => break,
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Err(TryRecvError::Disconnected) = stream_ended.try_recv() {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        let stream = rx
+            .map(|event| (event, WriteFlags::default()))
+            .map_err(|_| grpcio::Error::RemoteStopped);
+
+        let log = self.log.clone();
+        let f = sink
+            .send_all(stream)
+            .map(|_| ())
+            .map_err(move |e| debug!(log, "Event stream ended {:?}", e))
+            .then(move |result| {
+                drop(stream_alive);
+                result
+            });
+        ctx.spawn(f)
+    }
+}
+
+fn event_to_proto(event: BeaconChainEvent) -> BeaconChainEventProto {
+    let mut proto = BeaconChainEventProto::new();
+
+    match event {
+        BeaconChainEvent::BlockImported { block_root, slot } => {
+            let mut block_imported = BlockImported::new();
+            block_imported.set_block_root(block_root.as_bytes().to_vec());
+            block_imported.set_slot(slot.as_u64());
+            proto.set_block_imported(block_imported);
+        }
+        BeaconChainEvent::HeadChanged {
+            old_head,
+            new_head,
+            slot,
+            reorg_depth,
+        } => {
+            let mut head_changed = HeadChanged::new();
+            head_changed.set_old_head(old_head.as_bytes().to_vec());
+            head_changed.set_new_head(new_head.as_bytes().to_vec());
+            head_changed.set_slot(slot.as_u64());
+            head_changed.set_reorg_depth(reorg_depth as u64);
+            proto.set_head_changed(head_changed);
+        }
+        BeaconChainEvent::Finalized { block_root, slot } => {
+            let mut finalized = Finalized::new();
+            finalized.set_block_root(block_root.as_bytes().to_vec());
+            finalized.set_slot(slot.as_u64());
+            proto.set_finalized(finalized);
+        }
+        BeaconChainEvent::AttestationReceived(data) => {
+            let mut attestation_data = AttestationDataProto::new();
+            attestation_data.set_ssz(ssz_encode(&data));
+            proto.set_attestation_received(attestation_data);
+        }
+        BeaconChainEvent::OperationPooled(operation) => {
+            let operation = match operation {
+                PooledOperation::Deposit => OperationPooled_Operation::DEPOSIT,
+                PooledOperation::VoluntaryExit => OperationPooled_Operation::VOLUNTARY_EXIT,
+                PooledOperation::Transfer => OperationPooled_Operation::TRANSFER,
+                PooledOperation::ProposerSlashing => OperationPooled_Operation::PROPOSER_SLASHING,
+                PooledOperation::AttesterSlashing => OperationPooled_Operation::ATTESTER_SLASHING,
+            };
+            let mut operation_pooled = OperationPooled::new();
+            operation_pooled.set_operation(operation);
+            proto.set_operation_pooled(operation_pooled);
+        }
+    }
+
+    proto
 }
@@ -0,0 +1,192 @@
+//! Offline inspection of the beacon node database, used by the `db` subcommand.
+//!
+//! The database is opened read-only, so it may be inspected while a node is running. Writes made
+//! by the node after the database is opened are not visible.
+
+use beacon_chain::slot_clock::TestingSlotClock;
+use beacon_chain::{BeaconChain, PersistedBeaconChain};
+use clap::ArgMatches;
+use client::ClientConfig;
+use db::stores::{BeaconBlockStore, BeaconStateStore, ChainMetadataStore, COLUMNS};
+use db::{ClientDB, DiskDB};
+use serde::Serialize;
+use ssz::decode;
+use std::sync::Arc;
+use types::{BeaconBlock, Hash256};
+
+/// Runs the `db` subcommand given by `matches` against the database at `config.db_name`.
+pub fn run(matches: &ArgMatches, config: &ClientConfig) -> Result<(), String> {
+    let db = Arc::new(
+        DiskDB::open_read_only(&config.db_name)
+            .map_err(|e| format!("Unable to open database: {}", e.message))?,
+    );
+
+    match matches.subcommand() {
+        ("blocks", Some(_)) => list_blocks(db),
+        ("block", Some(matches)) => {
+            let root = parse_root(matches.value_of("root"))?;
+            let block = BeaconBlockStore::new(db)
+                .get_deserialized(&root)
+                .map_err(|e| e.message)?
+                .ok_or_else(|| format!("No block with root {}", root))?;
+            print_formatted(&block, matches.value_of("format"))
+        }
+        ("state", Some(matches)) => {
+            let root = parse_root(matches.value_of("root"))?;
+            let state = BeaconStateStore::new(db)
+                .get_deserialized(&root)
+                .map_err(|e| e.message)?
+                .ok_or_else(|| {
+                    format!(
+                        "No hot state with root {} (finalized states may have been frozen)",
+                        root
+                    )
+                })?;
+            print_formatted(&state, matches.value_of("format"))
+        }
+        ("chain", Some(_)) => show_canonical_chain(db),
+        ("fork-choice", Some(matches)) => show_block_tree(db, config, matches.value_of("format")),
+        ("sizes", Some(_)) => report_column_sizes(&*db),
+        _ => Err("Unknown db command, see --help".to_string()),
+    }
+}
+
+/// Parses a block or state root, with or without a `0x` prefix.
+fn parse_root(root: Option<&str>) -> Result<Hash256, String> {
+    let root = root.ok_or_else(|| "A root is required".to_string())?;
+    root.trim_start_matches("0x")
+        .parse::<Hash256>()
+        .map_err(|_| format!("Invalid root: {}", root))
+}
+
+/// Prints `item` to stdout as YAML (the default) or JSON.
+fn print_formatted<T: Serialize>(item: &T, format: Option<&str>) -> Result<(), String> {
+    let output = match format {
+        Some("json") => serde_json::to_string_pretty(item).map_err(|e| format!("{:?}", e))?,
+        _ => serde_yaml::to_string(item).map_err(|e| format!("{:?}", e))?,
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+/// Prints the slot and root of every stored block, ordered by slot.
+fn list_blocks(db: Arc<DiskDB>) -> Result<(), String> {
+    let block_store = BeaconBlockStore::new(db);
+
+    let mut blocks = vec![];
+    for entry in block_store.iter().map_err(|e| e.message)? {
+        let (root, ssz) = entry.map_err(|e| e.message)?;
+        let block = decode::<BeaconBlock>(&ssz).map_err(|_| format!("Bad block SSZ {}", root))?;
+        blocks.push((block.slot, root));
+    }
+    blocks.sort();
+
+    for (slot, root) in &blocks {
+        println!("{}\t{}", slot, root);
+    }
+    println!("{} blocks", blocks.len());
+
+    Ok(())
+}
+
+/// Walks back from the stored canonical head to genesis, printing each block.
+fn show_canonical_chain(db: Arc<DiskDB>) -> Result<(), String> {
+    let block_store = BeaconBlockStore::new(db.clone());
+    let state_store = BeaconStateStore::new(db.clone());
+
+    let ssz = ChainMetadataStore::new(db)
+        .get()
+        .map_err(|e| e.message)?
+        .ok_or_else(|| "No chain has been persisted".to_string())?;
+    let persisted = decode::<PersistedBeaconChain>(&ssz)
+        .map_err(|_| "Bad PersistedBeaconChain SSZ".to_string())?;
+
+    println!("finalized_head\t{}", persisted.finalized_head_block_root);
+    println!("heads\t{:?}", persisted.heads);
+    println!("slot\tblock_root\tstate_root\tstate");
+
+    let mut root = persisted.canonical_head_block_root;
+    while root != Hash256::zero() {
+        let block = block_store
+            .get_deserialized(&root)
+            .map_err(|e| e.message)?
+            .ok_or_else(|| format!("Missing block {}", root))?;
+        let state = if state_store
+            .exists(&block.state_root)
+            .map_err(|e| e.message)?
+        {
+            "hot"
+        } else {
+            "frozen"
+        };
+
+        println!("{}\t{}\t{}\t{}", block.slot, root, block.state_root, state);
+
+        root = block.previous_block_root;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the fork choice from the stored chain (as done when the node starts) and prints its
+/// block tree, as a Graphviz graph (the default), YAML or JSON.
+fn show_block_tree(
+    db: Arc<DiskDB>,
+    config: &ClientConfig,
+    format: Option<&str>,
+) -> Result<(), String> {
+    let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
+    let genesis_slot = config.spec.genesis_slot.as_u64();
+
+    // The weights of the tree do not depend upon the fork choice algorithm, nor is the clock
+    // read.
+    let chain = BeaconChain::from_store(
+        Arc::new(BeaconStateStore::with_epochs_per_cold_state(
+            db.clone(),
+            config.epochs_per_cold_state,
+        )),
+        block_store.clone(),
+        Arc::new(ChainMetadataStore::new(db)),
+        |_genesis_time| TestingSlotClock::new(genesis_slot),
+        config.spec.clone(),
+        config.fork_choice.build(block_store),
+    )
+    .map_err(|e| format!("Unable to load the chain: {:?}", e))?
+    .ok_or_else(|| "No chain has been persisted".to_string())?;
+
+    let tree = chain
+        .block_tree()
+        .map_err(|e| format!("Unable to read the block tree: {:?}", e))?;
+
+    match format {
+        Some("yaml") | Some("json") => print_formatted(&tree, format),
+        _ => {
+            print!("{}", tree.to_dot());
+            Ok(())
+        }
+    }
+}
+
+/// Prints the number of keys and total size of the keys and values in each column.
+///
+/// Columns which the database does not have are reported as absent.
+fn report_column_sizes(db: &DiskDB) -> Result<(), String> {
+    println!("column\tkeys\tbytes");
+
+    for col in COLUMNS.iter() {
+        if !db.has_column(col) {
+            println!("{}\tabsent\tabsent", col);
+            continue;
+        }
+
+        let (keys, bytes) = db
+            .iter_column(col)
+            .map_err(|e| e.message)?
+            .fold((0, 0), |(keys, bytes), (key, value)| {
+                (keys + 1, bytes + key.len() + value.len())
+            });
+        println!("{}\t{}\t{}", col, keys, bytes);
+    }
+
+    Ok(())
+}
@@ -1,8 +1,9 @@
 extern crate slog;
 
+mod db_inspect;
 mod run;
 
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use client::ClientConfig;
 use slog::{error, o, Drain};
 
@@ -24,6 +25,63 @@ fn main() {
                 .help("Data directory for keys and databases.")
                 .takes_value(true),
         )
+        // fork choice related arguments
+        .arg(
+            Arg::with_name("fork-choice")
+                .long("fork-choice")
+                .value_name("ALGORITHM")
+                .help("The fork choice rule used to find the head of the chain.")
+                .takes_value(true)
+                .possible_values(&["longest-chain", "slow", "bitwise", "optimized", "proto-array"])
+                .default_value("bitwise"),
+        )
+        // database related arguments
+        .arg(
+            Arg::with_name("db")
+                .long("db")
+                .value_name("DB")
+                .help("Type of database to use for the beacon chain.")
+                .takes_value(true)
+                .possible_values(&["memory", "disk"])
+                .default_value("memory"),
+        )
+        .arg(
+            Arg::with_name("db-path")
+                .long("db-path")
+                .value_name("DB_PATH")
+                .help("Directory of the on-disk database. Defaults to \"chain.db\" in the data directory.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("db-option")
+                .long("db-option")
+                .value_name("[COLUMN.]OPTION=VALUE")
+                .help("Tunes the on-disk database, for one column or for all columns without their own settings. OPTION is block-cache-size, write-buffer-size (bytes) or compression (none, snappy, lz4, zstd). May be given multiple times.")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("epochs-per-cold-state")
+                .long("epochs-per-cold-state")
+                .value_name("EPOCHS")
+                .help("The number of epochs between each finalized state kept in the database. Other finalized states are rebuilt by replaying blocks. Must not change for an existing database.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("state-cache-size")
+                .long("state-cache-size")
+                .value_name("STATES")
+                .help("The number of recent states to keep in memory, avoiding database reads when importing blocks.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("eth1-chain-file")
+                .long("eth1-chain-file")
+                .value_name("FILE")
+                .help("A JSON file of eth1 blocks and deposit logs to use in place of an eth1 node.")
+                .takes_value(true),
+        )
         // network related arguments
         .arg(
             Arg::with_name("listen-address")
@@ -46,6 +104,27 @@ fn main() {
                 .help("A list of comma separated multi addresses representing bootnodes to connect to.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("target-peers")
+                .long("target-peers")
+                .value_name("COUNT")
+                .help("The number of connected peers at which peer discovery stops searching for new peers.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-peers")
+                .long("max-peers")
+                .value_name("COUNT")
+                .help("The maximum number of connected peers.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("node-key-file")
+                .long("node-key-file")
+                .value_name("FILE")
+                .help("The file holding the node's network key. Created if it does not exist. Defaults to a file in the datadir.")
+                .takes_value(true),
+        )
         // rpc related arguments
         .arg(
             Arg::with_name("rpc")
@@ -68,13 +147,83 @@ fn main() {
                 .help("Listen port for RPC endpoint.")
                 .takes_value(true),
         )
+        // offline database inspection
+        .subcommand(
+            SubCommand::with_name("db")
+                .about("Inspects the on-disk database, without modifying it.")
+                .subcommand(
+                    SubCommand::with_name("blocks")
+                        .about("Lists the root and slot of each stored block."),
+                )
+                .subcommand(
+                    SubCommand::with_name("block")
+                        .about("Prints the block with the given root.")
+                        .arg(root_arg())
+                        .arg(format_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("state")
+                        .about("Prints the (unfinalized) state with the given root.")
+                        .arg(root_arg())
+                        .arg(format_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("chain")
+                        .about("Lists the canonical chain, from the stored head to genesis."),
+                )
+                .subcommand(
+                    SubCommand::with_name("fork-choice")
+                        .about("Prints the blocks which descend from the justified checkpoint, with their fork choice weights.")
+                        .arg(
+                            Arg::with_name("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .help("The output format, dot renders a Graphviz graph.")
+                                .takes_value(true)
+                                .possible_values(&["dot", "yaml", "json"])
+                                .default_value("dot"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("sizes")
+                        .about("Reports the number of keys and bytes stored in each column."),
+                ),
+        )
         .get_matches();
 
     // invalid arguments, panic
-    let config = ClientConfig::parse_args(matches, &logger).unwrap();
+    let config = ClientConfig::parse_args(matches.clone(), &logger).unwrap();
+
+    if let Some(db_matches) = matches.subcommand_matches("db") {
+        if let Err(e) = db_inspect::run(db_matches, &config) {
+            error!(logger, "Database inspection failed"; "error" => e);
+        }
+        return;
+    }
 
     match run::run_beacon_node(config, &logger) {
         Ok(_) => {}
         Err(e) => error!(logger, "Beacon node failed because {:?}", e),
     }
 }
+
+/// The `--root` argument of the `db` subcommands.
+fn root_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("root")
+        .long("root")
+        .value_name("ROOT")
+        .help("The hex-encoded root.")
+        .takes_value(true)
+        .required(true)
+}
+
+/// The `--format` argument of the `db` subcommands.
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("format")
+        .long("format")
+        .value_name("FORMAT")
+        .help("The output format.")
+        .takes_value(true)
+        .possible_values(&["yaml", "json"])
+        .default_value("yaml")
+}
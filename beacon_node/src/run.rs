@@ -1,14 +1,18 @@
-use client::client_types::TestingClientType;
+use client::client_types::{DiskDBClientType, MemoryDBClientType};
 use client::error;
-use client::{notifier, Client, ClientConfig};
+use client::{notifier, Client, ClientConfig, ClientTypes};
+use db::DBType;
+use exit_future::{Exit, Signal};
 use futures::sync::oneshot;
 use futures::Future;
 use slog::info;
 use std::cell::RefCell;
-use tokio::runtime::Builder;
+use tokio::runtime::{Builder, Runtime, TaskExecutor};
 use tokio_timer::clock::Clock;
 
 pub fn run_beacon_node(config: ClientConfig, log: &slog::Logger) -> error::Result<()> {
+    config.create_data_dir()?;
+
     let mut runtime = Builder::new()
         .name_prefix("main-")
         .clock(Clock::system())
@@ -34,8 +38,30 @@ pub fn run_beacon_node(config: ClientConfig, log: &slog::Logger) -> error::Resul
 
     let executor = runtime.executor();
 
-    // currently testing - using TestingClientType
-    let client: Client<TestingClientType> = Client::new(config, log.clone(), &executor)?;
+    match config.db_type {
+        DBType::RocksDB => {
+            info!(log, "Using on-disk database"; "path" => &config.db_name.to_str());
+            let client: Client<DiskDBClientType> = Client::new(config, log.clone(), &executor)?;
+            run(client, runtime, executor, ctrlc, exit_signal, exit, log)
+        }
+        DBType::Memory => {
+            info!(log, "Using in-memory database");
+            let client: Client<MemoryDBClientType> = Client::new(config, log.clone(), &executor)?;
+            run(client, runtime, executor, ctrlc, exit_signal, exit, log)
+        }
+    }
+}
+
+/// Runs the `client` until ctrl-c is received, then shuts it down.
+fn run<T: ClientTypes>(
+    client: Client<T>,
+    mut runtime: Runtime,
+    executor: TaskExecutor,
+    ctrlc: oneshot::Receiver<()>,
+    exit_signal: Signal,
+    exit: Exit,
+    log: &slog::Logger,
+) -> error::Result<()> {
     notifier::run(&client, executor, exit);
 
     runtime
@@ -0,0 +1,123 @@
+use criterion::Criterion;
+use criterion::{black_box, criterion_group, criterion_main, Benchmark};
+use db::stores::BeaconBlockStore;
+use db::MemoryDB;
+use fork_choice::{
+    BitwiseLMDGhost, ForkChoice, Justification, JustifiedCheckpoint, ProtoArrayLMDGhost,
+};
+use ssz::ssz_encode;
+use std::sync::Arc;
+use types::test_utils::TestingBeaconStateBuilder;
+use types::{BeaconBlock, ChainSpec, Hash256, Keypair};
+
+pub const VALIDATOR_COUNT: usize = 1_024;
+
+/// The number of competing chains built upon the genesis block.
+pub const FORK_COUNT: usize = 4;
+
+/// The number of blocks in each competing chain.
+pub const FORK_LENGTH: usize = 64;
+
+/// Builds `FORK_COUNT` chains of `FORK_LENGTH` blocks upon a genesis block and gives each
+/// validator a vote for a block in one of the chains.
+///
+/// Returns the genesis block as the justified checkpoint.
+fn build_fork_choice<F: ForkChoice>(
+    new_fork_choice: fn(Arc<BeaconBlockStore<MemoryDB>>) -> F,
+    spec: &ChainSpec,
+) -> (F, JustifiedCheckpoint) {
+    let db = Arc::new(MemoryDB::open());
+    let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
+
+    let (state, _keypairs) =
+        TestingBeaconStateBuilder::from_single_keypair(VALIDATOR_COUNT, &Keypair::random(), spec)
+            .build();
+    let state_root = state.canonical_root();
+    let justification = Justification::from(&state);
+
+    let mut fork_choice = new_fork_choice(block_store.clone());
+
+    let block_root = |fork: usize, height: usize| {
+        let mut bytes = [0; 32];
+        bytes[0] = fork as u8 + 1;
+        bytes[31] = height as u8;
+        Hash256::from(bytes)
+    };
+
+    let genesis_root = block_root(0, 0);
+    let mut genesis_block = BeaconBlock::empty(spec);
+    genesis_block.state_root = state_root;
+    block_store
+        .put(&genesis_root, &ssz_encode(&genesis_block))
+        .unwrap();
+
+    for fork in 0..FORK_COUNT {
+        let mut parent_root = genesis_root;
+        for height in 1..=FORK_LENGTH {
+            let root = block_root(fork + 1, height);
+            let mut block = BeaconBlock::empty(spec);
+            block.slot = spec.genesis_slot + height as u64;
+            block.previous_block_root = parent_root;
+            block.state_root = state_root;
+
+            block_store.put(&root, &ssz_encode(&block)).unwrap();
+            fork_choice
+                .add_block(&block, &root, justification, spec)
+                .unwrap();
+            parent_root = root;
+        }
+    }
+
+    for validator_index in 0..VALIDATOR_COUNT {
+        let fork = validator_index % FORK_COUNT;
+        let height = 1 + validator_index % FORK_LENGTH;
+        fork_choice
+            .add_vote(
+                validator_index as u64,
+                &block_root(fork + 1, height),
+                spec.genesis_epoch,
+                spec,
+            )
+            .unwrap();
+    }
+
+    let justified = JustifiedCheckpoint::new(genesis_root, justification, &state, spec);
+
+    (fork_choice, justified)
+}
+
+fn bitwise(block_store: Arc<BeaconBlockStore<MemoryDB>>) -> BitwiseLMDGhost<MemoryDB> {
+    BitwiseLMDGhost::new(block_store)
+}
+
+fn proto_array(block_store: Arc<BeaconBlockStore<MemoryDB>>) -> ProtoArrayLMDGhost<MemoryDB> {
+    ProtoArrayLMDGhost::new(block_store)
+}
+
+fn bench_find_head<F: ForkChoice + 'static>(
+    c: &mut Criterion,
+    name: &str,
+    new_fork_choice: fn(Arc<BeaconBlockStore<MemoryDB>>) -> F,
+) {
+    let spec = ChainSpec::foundation();
+    let (mut fork_choice, justified) = build_fork_choice(new_fork_choice, &spec);
+
+    c.bench(
+        &format!(
+            "{} validators, {} forks of {} blocks",
+            VALIDATOR_COUNT, FORK_COUNT, FORK_LENGTH
+        ),
+        Benchmark::new(name, move |b| {
+            b.iter(|| black_box(fork_choice.find_head(&justified, &spec).unwrap()))
+        })
+        .sample_size(10),
+    );
+}
+
+fn find_head(c: &mut Criterion) {
+    bench_find_head(c, "bitwise find_head", bitwise);
+    bench_find_head(c, "proto_array find_head", proto_array);
+}
+
+criterion_group!(benches, find_head);
+criterion_main!(benches);
@@ -0,0 +1,122 @@
+//! A snapshot of the block tree known to a fork choice, for debugging head selection.
+use crate::{ForkChoiceError, JustifiedCheckpoint};
+use db::{stores::BeaconBlockStore, ClientDB};
+use serde_derive::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use types::{Hash256, Slot};
+
+/// A block in a `BlockTree`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockTreeNode {
+    pub root: Hash256,
+    /// The parent of the block, `None` for the justified block.
+    pub parent: Option<Hash256>,
+    pub slot: Slot,
+    /// The sum of the weighted votes for this block and all of its descendants.
+    pub weight: u64,
+    /// The indices of the validators whose latest vote is for this block.
+    pub voters: Vec<u64>,
+}
+
+/// The justified block and all of its known descendants, with the weights given to each block
+/// by the latest votes.
+///
+/// Parents always appear before their children in `nodes`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockTree {
+    pub justified_root: Hash256,
+    pub nodes: Vec<BlockTreeNode>,
+}
+
+impl BlockTree {
+    /// Builds the tree of `justified.root` and its descendants given by `children`.
+    ///
+    /// `latest_vote` returns the block root of the latest vote of a validator, which is weighted
+    /// by the balances of `justified`. Votes for blocks outside of the tree are ignored.
+    pub(crate) fn from_children<T, V>(
+        justified: &JustifiedCheckpoint,
+        children: &HashMap<Hash256, Vec<Hash256>>,
+        latest_vote: V,
+        block_store: &BeaconBlockStore<T>,
+    ) -> Result<Self, ForkChoiceError>
+    where
+        T: ClientDB + Sized,
+        V: Fn(u64) -> Option<Hash256>,
+    {
+        let mut nodes: Vec<BlockTreeNode> = vec![];
+        let mut indices: HashMap<Hash256, usize> = HashMap::new();
+
+        let mut stack = vec![(justified.root, None)];
+        while let Some((root, parent)) = stack.pop() {
+            let slot = block_store
+                .get_deserialized(&root)?
+                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(root))?
+                .slot;
+
+            indices.insert(root, nodes.len());
+            nodes.push(BlockTreeNode {
+                root,
+                parent,
+                slot,
+                weight: 0,
+                voters: vec![],
+            });
+
+            if let Some(children) = children.get(&root) {
+                stack.extend(children.iter().map(|child| (*child, Some(root))));
+            }
+        }
+
+        for validator_index in 0..justified.balances.len() as u64 {
+            if let Some(index) = latest_vote(validator_index).and_then(|root| indices.get(&root)) {
+                nodes[*index].voters.push(validator_index);
+            }
+        }
+
+        // Children always follow their parents, so iterating backwards visits every child before
+        // its parent.
+        for index in (0..nodes.len()).rev() {
+            let votes: u64 = nodes[index]
+                .voters
+                .iter()
+                .map(|validator_index| justified.balances[*validator_index as usize])
+                .sum();
+            nodes[index].weight += votes;
+
+            if let Some(parent) = nodes[index].parent {
+                let weight = nodes[index].weight;
+                nodes[indices[&parent]].weight += weight;
+            }
+        }
+
+        Ok(BlockTree {
+            justified_root: justified.root,
+            nodes,
+        })
+    }
+
+    /// Renders the tree in the Graphviz DOT language, with an edge from each parent to its
+    /// children.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph block_tree {\n    node [shape=box];\n");
+
+        for node in &self.nodes {
+            let _ = writeln!(
+                dot,
+                "    \"{:?}\" [label=\"{}\\nslot {}\\nweight {}\\nvoters {}\"];",
+                node.root,
+                node.root,
+                node.slot,
+                node.weight,
+                node.voters.len()
+            );
+            if let Some(parent) = node.parent {
+                let _ = writeln!(dot, "    \"{:?}\" -> \"{:?}\";", parent, node.root);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -0,0 +1,112 @@
+//! The justification information used to restrict the fork choice to branches which agree with
+//! the best justified checkpoint.
+use std::collections::{HashMap, HashSet};
+use types::{BeaconState, ChainSpec, Epoch, Hash256};
+
+/// The justified and finalized epochs of a state.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Justification {
+    pub justified_epoch: Epoch,
+    pub finalized_epoch: Epoch,
+}
+
+impl<'a> From<&'a BeaconState> for Justification {
+    fn from(state: &'a BeaconState) -> Justification {
+        Justification {
+            justified_epoch: state.current_justified_epoch,
+            finalized_epoch: state.finalized_epoch,
+        }
+    }
+}
+
+/// The checkpoint from which `find_head` searches for the head of the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JustifiedCheckpoint {
+    /// The root of the justified block.
+    pub root: Hash256,
+    /// The best justified and finalized epochs of all imported states. Only branches leading to a
+    /// block whose post-state agrees with these epochs are considered.
+    pub justification: Justification,
+    /// The weighted balances of the validators in the justified state, indexed by validator
+    /// index. Inactive validators have no weight.
+    pub balances: Vec<u64>,
+}
+
+impl JustifiedCheckpoint {
+    /// Builds a checkpoint for the block with the given `root`, taking a snapshot of the
+    /// balances of its post-state, `state`.
+    ///
+    /// Votes are weighted by min(balance, MAX_DEPOSIT_AMOUNT) // FORK_CHOICE_BALANCE_INCREMENT.
+    pub fn new(
+        root: Hash256,
+        justification: Justification,
+        state: &BeaconState,
+        spec: &ChainSpec,
+    ) -> Self {
+        let mut balances = vec![0; state.validator_balances.len()];
+        for index in state.get_active_validator_indices(state.current_epoch(spec)) {
+            balances[index] =
+                std::cmp::min(state.validator_balances[index], spec.max_deposit_amount)
+                    / spec.fork_choice_balance_increment;
+        }
+
+        Self {
+            root,
+            justification,
+            balances,
+        }
+    }
+
+    /// Returns `true` if a head whose post-state has the given `justification` may be chosen.
+    ///
+    /// Prior to the first justification all heads are viable.
+    pub fn is_viable(&self, justification: &Justification, spec: &ChainSpec) -> bool {
+        self.justification.justified_epoch == spec.genesis_epoch
+            || *justification == self.justification
+    }
+}
+
+/// Returns `start` and the blocks descending from it which lead to a viable head (see
+/// `JustifiedCheckpoint::is_viable`).
+///
+/// Returns `None` if there is no viable head, in which case no branches should be filtered.
+pub fn viable_blocks(
+    start: Hash256,
+    children: &HashMap<Hash256, Vec<Hash256>>,
+    justifications: &HashMap<Hash256, Justification>,
+    justified: &JustifiedCheckpoint,
+    spec: &ChainSpec,
+) -> Option<HashSet<Hash256>> {
+    // Parents are always visited before their children.
+    let mut visited = vec![];
+    let mut stack = vec![start];
+    while let Some(block_hash) = stack.pop() {
+        visited.push(block_hash);
+        if let Some(children) = children.get(&block_hash) {
+            stack.extend(children.iter().cloned());
+        }
+    }
+
+    let mut viable = HashSet::new();
+    for block_hash in visited.into_iter().rev() {
+        let is_viable = match children.get(&block_hash) {
+            Some(children) if !children.is_empty() => {
+                children.iter().any(|child| viable.contains(child))
+            }
+            _ => justifications
+                .get(&block_hash)
+                .map_or(false, |justification| {
+                    justified.is_viable(justification, spec)
+                }),
+        };
+        if is_viable {
+            viable.insert(block_hash);
+        }
+    }
+
+    if viable.contains(&start) {
+        Some(viable)
+    } else {
+        None
+    }
+}
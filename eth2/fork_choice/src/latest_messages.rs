@@ -0,0 +1,232 @@
+//! Tracks the latest message (the block voted for in an attestation) of each validator.
+use log::trace;
+use std::collections::{HashMap, HashSet};
+use types::{Epoch, Hash256};
+
+/// The block a validator most recently voted for, and the epoch of the vote.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatestMessage {
+    pub block_root: Hash256,
+    pub epoch: Epoch,
+}
+
+/// Stores the latest message of each validator, keeping only the message with the highest epoch.
+///
+/// Messages for blocks which have not yet been added to the fork choice are held separately and
+/// only considered once the block arrives (see `queue` and `dequeue`). At most one such message
+/// (the one with the highest epoch) is held for each validator, and messages from before
+/// finalization are dropped by `prune`, so the queue cannot grow without bound.
+#[derive(Debug, Default)]
+pub struct LatestMessages {
+    latest: HashMap<u64, LatestMessage>,
+    /// The newest message of each validator for a block which is not yet known.
+    pending: HashMap<u64, LatestMessage>,
+    /// The validators with a message in `pending` for each unknown block.
+    pending_by_root: HashMap<Hash256, HashSet<u64>>,
+}
+
+impl LatestMessages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the block root of the latest message of the given validator.
+    pub fn get(&self, validator_index: u64) -> Option<&Hash256> {
+        self.latest
+            .get(&validator_index)
+            .map(|message| &message.block_root)
+    }
+
+    /// Records a vote for `block_root` as the latest message of the validator, unless a message
+    /// from the same or a later epoch is already known.
+    ///
+    /// Returns `true` if the message was recorded.
+    pub fn insert(&mut self, validator_index: u64, block_root: Hash256, epoch: Epoch) -> bool {
+        let message = LatestMessage { block_root, epoch };
+
+        // A queued message which is not newer can no longer become the latest message.
+        if self
+            .pending
+            .get(&validator_index)
+            .map_or(false, |pending| pending.epoch <= epoch)
+        {
+            self.remove_pending(validator_index);
+        }
+
+        match self.latest.get_mut(&validator_index) {
+            Some(latest) if latest.epoch >= epoch => false,
+            Some(latest) => {
+                trace!("Updating old attestation: {:?}", latest);
+                *latest = message;
+                true
+            }
+            None => {
+                self.latest.insert(validator_index, message);
+                true
+            }
+        }
+    }
+
+    /// Holds a vote for a block which is not yet known until `dequeue` is called for the block.
+    ///
+    /// The vote replaces any queued vote of the validator from an earlier epoch, and is ignored if
+    /// the validator already has a latest or queued message from the same or a later epoch.
+    pub fn queue(&mut self, validator_index: u64, block_root: Hash256, epoch: Epoch) {
+        let is_newer = |message: Option<&LatestMessage>| message.map_or(true, |m| m.epoch < epoch);
+        if !is_newer(self.latest.get(&validator_index))
+            || !is_newer(self.pending.get(&validator_index))
+        {
+            return;
+        }
+
+        self.remove_pending(validator_index);
+        self.pending
+            .insert(validator_index, LatestMessage { block_root, epoch });
+        self.pending_by_root
+            .entry(block_root)
+            .or_insert_with(HashSet::new)
+            .insert(validator_index);
+    }
+
+    /// Inserts all votes which were queued for `block_root`, which is now known.
+    pub fn dequeue(&mut self, block_root: &Hash256) {
+        if let Some(validators) = self.pending_by_root.remove(block_root) {
+            trace!(
+                "Applying {} queued votes for block: {}",
+                validators.len(),
+                block_root
+            );
+            for validator_index in validators {
+                if let Some(message) = self.pending.remove(&validator_index) {
+                    self.insert(validator_index, message.block_root, message.epoch);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of votes which are queued for unknown blocks.
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Removes all messages (latest or queued) for the given blocks, and all queued messages from
+    /// before `finalized_epoch`.
+    ///
+    /// A block voted for before finalization which is not yet known can only be from an abandoned
+    /// fork, so it will never be added.
+    pub fn prune(&mut self, block_roots: &HashSet<&Hash256>, finalized_epoch: Epoch) {
+        self.latest
+            .retain(|_, message| !block_roots.contains(&message.block_root));
+
+        let expired: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, message)| {
+                block_roots.contains(&message.block_root) || message.epoch < finalized_epoch
+            })
+            .map(|(validator_index, _)| *validator_index)
+            .collect();
+        for validator_index in expired {
+            self.remove_pending(validator_index);
+        }
+    }
+
+    /// Removes the queued message of the validator, if any.
+    fn remove_pending(&mut self, validator_index: u64) {
+        if let Some(message) = self.pending.remove(&validator_index) {
+            if let Some(validators) = self.pending_by_root.get_mut(&message.block_root) {
+                validators.remove(&validator_index);
+                if validators.is_empty() {
+                    self.pending_by_root.remove(&message.block_root);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_latest_epoch() {
+        let mut messages = LatestMessages::new();
+        let (a, b) = (Hash256::from_slice(&[1; 32]), Hash256::from_slice(&[2; 32]));
+
+        assert!(messages.insert(0, a, Epoch::new(2)));
+        assert!(!messages.insert(0, b, Epoch::new(1)));
+        assert!(!messages.insert(0, b, Epoch::new(2)));
+        assert_eq!(messages.get(0), Some(&a));
+
+        assert!(messages.insert(0, b, Epoch::new(3)));
+        assert_eq!(messages.get(0), Some(&b));
+        assert_eq!(messages.get(1), None);
+    }
+
+    #[test]
+    fn test_queued_until_block_is_known() {
+        let mut messages = LatestMessages::new();
+        let (a, b) = (Hash256::from_slice(&[1; 32]), Hash256::from_slice(&[2; 32]));
+
+        messages.insert(0, a, Epoch::new(1));
+        messages.queue(0, b, Epoch::new(2));
+        assert_eq!(messages.get(0), Some(&a));
+
+        messages.dequeue(&b);
+        assert_eq!(messages.get(0), Some(&b));
+
+        messages.queue(1, a, Epoch::new(1));
+        messages.prune(&[&a].iter().cloned().collect(), Epoch::new(0));
+        messages.dequeue(&a);
+        assert_eq!(messages.get(1), None);
+    }
+
+    #[test]
+    fn test_one_queued_vote_per_validator() {
+        let mut messages = LatestMessages::new();
+        let (a, b, c) = (
+            Hash256::from_slice(&[1; 32]),
+            Hash256::from_slice(&[2; 32]),
+            Hash256::from_slice(&[3; 32]),
+        );
+
+        messages.queue(0, a, Epoch::new(2));
+        // An older vote is ignored, a newer vote replaces the queued vote.
+        messages.queue(0, b, Epoch::new(1));
+        messages.queue(0, c, Epoch::new(3));
+        assert_eq!(messages.num_pending(), 1);
+
+        messages.dequeue(&a);
+        messages.dequeue(&b);
+        assert_eq!(messages.get(0), None);
+        messages.dequeue(&c);
+        assert_eq!(messages.get(0), Some(&c));
+        assert_eq!(messages.num_pending(), 0);
+
+        // A vote which is older than the latest message is never queued.
+        messages.queue(0, a, Epoch::new(3));
+        assert_eq!(messages.num_pending(), 0);
+
+        // A newer latest message replaces a queued vote.
+        messages.queue(0, a, Epoch::new(4));
+        messages.insert(0, b, Epoch::new(4));
+        assert_eq!(messages.num_pending(), 0);
+    }
+
+    #[test]
+    fn test_queued_votes_expire_at_finalization() {
+        let mut messages = LatestMessages::new();
+        let (a, b) = (Hash256::from_slice(&[1; 32]), Hash256::from_slice(&[2; 32]));
+
+        messages.queue(0, a, Epoch::new(1));
+        messages.queue(1, b, Epoch::new(3));
+
+        messages.prune(&HashSet::new(), Epoch::new(2));
+        assert_eq!(messages.num_pending(), 1);
+
+        messages.dequeue(&a);
+        messages.dequeue(&b);
+        assert_eq!(messages.get(0), None);
+        assert_eq!(messages.get(1), Some(&b));
+    }
+}
@@ -1,8 +1,8 @@
 //! This crate stores the various implementations of fork-choice rules that can be used for the
 //! beacon blockchain.
 //!
-//! There are three implementations. One is the naive longest chain rule (primarily for testing
-//! purposes). The other two are proposed implementations of the LMD-GHOST fork-choice rule with various forms of optimisation.
+//! One is the naive longest chain rule (primarily for testing purposes). The others are proposed
+//! implementations of the LMD-GHOST fork-choice rule with various forms of optimisation.
 //!
 //! The current implementations are:
 //! - [`longest-chain`]: Simplistic longest-chain fork choice - primarily for testing, **not for
@@ -11,27 +11,39 @@
 //! specifications (https://github.com/ethereum/eth2.0-specs/blob/v0.1/specs/core/0_beacon-chain.md#get_block_root).
 //! - [`bitwise_lmd_ghost`]: This is an optimised version of bitwise LMD-GHOST as proposed
 //! by Vitalik. The reference implementation can be found at: https://github.com/ethereum/research/blob/master/ghost/ghost.py
+//! - [`proto_array_lmd_ghost`]: LMD-GHOST over a flat array of blocks, which is updated
+//! incrementally as votes change rather than recomputed for each head lookup.
 //!
 //! [`longest-chain`]: struct.LongestChain.html
 //! [`slow_lmd_ghost`]: struct.SlowLmdGhost.html
 //! [`bitwise_lmd_ghost`]: struct.OptimisedLmdGhost.html
+//! [`proto_array_lmd_ghost`]: struct.ProtoArrayLMDGhost.html
 
 extern crate db;
 extern crate ssz;
 extern crate types;
 
 pub mod bitwise_lmd_ghost;
+pub mod block_tree;
+pub mod justification;
+pub mod latest_messages;
 pub mod longest_chain;
 pub mod optimized_lmd_ghost;
+pub mod proto_array_lmd_ghost;
 pub mod slow_lmd_ghost;
 
-use db::stores::BeaconBlockAtSlotError;
-use db::DBError;
-use types::{BeaconBlock, ChainSpec, Hash256};
+use db::{stores::BeaconBlockStore, ClientDB, DBError};
+use state_processing::per_epoch_processing::get_attestation_participants::get_attestation_participants;
+use std::sync::Arc;
+use types::{Attestation, BeaconBlock, BeaconState, BeaconStateError, ChainSpec, Epoch, Hash256};
 
 pub use bitwise_lmd_ghost::BitwiseLMDGhost;
+pub use block_tree::{BlockTree, BlockTreeNode};
+pub use justification::{Justification, JustifiedCheckpoint};
+pub use latest_messages::LatestMessages;
 pub use longest_chain::LongestChain;
 pub use optimized_lmd_ghost::OptimizedLMDGhost;
+pub use proto_array_lmd_ghost::ProtoArrayLMDGhost;
 pub use slow_lmd_ghost::SlowLMDGhost;
 
 /// Defines the interface for Fork Choices. Each Fork choice will define their own data structures
@@ -40,27 +52,133 @@ pub use slow_lmd_ghost::SlowLMDGhost;
 pub trait ForkChoice: Send + Sync {
     /// Called when a block has been added. Allows generic block-level data structures to be
     /// built for a given fork-choice.
+    ///
+    /// `justification` is read from the post-state of the block.
     fn add_block(
         &mut self,
         block: &BeaconBlock,
         block_hash: &Hash256,
+        justification: Justification,
         spec: &ChainSpec,
     ) -> Result<(), ForkChoiceError>;
-    /// Called when an attestation has been added. Allows generic attestation-level data structures to be built for a given fork choice.
-    // This can be generalised to a full attestation if required later.
+    /// Called when a verified attestation has been received, either from the network or in a
+    /// block. Adds a vote from each of the participating validators.
+    ///
+    /// The participants are read from the committees of `state`, which must have the epoch cache
+    /// built for the epoch of the attestation.
     fn add_attestation(
+        &mut self,
+        attestation: &Attestation,
+        state: &BeaconState,
+        spec: &ChainSpec,
+    ) -> Result<(), ForkChoiceError> {
+        let participants = get_attestation_participants(
+            state,
+            &attestation.data,
+            &attestation.aggregation_bitfield,
+            spec,
+        )?;
+        let epoch = attestation.data.slot.epoch(spec.slots_per_epoch);
+
+        for validator_index in participants {
+            self.add_vote(
+                validator_index as u64,
+                &attestation.data.beacon_block_root,
+                epoch,
+                spec,
+            )?;
+        }
+        Ok(())
+    }
+    /// Called for each validator which has voted for a block in an attestation from the given
+    /// epoch. Allows generic attestation-level data structures to be built for a given fork
+    /// choice.
+    ///
+    /// Only the latest message (by epoch) of each validator is counted. Votes for blocks which
+    /// are not yet known must be held until the block is added.
+    fn add_vote(
         &mut self,
         validator_index: u64,
         target_block_hash: &Hash256,
+        epoch: Epoch,
         spec: &ChainSpec,
     ) -> Result<(), ForkChoiceError>;
     /// The fork-choice algorithm to find the current canonical head of the chain.
-    // TODO: Remove the justified_start_block parameter and make it internal
+    ///
+    /// The head is a descendant of the `justified` block, which lies upon a branch that agrees
+    /// with the justification of `justified` (unless no such branch exists). Votes are weighted
+    /// by the balances of `justified`.
     fn find_head(
         &mut self,
-        justified_start_block: &Hash256,
+        justified: &JustifiedCheckpoint,
         spec: &ChainSpec,
     ) -> Result<Hash256, ForkChoiceError>;
+    /// Called when blocks have been removed from the database (e.g., abandoned forks which were
+    /// pruned after finalization). Removes all references to the blocks (including attestation
+    /// targets) from the fork-choice data structures.
+    ///
+    /// Votes for unknown blocks from before `finalized_epoch` are also dropped, as those blocks
+    /// can no longer be added.
+    fn prune(
+        &mut self,
+        block_hashes: &[Hash256],
+        finalized_epoch: Epoch,
+    ) -> Result<(), ForkChoiceError>;
+    /// Returns the `justified` block and its known descendants, with the weight of each block
+    /// (using the balances of `justified`) and the validators whose latest vote is for it.
+    fn block_tree(&self, justified: &JustifiedCheckpoint) -> Result<BlockTree, ForkChoiceError>;
+}
+
+/// Allows a fork choice to be chosen at runtime (see `ForkChoiceAlgorithm::build`).
+impl<F: ForkChoice + ?Sized> ForkChoice for Box<F> {
+    fn add_block(
+        &mut self,
+        block: &BeaconBlock,
+        block_hash: &Hash256,
+        justification: Justification,
+        spec: &ChainSpec,
+    ) -> Result<(), ForkChoiceError> {
+        (**self).add_block(block, block_hash, justification, spec)
+    }
+
+    fn add_attestation(
+        &mut self,
+        attestation: &Attestation,
+        state: &BeaconState,
+        spec: &ChainSpec,
+    ) -> Result<(), ForkChoiceError> {
+        (**self).add_attestation(attestation, state, spec)
+    }
+
+    fn add_vote(
+        &mut self,
+        validator_index: u64,
+        target_block_hash: &Hash256,
+        epoch: Epoch,
+        spec: &ChainSpec,
+    ) -> Result<(), ForkChoiceError> {
+        (**self).add_vote(validator_index, target_block_hash, epoch, spec)
+    }
+
+    fn find_head(
+        &mut self,
+        justified: &JustifiedCheckpoint,
+        spec: &ChainSpec,
+    ) -> Result<Hash256, ForkChoiceError> {
+        (**self).find_head(justified, spec)
+    }
+
+    fn prune(
+        &mut self,
+        block_hashes: &[Hash256],
+        finalized_epoch: Epoch,
+    ) -> Result<(), ForkChoiceError> {
+        (**self).prune(block_hashes, finalized_epoch)
+    }
+
+    fn block_tree(&self, justified: &JustifiedCheckpoint) -> Result<BlockTree, ForkChoiceError> {
+        (**self).block_tree(justified)
+    }
 }
 
 /// Possible fork choice errors that can occur.
@@ -73,6 +191,9 @@ pub enum ForkChoiceError {
     ChildrenNotFound,
     StorageError(String),
     HeadNotFound,
+    /// Applying the votes would take the weight of the block with this root out of bounds.
+    InvalidNodeWeight(Hash256),
+    BeaconStateError(BeaconStateError),
 }
 
 impl From<DBError> for ForkChoiceError {
@@ -81,17 +202,9 @@ impl From<DBError> for ForkChoiceError {
     }
 }
 
-impl From<BeaconBlockAtSlotError> for ForkChoiceError {
-    fn from(e: BeaconBlockAtSlotError) -> ForkChoiceError {
-        match e {
-            BeaconBlockAtSlotError::UnknownBeaconBlock(hash) => {
-                ForkChoiceError::MissingBeaconBlock(hash)
-            }
-            BeaconBlockAtSlotError::InvalidBeaconBlock(hash) => {
-                ForkChoiceError::MissingBeaconBlock(hash)
-            }
-            BeaconBlockAtSlotError::DBError(string) => ForkChoiceError::StorageError(string),
-        }
+impl From<BeaconStateError> for ForkChoiceError {
+    fn from(e: BeaconStateError) -> ForkChoiceError {
+        ForkChoiceError::BeaconStateError(e)
     }
 }
 
@@ -106,4 +219,24 @@ pub enum ForkChoiceAlgorithm {
     BitwiseLMDGhost,
     /// An optimised implementation of LMD ghost.
     OptimizedLMDGhost,
+    /// LMD ghost over a proto-array, with node weights updated incrementally from vote changes.
+    ProtoArrayLMDGhost,
+}
+
+impl ForkChoiceAlgorithm {
+    /// Instantiates the fork choice for this algorithm, reading blocks from `block_store`.
+    pub fn build<T: ClientDB + 'static>(
+        &self,
+        block_store: Arc<BeaconBlockStore<T>>,
+    ) -> Box<dyn ForkChoice> {
+        match self {
+            ForkChoiceAlgorithm::LongestChain => Box::new(LongestChain::new(block_store)),
+            ForkChoiceAlgorithm::SlowLMDGhost => Box::new(SlowLMDGhost::new(block_store)),
+            ForkChoiceAlgorithm::BitwiseLMDGhost => Box::new(BitwiseLMDGhost::new(block_store)),
+            ForkChoiceAlgorithm::OptimizedLMDGhost => Box::new(OptimizedLMDGhost::new(block_store)),
+            ForkChoiceAlgorithm::ProtoArrayLMDGhost => {
+                Box::new(ProtoArrayLMDGhost::new(block_store))
+            }
+        }
+    }
 }
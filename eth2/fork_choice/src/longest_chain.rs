@@ -1,7 +1,8 @@
-use crate::{ForkChoice, ForkChoiceError};
+use crate::{BlockTree, ForkChoice, ForkChoiceError, Justification, JustifiedCheckpoint};
 use db::{stores::BeaconBlockStore, ClientDB};
+use std::collections::HashMap;
 use std::sync::Arc;
-use types::{BeaconBlock, ChainSpec, Hash256, Slot};
+use types::{BeaconBlock, ChainSpec, Epoch, Hash256, Slot};
 
 pub struct LongestChain<T>
 where
@@ -30,6 +31,7 @@ impl<T: ClientDB + Sized> ForkChoice for LongestChain<T> {
         &mut self,
         block: &BeaconBlock,
         block_hash: &Hash256,
+        _: Justification,
         _: &ChainSpec,
     ) -> Result<(), ForkChoiceError> {
         // add the block hash to head_block_hashes removing the parent if it exists
@@ -39,17 +41,22 @@ impl<T: ClientDB + Sized> ForkChoice for LongestChain<T> {
         Ok(())
     }
 
-    fn add_attestation(
+    fn add_vote(
         &mut self,
         _: u64,
         _: &Hash256,
+        _: Epoch,
         _: &ChainSpec,
     ) -> Result<(), ForkChoiceError> {
         // do nothing
         Ok(())
     }
 
-    fn find_head(&mut self, _: &Hash256, _: &ChainSpec) -> Result<Hash256, ForkChoiceError> {
+    fn find_head(
+        &mut self,
+        _: &JustifiedCheckpoint,
+        _: &ChainSpec,
+    ) -> Result<Hash256, ForkChoiceError> {
         let mut head_blocks: Vec<(usize, BeaconBlock)> = vec![];
         /*
          * Load all the head_block hashes from the DB as SszBeaconBlocks.
@@ -105,4 +112,45 @@ impl<T: ClientDB + Sized> ForkChoice for LongestChain<T> {
 
         Ok(self.head_block_hashes[head_index.unwrap()])
     }
+
+    fn prune(&mut self, block_hashes: &[Hash256], _: Epoch) -> Result<(), ForkChoiceError> {
+        self.head_block_hashes
+            .retain(|hash| !block_hashes.contains(hash));
+        Ok(())
+    }
+
+    /// Votes are not used by this fork choice, so the tree has no weights.
+    fn block_tree(&self, justified: &JustifiedCheckpoint) -> Result<BlockTree, ForkChoiceError> {
+        let get_block = |block_hash: &Hash256| -> Result<BeaconBlock, ForkChoiceError> {
+            self.block_store
+                .get_deserialized(block_hash)?
+                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*block_hash))
+        };
+        let justified_slot = get_block(&justified.root)?.slot;
+
+        // walk back from each head to the justified block, ignoring heads which do not descend
+        // from it.
+        let mut children: HashMap<Hash256, Vec<Hash256>> = HashMap::new();
+        for head in &self.head_block_hashes {
+            let mut branch = vec![];
+            let mut block_hash = *head;
+            let mut block = get_block(&block_hash)?;
+            while block.slot > justified_slot {
+                branch.push((block.previous_block_root, block_hash));
+                block_hash = block.previous_block_root;
+                block = get_block(&block_hash)?;
+            }
+
+            if block_hash == justified.root {
+                for (parent, child) in branch {
+                    let siblings = children.entry(parent).or_insert_with(|| vec![]);
+                    if !siblings.contains(&child) {
+                        siblings.push(child);
+                    }
+                }
+            }
+        }
+
+        BlockTree::from_children(justified, &children, |_| None, &self.block_store)
+    }
 }
@@ -1,16 +1,16 @@
 //! The optimised bitwise LMD-GHOST fork choice rule.
 extern crate bit_vec;
 
-use crate::{ForkChoice, ForkChoiceError};
-use db::{
-    stores::{BeaconBlockStore, BeaconStateStore},
-    ClientDB,
+use crate::justification::viable_blocks;
+use crate::{
+    BlockTree, ForkChoice, ForkChoiceError, Justification, JustifiedCheckpoint, LatestMessages,
 };
+use db::{stores::BeaconBlockStore, ClientDB};
 use log::{debug, trace};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use types::{BeaconBlock, ChainSpec, Hash256, Slot, SlotHeight};
+use types::{BeaconBlock, ChainSpec, Epoch, Hash256, SlotHeight};
 
 //TODO: Pruning - Children
 //TODO: Handle Syncing
@@ -42,13 +42,12 @@ pub struct OptimizedLMDGhost<T: ClientDB + Sized> {
     ancestors: Vec<HashMap<Hash256, Hash256>>,
     /// Stores the children for any given parent.
     children: HashMap<Hash256, Vec<Hash256>>,
-    /// The latest attestation targets as a map of validator index to block hash.
-    //TODO: Could this be a fixed size vec
-    latest_attestation_targets: HashMap<u64, Hash256>,
+    /// The latest attestation targets of each validator.
+    latest_messages: LatestMessages,
+    /// The justification of the post-state of each block.
+    justifications: HashMap<Hash256, Justification>,
     /// Block storage access.
     block_store: Arc<BeaconBlockStore<T>>,
-    /// State storage access.
-    state_store: Arc<BeaconStateStore<T>>,
     max_known_height: SlotHeight,
 }
 
@@ -56,56 +55,32 @@ impl<T> OptimizedLMDGhost<T>
 where
     T: ClientDB + Sized,
 {
-    pub fn new(
-        block_store: Arc<BeaconBlockStore<T>>,
-        state_store: Arc<BeaconStateStore<T>>,
-    ) -> Self {
+    pub fn new(block_store: Arc<BeaconBlockStore<T>>) -> Self {
         OptimizedLMDGhost {
             cache: HashMap::new(),
             ancestors: vec![HashMap::new(); 16],
-            latest_attestation_targets: HashMap::new(),
+            latest_messages: LatestMessages::new(),
             children: HashMap::new(),
             max_known_height: SlotHeight::new(0),
+            justifications: HashMap::new(),
             block_store,
-            state_store,
         }
     }
 
-    /// Finds the latest votes weighted by validator balance. Returns a hashmap of block_hash to
-    /// weighted votes.
-    pub fn get_latest_votes(
-        &self,
-        state_root: &Hash256,
-        block_slot: Slot,
-        spec: &ChainSpec,
-    ) -> Result<HashMap<Hash256, u64>, ForkChoiceError> {
-        // get latest votes
-        // Note: Votes are weighted by min(balance, MAX_DEPOSIT_AMOUNT) //
-        // FORK_CHOICE_BALANCE_INCREMENT
+    /// Finds the latest votes weighted by the validator `balances`. Returns a hashmap of
+    /// block_hash to weighted votes.
+    pub fn get_latest_votes(&self, balances: &[u64]) -> HashMap<Hash256, u64> {
         // build a hashmap of block_hash to weighted votes
         let mut latest_votes: HashMap<Hash256, u64> = HashMap::new();
-        // gets the current weighted votes
-        let current_state = self
-            .state_store
-            .get_deserialized(&state_root)?
-            .ok_or_else(|| ForkChoiceError::MissingBeaconState(*state_root))?;
-
-        let active_validator_indices =
-            current_state.get_active_validator_indices(block_slot.epoch(spec.slots_per_epoch));
-
-        for index in active_validator_indices {
-            let balance = std::cmp::min(
-                current_state.validator_balances[index],
-                spec.max_deposit_amount,
-            ) / spec.fork_choice_balance_increment;
-            if balance > 0 {
-                if let Some(target) = self.latest_attestation_targets.get(&(index as u64)) {
+        for (index, balance) in balances.iter().enumerate() {
+            if *balance > 0 {
+                if let Some(target) = self.latest_messages.get(index as u64) {
                     *latest_votes.entry(*target).or_insert_with(|| 0) += balance;
                 }
             }
         }
         trace!("Latest votes: {:?}", latest_votes);
-        Ok(latest_votes)
+        latest_votes
     }
 
     /// Gets the ancestor at a given height `at_height` of a block specified by `block_hash`.
@@ -216,6 +191,7 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
         &mut self,
         block: &BeaconBlock,
         block_hash: &Hash256,
+        justification: Justification,
         spec: &ChainSpec,
     ) -> Result<(), ForkChoiceError> {
         // get the height of the parent
@@ -240,56 +216,43 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
             if parent_height % (1 << index) == 0 {
                 self.ancestors[index].insert(*block_hash, *parent_hash);
             } else {
-                // TODO: This is unsafe. Will panic if parent_hash doesn't exist. Using it for debugging
-                let parent_ancestor = self.ancestors[index][parent_hash];
+                // The parent of the finalized block is unknown when fork choice is rebuilt from
+                // it. Ancestors are only looked up above the finalized block, so the parent may
+                // stand in for its own ancestors.
+                let parent_ancestor = self.ancestors[index]
+                    .get(parent_hash)
+                    .cloned()
+                    .unwrap_or(*parent_hash);
                 self.ancestors[index].insert(*block_hash, parent_ancestor);
             }
         }
         // update the max height
         self.max_known_height = std::cmp::max(self.max_known_height, parent_height + 1);
+        self.justifications.insert(*block_hash, justification);
+        // apply any votes which arrived before the block
+        self.latest_messages.dequeue(block_hash);
         Ok(())
     }
 
-    fn add_attestation(
+    fn add_vote(
         &mut self,
         validator_index: u64,
         target_block_root: &Hash256,
-        spec: &ChainSpec,
+        epoch: Epoch,
+        _: &ChainSpec,
     ) -> Result<(), ForkChoiceError> {
-        // simply add the attestation to the latest_attestation_target if the block_height is
-        // larger
         trace!(
             "Adding attestation of validator: {:?} for block: {}",
             validator_index,
             target_block_root
         );
-        let attestation_target = self
-            .latest_attestation_targets
-            .entry(validator_index)
-            .or_insert_with(|| *target_block_root);
-        // if we already have a value
-        if attestation_target != target_block_root {
-            trace!("Old attestation found: {:?}", attestation_target);
-            // get the height of the target block
-            let block_height = self
-                .block_store
-                .get_deserialized(&target_block_root)?
-                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*target_block_root))?
-                .slot
-                .height(spec.genesis_slot);
-
-            // get the height of the past target block
-            let past_block_height = self
-                .block_store
-                .get_deserialized(&attestation_target)?
-                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*attestation_target))?
-                .slot
-                .height(spec.genesis_slot);
-            // update the attestation only if the new target is higher
-            if past_block_height < block_height {
-                trace!("Updating old attestation");
-                *attestation_target = *target_block_root;
-            }
+        // the vote is applied when the block is added if the block is not yet known
+        if self.block_store.exists(target_block_root)? {
+            self.latest_messages
+                .insert(validator_index, *target_block_root, epoch);
+        } else {
+            self.latest_messages
+                .queue(validator_index, *target_block_root, epoch);
         }
         Ok(())
     }
@@ -297,9 +260,10 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
     /// Perform lmd_ghost on the current chain to find the head.
     fn find_head(
         &mut self,
-        justified_block_start: &Hash256,
+        justified: &JustifiedCheckpoint,
         spec: &ChainSpec,
     ) -> Result<Hash256, ForkChoiceError> {
+        let justified_block_start = &justified.root;
         debug!(
             "Starting optimised fork choice at block: {}",
             justified_block_start
@@ -309,13 +273,22 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
             .get_deserialized(&justified_block_start)?
             .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*justified_block_start))?;
 
-        let block_slot = block.slot;
-        let state_root = block.state_root;
-        let mut block_height = block_slot.height(spec.genesis_slot);
+        let mut block_height = block.slot.height(spec.genesis_slot);
 
         let mut current_head = *justified_block_start;
 
-        let mut latest_votes = self.get_latest_votes(&state_root, block_slot, spec)?;
+        // only descend into branches which agree with the justified checkpoint
+        let viable = viable_blocks(
+            current_head,
+            &self.children,
+            &self.justifications,
+            justified,
+            spec,
+        );
+        let is_viable =
+            |block_hash: &Hash256| viable.as_ref().map_or(true, |v| v.contains(block_hash));
+
+        let mut latest_votes = self.get_latest_votes(&justified.balances);
 
         // remove any votes that don't relate to our current head.
         latest_votes
@@ -328,14 +301,15 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
                 current_head,
                 latest_votes.len()
             );
-            // if there are no children, we are done, return the current_head
-            let children = match self.children.get(&current_head) {
-                Some(children) => children.clone(),
-                None => {
-                    debug!("Head found: {}", current_head);
-                    return Ok(current_head);
-                }
+            // if there are no viable children, we are done, return the current_head
+            let children: Vec<Hash256> = match self.children.get(&current_head) {
+                Some(children) => children.iter().cloned().filter(&is_viable).collect(),
+                None => vec![],
             };
+            if children.is_empty() {
+                debug!("Head found: {}", current_head);
+                return Ok(current_head);
+            }
 
             // logarithmic lookup blocks to see if there are obvious winners, if so,
             // progress to the next iteration.
@@ -348,8 +322,10 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
                     block_height - (block_height % step) + step,
                     spec,
                 ) {
-                    current_head = clear_winner;
-                    break;
+                    if is_viable(&clear_winner) {
+                        current_head = clear_winner;
+                        break;
+                    }
                 }
                 step /= 2;
             }
@@ -375,6 +351,7 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
                         *child_votes.entry(child).or_insert_with(|| 0) += vote;
                     }
                 }
+                child_votes.retain(|child, _| is_viable(child));
                 // check if we have votes of children, if not select the smallest hash child
                 if child_votes.is_empty() {
                     current_head = *children
@@ -417,6 +394,41 @@ impl<T: ClientDB + Sized> ForkChoice for OptimizedLMDGhost<T> {
             });
         }
     }
+
+    fn prune(
+        &mut self,
+        block_hashes: &[Hash256],
+        finalized_epoch: Epoch,
+    ) -> Result<(), ForkChoiceError> {
+        let pruned: HashSet<&Hash256> = block_hashes.iter().collect();
+
+        for ancestors in self.ancestors.iter_mut() {
+            ancestors.retain(|block_hash, _| !pruned.contains(block_hash));
+        }
+        self.cache.retain(|key, ancestor| {
+            !pruned.contains(&key.block_hash) && !pruned.contains(ancestor)
+        });
+        // `find_head` expects every entry in `children` to be non-empty.
+        self.children.retain(|parent, children| {
+            children.retain(|child| !pruned.contains(child));
+            !pruned.contains(parent) && !children.is_empty()
+        });
+        self.latest_messages.prune(&pruned, finalized_epoch);
+        self.justifications
+            .retain(|block_hash, _| !pruned.contains(block_hash));
+
+        debug!("Pruned {} blocks from fork choice", pruned.len());
+        Ok(())
+    }
+
+    fn block_tree(&self, justified: &JustifiedCheckpoint) -> Result<BlockTree, ForkChoiceError> {
+        BlockTree::from_children(
+            justified,
+            &self.children,
+            |validator_index| self.latest_messages.get(validator_index).cloned(),
+            &self.block_store,
+        )
+    }
 }
 
 /// Type for storing blocks in a memory cache. Key is comprised of block-hash plus the height.
@@ -0,0 +1,436 @@
+//! An LMD-GHOST fork choice rule built upon a flat "proto-array" of blocks.
+//!
+//! Blocks are stored in a vector in the order they were added, so a parent always appears before
+//! its children. Each node holds the total weight of the votes for itself and its descendants and
+//! a pointer to its best descendant. Instead of recounting every vote when finding the head, only
+//! the change in each validator's vote (or balance) since the last run is applied. The changes
+//! are propagated to the parents in a single backwards pass over the array.
+use crate::{BlockTree, ForkChoice, ForkChoiceError, Justification, JustifiedCheckpoint};
+use db::{stores::BeaconBlockStore, ClientDB};
+use log::{debug, trace};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use types::{BeaconBlock, ChainSpec, Epoch, Hash256};
+
+/// A block in the proto-array.
+#[derive(Debug, Clone, PartialEq)]
+struct ProtoNode {
+    root: Hash256,
+    /// The justification of the post-state of the block, if known.
+    justification: Option<Justification>,
+    /// The index of the parent, or `None` if the parent is not in the array.
+    parent: Option<usize>,
+    /// The sum of the weighted votes for this block and all of its descendants.
+    weight: u64,
+    /// The index of the child with the highest weight.
+    best_child: Option<usize>,
+    /// The index of the head of the chain through `best_child`.
+    best_descendant: Option<usize>,
+}
+
+/// The vote of a single validator.
+///
+/// `current_root` is the vote which is included in the node weights, `next_root` is the latest
+/// vote received (which may be for a block that is not yet known). A zero hash represents no vote.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct VoteTracker {
+    current_root: Hash256,
+    next_root: Hash256,
+    next_epoch: Epoch,
+}
+
+/// Stores the necessary data structures to run the proto-array lmd ghost algorithm.
+pub struct ProtoArrayLMDGhost<T: ClientDB + Sized> {
+    /// All known blocks, parents before children.
+    nodes: Vec<ProtoNode>,
+    /// Maps a block root to its index in `nodes`.
+    indices: HashMap<Hash256, usize>,
+    /// The latest votes, indexed by validator index.
+    votes: Vec<VoteTracker>,
+    /// The weighted balances which are included in the node weights, indexed by validator index.
+    balances: Vec<u64>,
+    /// Block storage access.
+    block_store: Arc<BeaconBlockStore<T>>,
+}
+
+impl<T> ProtoArrayLMDGhost<T>
+where
+    T: ClientDB + Sized,
+{
+    pub fn new(block_store: Arc<BeaconBlockStore<T>>) -> Self {
+        ProtoArrayLMDGhost {
+            nodes: vec![],
+            indices: HashMap::new(),
+            votes: vec![],
+            balances: vec![],
+            block_store,
+        }
+    }
+
+    /// Returns the index of the block with the given root, adding it to the array (without a
+    /// parent) if it is only known to the block store.
+    ///
+    /// Blocks which are not added through `add_block` (e.g., the genesis block) are the roots of
+    /// the tree.
+    fn get_or_insert_index(&mut self, block_hash: &Hash256) -> Result<usize, ForkChoiceError> {
+        if let Some(index) = self.indices.get(block_hash) {
+            return Ok(*index);
+        }
+
+        if !self.block_store.exists(block_hash)? {
+            return Err(ForkChoiceError::MissingBeaconBlock(*block_hash));
+        }
+
+        Ok(self.insert_node(*block_hash, None, None))
+    }
+
+    fn insert_node(
+        &mut self,
+        root: Hash256,
+        justification: Option<Justification>,
+        parent: Option<usize>,
+    ) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(ProtoNode {
+            root,
+            justification,
+            parent,
+            weight: 0,
+            best_child: None,
+            best_descendant: None,
+        });
+        self.indices.insert(root, index);
+        index
+    }
+
+    /// Returns the change in weight of each node caused by votes and balances which have changed
+    /// since the last call. Updates `votes` so each applied vote becomes the current vote.
+    ///
+    /// Votes for blocks which are not yet in the array are left pending.
+    fn compute_deltas(&mut self, new_balances: &[u64]) -> Vec<i64> {
+        let mut deltas = vec![0i64; self.nodes.len()];
+
+        for (validator_index, vote) in self.votes.iter_mut().enumerate() {
+            let old_balance = self.balances.get(validator_index).cloned().unwrap_or(0);
+            let new_balance = new_balances.get(validator_index).cloned().unwrap_or(0);
+
+            let next_root = if self.indices.contains_key(&vote.next_root) {
+                vote.next_root
+            } else {
+                vote.current_root
+            };
+
+            if vote.current_root != next_root || old_balance != new_balance {
+                if let Some(index) = self.indices.get(&vote.current_root) {
+                    deltas[*index] -= old_balance as i64;
+                }
+                if let Some(index) = self.indices.get(&next_root) {
+                    deltas[*index] += new_balance as i64;
+                }
+                vote.current_root = next_root;
+            }
+        }
+
+        deltas
+    }
+
+    /// Applies `deltas` to the node weights.
+    fn apply_score_changes(&mut self, mut deltas: Vec<i64>) -> Result<(), ForkChoiceError> {
+        // Children always follow their parents, so iterating backwards visits every child before
+        // its parent.
+        for index in (0..self.nodes.len()).rev() {
+            let delta = deltas[index];
+            let node = &mut self.nodes[index];
+            node.weight = if delta < 0 {
+                node.weight.checked_sub(delta.wrapping_neg() as u64)
+            } else {
+                node.weight.checked_add(delta as u64)
+            }
+            .ok_or_else(|| ForkChoiceError::InvalidNodeWeight(node.root))?;
+
+            if let Some(parent) = node.parent {
+                deltas[parent] = deltas[parent]
+                    .checked_add(delta)
+                    .ok_or_else(|| ForkChoiceError::InvalidNodeWeight(node.root))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the best child and best descendant of every node, given the present weights.
+    ///
+    /// Only children which lead to a head that is viable for the `justified` checkpoint are
+    /// considered. Each node is compared against the present best child of its parent, so the
+    /// tree is not rebuilt from scratch. If a best child stops leading to a viable head its
+    /// siblings may need to be reconsidered; only then are the best children of all nodes
+    /// recomputed.
+    fn update_best_descendants(&mut self, justified: &JustifiedCheckpoint, spec: &ChainSpec) {
+        let mut demoted = false;
+
+        // The best descendant of each child is known before the child is compared with its
+        // siblings.
+        for index in (0..self.nodes.len()).rev() {
+            if let Some(parent) = self.nodes[index].parent {
+                demoted |= self.maybe_update_best_child(parent, index, justified, spec);
+            }
+        }
+
+        if demoted {
+            trace!("A best child no longer leads to a viable head, recomputing best children");
+            for node in self.nodes.iter_mut() {
+                node.best_child = None;
+                node.best_descendant = None;
+            }
+            for index in (0..self.nodes.len()).rev() {
+                if let Some(parent) = self.nodes[index].parent {
+                    self.maybe_update_best_child(parent, index, justified, spec);
+                }
+            }
+        }
+    }
+
+    fn leads_to_viable_head(
+        &self,
+        index: usize,
+        justified: &JustifiedCheckpoint,
+        spec: &ChainSpec,
+    ) -> bool {
+        let head = self.nodes[index].best_descendant.unwrap_or(index);
+        self.nodes[head]
+            .justification
+            .map_or(false, |justification| {
+                justified.is_viable(&justification, spec)
+            })
+    }
+
+    /// Compares `child` with the present best child of `parent`, updating the best child and
+    /// best descendant of `parent` if required.
+    ///
+    /// The child with the highest weight is preferred, with the smaller root breaking ties (as in
+    /// the other fork choice implementations). A child which does not lead to a viable head is never chosen.
+    ///
+    /// Returns `true` if `child` was the best child but no longer leads to a viable head, in
+    /// which case `parent` has no best child.
+    fn maybe_update_best_child(
+        &mut self,
+        parent: usize,
+        child: usize,
+        justified: &JustifiedCheckpoint,
+        spec: &ChainSpec,
+    ) -> bool {
+        let child_is_viable = self.leads_to_viable_head(child, justified, spec);
+
+        let change_to_child = match self.nodes[parent].best_child {
+            Some(best_child) if best_child == child => {
+                if !child_is_viable {
+                    self.nodes[parent].best_child = None;
+                    self.nodes[parent].best_descendant = None;
+                    return true;
+                }
+                // The best descendant of the child may have changed.
+                true
+            }
+            Some(best_child) => {
+                let best_child_is_viable = self.leads_to_viable_head(best_child, justified, spec);
+                let (child, best_child) = (&self.nodes[child], &self.nodes[best_child]);
+
+                if child_is_viable != best_child_is_viable {
+                    child_is_viable
+                } else {
+                    child.weight > best_child.weight
+                        || (child.weight == best_child.weight && child.root < best_child.root)
+                }
+            }
+            None => child_is_viable,
+        };
+
+        if change_to_child {
+            let best_descendant = self.nodes[child].best_descendant.unwrap_or(child);
+            self.nodes[parent].best_child = Some(child);
+            self.nodes[parent].best_descendant = Some(best_descendant);
+        }
+
+        false
+    }
+
+    /// Returns the index of the head found by following the heaviest child from `index`, without
+    /// regard for the justified checkpoint.
+    ///
+    /// Used only when no head is viable for the justified checkpoint, so the children of each
+    /// node are found by a scan of the array.
+    fn heaviest_descendant(&self, mut index: usize) -> usize {
+        loop {
+            let best_child = (index + 1..self.nodes.len())
+                .filter(|child| self.nodes[*child].parent == Some(index))
+                .max_by(|a, b| {
+                    let (a, b) = (&self.nodes[*a], &self.nodes[*b]);
+                    // Reversed so that the smaller root breaks ties.
+                    a.weight.cmp(&b.weight).then_with(|| b.root.cmp(&a.root))
+                });
+
+            match best_child {
+                Some(child) => index = child,
+                None => return index,
+            }
+        }
+    }
+
+    /// Removes the weight of any vote for a block in `pruned` from the node weights.
+    fn remove_votes_for(&mut self, pruned: &HashSet<&Hash256>) -> Result<(), ForkChoiceError> {
+        let mut deltas = vec![0i64; self.nodes.len()];
+
+        for (validator_index, vote) in self.votes.iter_mut().enumerate() {
+            if pruned.contains(&vote.current_root) {
+                if let Some(index) = self.indices.get(&vote.current_root) {
+                    deltas[*index] -=
+                        self.balances.get(validator_index).cloned().unwrap_or(0) as i64;
+                }
+                vote.current_root = Hash256::zero();
+            }
+            if pruned.contains(&vote.next_root) {
+                vote.next_root = vote.current_root;
+            }
+        }
+
+        self.apply_score_changes(deltas)
+    }
+}
+
+impl<T: ClientDB + Sized> ForkChoice for ProtoArrayLMDGhost<T> {
+    fn add_block(
+        &mut self,
+        block: &BeaconBlock,
+        block_hash: &Hash256,
+        justification: Justification,
+        _: &ChainSpec,
+    ) -> Result<(), ForkChoiceError> {
+        if self.indices.contains_key(block_hash) {
+            return Ok(());
+        }
+
+        let parent = self.get_or_insert_index(&block.previous_block_root)?;
+        self.insert_node(*block_hash, Some(justification), Some(parent));
+        Ok(())
+    }
+
+    fn add_vote(
+        &mut self,
+        validator_index: u64,
+        target_block_root: &Hash256,
+        epoch: Epoch,
+        _: &ChainSpec,
+    ) -> Result<(), ForkChoiceError> {
+        trace!(
+            "Adding attestation of validator: {:?} for block: {}",
+            validator_index,
+            target_block_root
+        );
+        let validator_index = validator_index as usize;
+        if validator_index >= self.votes.len() {
+            self.votes
+                .resize(validator_index + 1, VoteTracker::default());
+        }
+
+        // update the vote only if it is from a later epoch. Votes for unknown blocks are applied
+        // by `find_head` once the block is added.
+        let vote = &mut self.votes[validator_index];
+        if vote.next_root == Hash256::zero() || epoch > vote.next_epoch {
+            vote.next_root = *target_block_root;
+            vote.next_epoch = epoch;
+        }
+        Ok(())
+    }
+
+    /// Perform lmd_ghost on the current chain to find the head.
+    fn find_head(
+        &mut self,
+        justified: &JustifiedCheckpoint,
+        spec: &ChainSpec,
+    ) -> Result<Hash256, ForkChoiceError> {
+        debug!(
+            "Starting proto-array fork choice at block: {}",
+            justified.root
+        );
+        let justified_index = self.get_or_insert_index(&justified.root)?;
+
+        let deltas = self.compute_deltas(&justified.balances);
+        self.apply_score_changes(deltas)?;
+        self.balances = justified.balances.clone();
+
+        self.update_best_descendants(justified, spec);
+
+        let head_index = match self.nodes[justified_index].best_descendant {
+            Some(head_index) => head_index,
+            // no branch agrees with the justified checkpoint, consider them all
+            None => self.heaviest_descendant(justified_index),
+        };
+        let head = self.nodes[head_index].root;
+
+        debug!("Head found: {}", head);
+        Ok(head)
+    }
+
+    fn prune(
+        &mut self,
+        block_hashes: &[Hash256],
+        finalized_epoch: Epoch,
+    ) -> Result<(), ForkChoiceError> {
+        let pruned: HashSet<&Hash256> = block_hashes.iter().collect();
+
+        self.remove_votes_for(&pruned)?;
+
+        // Each validator has at most one vote for an unknown block, which can never be applied if
+        // it is from before finalization.
+        for vote in self.votes.iter_mut() {
+            if vote.next_epoch < finalized_epoch && !self.indices.contains_key(&vote.next_root) {
+                vote.next_root = vote.current_root;
+            }
+        }
+
+        let old_nodes = std::mem::replace(&mut self.nodes, vec![]);
+        self.indices.clear();
+        for node in old_nodes.iter() {
+            if pruned.contains(&node.root) {
+                continue;
+            }
+            // Parents are visited first, so a surviving parent has already been re-indexed.
+            let parent = node
+                .parent
+                .and_then(|parent| self.indices.get(&old_nodes[parent].root).cloned());
+            // The best child and descendant are recomputed by the next `find_head`.
+            let index = self.insert_node(node.root, node.justification, parent);
+            self.nodes[index].weight = node.weight;
+        }
+
+        debug!("Pruned {} blocks from fork choice", pruned.len());
+        Ok(())
+    }
+
+    fn block_tree(&self, justified: &JustifiedCheckpoint) -> Result<BlockTree, ForkChoiceError> {
+        let mut children: HashMap<Hash256, Vec<Hash256>> = HashMap::new();
+        for node in &self.nodes {
+            if let Some(parent) = node.parent {
+                children
+                    .entry(self.nodes[parent].root)
+                    .or_insert_with(|| vec![])
+                    .push(node.root);
+            }
+        }
+
+        // The latest vote, which may not yet be included in the node weights.
+        let latest_vote = |validator_index: u64| {
+            self.votes
+                .get(validator_index as usize)
+                .map(|vote| {
+                    if self.indices.contains_key(&vote.next_root) {
+                        vote.next_root
+                    } else {
+                        vote.current_root
+                    }
+                })
+                .filter(|root| *root != Hash256::zero())
+        };
+
+        BlockTree::from_children(justified, &children, latest_vote, &self.block_store)
+    }
+}
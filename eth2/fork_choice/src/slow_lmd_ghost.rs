@@ -1,80 +1,82 @@
 extern crate db;
 
-use crate::{ForkChoice, ForkChoiceError};
-use db::{
-    stores::{BeaconBlockStore, BeaconStateStore},
-    ClientDB,
+use crate::justification::viable_blocks;
+use crate::{
+    BlockTree, ForkChoice, ForkChoiceError, Justification, JustifiedCheckpoint, LatestMessages,
 };
+use db::{stores::BeaconBlockStore, ClientDB};
 use log::{debug, trace};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use types::{BeaconBlock, ChainSpec, Hash256, Slot};
+use types::{BeaconBlock, ChainSpec, Epoch, Hash256, Slot};
 
 //TODO: Pruning and syncing
 
 pub struct SlowLMDGhost<T: ClientDB + Sized> {
-    /// The latest attestation targets as a map of validator index to block hash.
-    //TODO: Could this be a fixed size vec
-    latest_attestation_targets: HashMap<u64, Hash256>,
+    /// The latest attestation targets of each validator.
+    latest_messages: LatestMessages,
     /// Stores the children for any given parent.
     children: HashMap<Hash256, Vec<Hash256>>,
+    /// The justification of the post-state of each block.
+    justifications: HashMap<Hash256, Justification>,
     /// Block storage access.
     block_store: Arc<BeaconBlockStore<T>>,
-    /// State storage access.
-    state_store: Arc<BeaconStateStore<T>>,
 }
 
 impl<T> SlowLMDGhost<T>
 where
     T: ClientDB + Sized,
 {
-    pub fn new(
-        block_store: Arc<BeaconBlockStore<T>>,
-        state_store: Arc<BeaconStateStore<T>>,
-    ) -> Self {
+    pub fn new(block_store: Arc<BeaconBlockStore<T>>) -> Self {
         SlowLMDGhost {
-            latest_attestation_targets: HashMap::new(),
+            latest_messages: LatestMessages::new(),
             children: HashMap::new(),
+            justifications: HashMap::new(),
             block_store,
-            state_store,
         }
     }
 
-    /// Finds the latest votes weighted by validator balance. Returns a hashmap of block_hash to
-    /// weighted votes.
-    pub fn get_latest_votes(
-        &self,
-        state_root: &Hash256,
-        block_slot: Slot,
-        spec: &ChainSpec,
-    ) -> Result<HashMap<Hash256, u64>, ForkChoiceError> {
-        // get latest votes
-        // Note: Votes are weighted by min(balance, MAX_DEPOSIT_AMOUNT) //
-        // FORK_CHOICE_BALANCE_INCREMENT
+    /// Finds the latest votes weighted by the validator `balances`. Returns a hashmap of
+    /// block_hash to weighted votes.
+    pub fn get_latest_votes(&self, balances: &[u64]) -> HashMap<Hash256, u64> {
         // build a hashmap of block_hash to weighted votes
         let mut latest_votes: HashMap<Hash256, u64> = HashMap::new();
-        // gets the current weighted votes
-        let current_state = self
-            .state_store
-            .get_deserialized(&state_root)?
-            .ok_or_else(|| ForkChoiceError::MissingBeaconState(*state_root))?;
-
-        let active_validator_indices =
-            current_state.get_active_validator_indices(block_slot.epoch(spec.slots_per_epoch));
-
-        for index in active_validator_indices {
-            let balance = std::cmp::min(
-                current_state.validator_balances[index],
-                spec.max_deposit_amount,
-            ) / spec.fork_choice_balance_increment;
-            if balance > 0 {
-                if let Some(target) = self.latest_attestation_targets.get(&(index as u64)) {
+        for (index, balance) in balances.iter().enumerate() {
+            if *balance > 0 {
+                if let Some(target) = self.latest_messages.get(index as u64) {
                     *latest_votes.entry(*target).or_insert_with(|| 0) += balance;
                 }
             }
         }
         trace!("Latest votes: {:?}", latest_votes);
-        Ok(latest_votes)
+        latest_votes
+    }
+
+    /// Walks back from `head_root` to find the root of its ancestor at `slot`.
+    ///
+    /// Returns `None` if there is no block at `slot` in the chain of `head_root` (e.g., the slot
+    /// was skipped or is later than `head_root`).
+    fn block_root_at_slot(
+        &self,
+        head_root: Hash256,
+        slot: Slot,
+    ) -> Result<Option<Hash256>, ForkChoiceError> {
+        let mut block_root = head_root;
+
+        loop {
+            let block = self
+                .block_store
+                .get_deserialized(&block_root)?
+                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(block_root))?;
+
+            if block.slot == slot {
+                return Ok(Some(block_root));
+            } else if block.slot < slot {
+                return Ok(None);
+            }
+
+            block_root = block.previous_block_root;
+        }
     }
 
     /// Get the total number of votes for some given block root.
@@ -93,9 +95,8 @@ where
             .slot;
 
         for (vote_hash, votes) in latest_votes.iter() {
-            let (root_at_slot, _) = self
-                .block_store
-                .block_at_slot(&vote_hash, block_slot)?
+            let root_at_slot = self
+                .block_root_at_slot(*vote_hash, block_slot)?
                 .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*block_root))?;
             if root_at_slot == *block_root {
                 count += votes;
@@ -111,6 +112,7 @@ impl<T: ClientDB + Sized> ForkChoice for SlowLMDGhost<T> {
         &mut self,
         block: &BeaconBlock,
         block_hash: &Hash256,
+        justification: Justification,
         _: &ChainSpec,
     ) -> Result<(), ForkChoiceError> {
         // build the children hashmap
@@ -121,50 +123,33 @@ impl<T: ClientDB + Sized> ForkChoice for SlowLMDGhost<T> {
             .or_insert_with(|| vec![]))
         .push(block_hash.clone());
 
+        self.justifications.insert(*block_hash, justification);
+        // apply any votes which arrived before the block
+        self.latest_messages.dequeue(block_hash);
+
         // complete
         Ok(())
     }
 
-    fn add_attestation(
+    fn add_vote(
         &mut self,
         validator_index: u64,
         target_block_root: &Hash256,
-        spec: &ChainSpec,
+        epoch: Epoch,
+        _: &ChainSpec,
     ) -> Result<(), ForkChoiceError> {
-        // simply add the attestation to the latest_attestation_target if the block_height is
-        // larger
         trace!(
             "Adding attestation of validator: {:?} for block: {}",
             validator_index,
             target_block_root
         );
-        let attestation_target = self
-            .latest_attestation_targets
-            .entry(validator_index)
-            .or_insert_with(|| *target_block_root);
-        // if we already have a value
-        if attestation_target != target_block_root {
-            trace!("Old attestation found: {:?}", attestation_target);
-            // get the height of the target block
-            let block_height = self
-                .block_store
-                .get_deserialized(&target_block_root)?
-                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*target_block_root))?
-                .slot
-                .height(spec.genesis_slot);
-
-            // get the height of the past target block
-            let past_block_height = self
-                .block_store
-                .get_deserialized(&attestation_target)?
-                .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*attestation_target))?
-                .slot
-                .height(spec.genesis_slot);
-            // update the attestation only if the new target is higher
-            if past_block_height < block_height {
-                trace!("Updating old attestation");
-                *attestation_target = *target_block_root;
-            }
+        // the vote is applied when the block is added if the block is not yet known
+        if self.block_store.exists(target_block_root)? {
+            self.latest_messages
+                .insert(validator_index, *target_block_root, epoch);
+        } else {
+            self.latest_messages
+                .queue(validator_index, *target_block_root, epoch);
         }
         Ok(())
     }
@@ -172,29 +157,38 @@ impl<T: ClientDB + Sized> ForkChoice for SlowLMDGhost<T> {
     /// A very inefficient implementation of LMD ghost.
     fn find_head(
         &mut self,
-        justified_block_start: &Hash256,
+        justified: &JustifiedCheckpoint,
         spec: &ChainSpec,
     ) -> Result<Hash256, ForkChoiceError> {
         debug!("Running LMD Ghost Fork-choice rule");
-        let start = self
-            .block_store
-            .get_deserialized(&justified_block_start)?
-            .ok_or_else(|| ForkChoiceError::MissingBeaconBlock(*justified_block_start))?;
+        let latest_votes = self.get_latest_votes(&justified.balances);
 
-        let start_state_root = start.state_root;
+        let mut head_hash = justified.root;
 
-        let latest_votes = self.get_latest_votes(&start_state_root, start.slot, spec)?;
-
-        let mut head_hash = *justified_block_start;
+        // only descend into branches which agree with the justified checkpoint
+        let viable = viable_blocks(
+            head_hash,
+            &self.children,
+            &self.justifications,
+            justified,
+            spec,
+        );
 
         loop {
             debug!("Iteration for block: {}", head_hash);
 
-            let children = match self.children.get(&head_hash) {
-                Some(children) => children,
-                // we have found the head, exit
-                None => break,
+            let children: Vec<Hash256> = match self.children.get(&head_hash) {
+                Some(children) => children
+                    .iter()
+                    .cloned()
+                    .filter(|child| viable.as_ref().map_or(true, |v| v.contains(child)))
+                    .collect(),
+                None => vec![],
             };
+            // we have found the head, exit
+            if children.is_empty() {
+                break;
+            }
 
             // if we only have one child, use it
             if children.len() == 1 {
@@ -206,7 +200,7 @@ impl<T: ClientDB + Sized> ForkChoice for SlowLMDGhost<T> {
 
             let mut head_vote_count = 0;
             head_hash = children[0];
-            for child_hash in children {
+            for child_hash in &children {
                 let vote_count = self.get_vote_count(&latest_votes, &child_hash)?;
                 trace!("Vote count for child: {} is: {}", child_hash, vote_count);
 
@@ -222,4 +216,32 @@ impl<T: ClientDB + Sized> ForkChoice for SlowLMDGhost<T> {
         }
         Ok(head_hash)
     }
+
+    fn prune(
+        &mut self,
+        block_hashes: &[Hash256],
+        finalized_epoch: Epoch,
+    ) -> Result<(), ForkChoiceError> {
+        let pruned: HashSet<&Hash256> = block_hashes.iter().collect();
+
+        // `find_head` expects every entry in `children` to be non-empty.
+        self.children.retain(|parent, children| {
+            children.retain(|child| !pruned.contains(child));
+            !pruned.contains(parent) && !children.is_empty()
+        });
+        self.latest_messages.prune(&pruned, finalized_epoch);
+        self.justifications
+            .retain(|block_hash, _| !pruned.contains(block_hash));
+
+        Ok(())
+    }
+
+    fn block_tree(&self, justified: &JustifiedCheckpoint) -> Result<BlockTree, ForkChoiceError> {
+        BlockTree::from_children(
+            justified,
+            &self.children,
+            |validator_index| self.latest_messages.get(validator_index).cloned(),
+            &self.block_store,
+        )
+    }
 }
@@ -13,19 +13,20 @@ extern crate types;
 extern crate yaml_rust;
 
 pub use beacon_chain::BeaconChain;
-use bls::Signature;
-use db::stores::{BeaconBlockStore, BeaconStateStore};
+use bls::{AggregateSignature, Signature};
+use db::stores::BeaconBlockStore;
 use db::MemoryDB;
 // use env_logger::{Builder, Env};
-use fork_choice::{
-    BitwiseLMDGhost, ForkChoice, ForkChoiceAlgorithm, LongestChain, OptimizedLMDGhost, SlowLMDGhost,
-};
+use fork_choice::{ForkChoice, ForkChoiceAlgorithm, Justification, JustifiedCheckpoint};
 use ssz::ssz_encode;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::{fs::File, io::prelude::*, path::PathBuf};
 use types::test_utils::TestingBeaconStateBuilder;
-use types::{BeaconBlock, BeaconBlockBody, ChainSpec, Eth1Data, Hash256, Keypair, Slot};
+use types::{
+    Attestation, AttestationData, BeaconBlock, BeaconBlockBody, BeaconState, Bitfield, ChainSpec,
+    Eth1Data, Hash256, Keypair, Slot,
+};
 use yaml_rust::yaml;
 
 // Note: We Assume the block Id's are hex-encoded.
@@ -54,6 +55,15 @@ fn test_bitwise_lmd_ghost() {
     );
 }
 
+#[test]
+fn test_proto_array_lmd_ghost() {
+    test_yaml_vectors(
+        ForkChoiceAlgorithm::ProtoArrayLMDGhost,
+        "tests/lmd_ghost_test_vectors.yaml",
+        100,
+    );
+}
+
 #[test]
 fn test_slow_lmd_ghost() {
     test_yaml_vectors(
@@ -72,6 +82,236 @@ fn test_longest_chain() {
     );
 }
 
+#[test]
+fn test_attestations_for_unknown_blocks_are_queued() {
+    for fork_choice_algo in &[
+        ForkChoiceAlgorithm::OptimizedLMDGhost,
+        ForkChoiceAlgorithm::BitwiseLMDGhost,
+        ForkChoiceAlgorithm::SlowLMDGhost,
+        ForkChoiceAlgorithm::ProtoArrayLMDGhost,
+    ] {
+        let spec = ChainSpec::foundation();
+        let (mut fork_choice, block_store, state) = setup_inital_state(fork_choice_algo, 100);
+
+        let mut genesis_block = BeaconBlock::empty(&spec);
+        genesis_block.state_root = state.canonical_root();
+        let genesis_root = id_to_hash(&"b0".to_string());
+        block_store
+            .put(&genesis_root, &ssz_encode(&genesis_block)[..])
+            .unwrap();
+        let justification = Justification::from(&state);
+        let justified = JustifiedCheckpoint::new(genesis_root, justification, &state, &spec);
+
+        let mut child_block = genesis_block.clone();
+        child_block.slot = spec.genesis_slot + 1;
+        child_block.previous_block_root = genesis_root;
+
+        let add_block = |fork_choice: &mut Box<ForkChoice>, id: &str| {
+            let block_root = id_to_hash(&id.to_string());
+            let mut block = child_block.clone();
+            // give each block a distinct body so the blocks differ.
+            block.body.eth1_data.block_hash = block_root;
+            block_store
+                .put(&block_root, &ssz_encode(&block)[..])
+                .unwrap();
+            fork_choice
+                .add_block(&block, &block_root, justification, &spec)
+                .unwrap();
+            block_root
+        };
+
+        let b1 = add_block(&mut fork_choice, "b1");
+
+        // an attestation from the whole committee of the genesis slot, for a block which has not
+        // yet been received.
+        let b2 = id_to_hash(&"b2".to_string());
+        let committee = &state
+            .get_crosslink_committees_at_slot(spec.genesis_slot, &spec)
+            .unwrap()[0];
+        let mut aggregation_bitfield = Bitfield::new();
+        for i in 0..committee.committee.len() {
+            aggregation_bitfield.set(i, true);
+        }
+        let attestation = Attestation {
+            aggregation_bitfield,
+            data: AttestationData {
+                slot: spec.genesis_slot,
+                beacon_block_root: b2,
+                shard: committee.shard,
+                ..AttestationData::default()
+            },
+            custody_bitfield: Bitfield::new(),
+            aggregate_signature: AggregateSignature::new(),
+        };
+        fork_choice
+            .add_attestation(&attestation, &state, &spec)
+            .unwrap();
+
+        assert_eq!(fork_choice.find_head(&justified, &spec).unwrap(), b1);
+
+        add_block(&mut fork_choice, "b2");
+
+        assert_eq!(
+            fork_choice.find_head(&justified, &spec).unwrap(),
+            b2,
+            "{:?} should apply the queued attestation",
+            fork_choice_algo
+        );
+    }
+}
+
+#[test]
+fn test_heads_must_agree_with_justified_checkpoint() {
+    for fork_choice_algo in &[
+        ForkChoiceAlgorithm::OptimizedLMDGhost,
+        ForkChoiceAlgorithm::BitwiseLMDGhost,
+        ForkChoiceAlgorithm::SlowLMDGhost,
+        ForkChoiceAlgorithm::ProtoArrayLMDGhost,
+    ] {
+        let spec = ChainSpec::foundation();
+        let (mut fork_choice, block_store, state) = setup_inital_state(fork_choice_algo, 100);
+
+        let mut genesis_block = BeaconBlock::empty(&spec);
+        genesis_block.state_root = state.canonical_root();
+        let genesis_root = id_to_hash(&"b0".to_string());
+        block_store
+            .put(&genesis_root, &ssz_encode(&genesis_block)[..])
+            .unwrap();
+
+        // b1 has justified a later epoch than b2.
+        let genesis_justification = Justification::from(&state);
+        let later_justification = Justification {
+            justified_epoch: spec.genesis_epoch + 1,
+            ..genesis_justification
+        };
+
+        let mut add_block = |id: &str, justification: Justification| {
+            let block_root = id_to_hash(&id.to_string());
+            let mut block = genesis_block.clone();
+            block.slot = spec.genesis_slot + 1;
+            block.previous_block_root = genesis_root;
+            block.body.eth1_data.block_hash = block_root;
+            block_store
+                .put(&block_root, &ssz_encode(&block)[..])
+                .unwrap();
+            fork_choice
+                .add_block(&block, &block_root, justification, &spec)
+                .unwrap();
+            block_root
+        };
+        let b1 = add_block("b1", later_justification);
+        let b2 = add_block("b2", genesis_justification);
+
+        // b2 has the most votes.
+        for validator_index in 0..10 {
+            let block_root = if validator_index < 3 { b1 } else { b2 };
+            fork_choice
+                .add_vote(validator_index, &block_root, spec.genesis_epoch, &spec)
+                .unwrap();
+        }
+
+        let justified = JustifiedCheckpoint::new(genesis_root, later_justification, &state, &spec);
+        assert_eq!(
+            fork_choice.find_head(&justified, &spec).unwrap(),
+            b1,
+            "{:?} should only choose a head which agrees with the justified checkpoint",
+            fork_choice_algo
+        );
+
+        // no head agrees with this checkpoint, so all branches are considered.
+        let justified = JustifiedCheckpoint::new(
+            genesis_root,
+            Justification {
+                justified_epoch: spec.genesis_epoch + 2,
+                ..genesis_justification
+            },
+            &state,
+            &spec,
+        );
+        assert_eq!(
+            fork_choice.find_head(&justified, &spec).unwrap(),
+            b2,
+            "{:?} should fall back to all heads",
+            fork_choice_algo
+        );
+    }
+}
+
+#[test]
+fn test_block_tree() {
+    for fork_choice_algo in &[
+        ForkChoiceAlgorithm::OptimizedLMDGhost,
+        ForkChoiceAlgorithm::BitwiseLMDGhost,
+        ForkChoiceAlgorithm::SlowLMDGhost,
+        ForkChoiceAlgorithm::ProtoArrayLMDGhost,
+    ] {
+        let spec = ChainSpec::foundation();
+        let (mut fork_choice, block_store, state) = setup_inital_state(fork_choice_algo, 100);
+
+        let mut genesis_block = BeaconBlock::empty(&spec);
+        genesis_block.state_root = state.canonical_root();
+        let genesis_root = id_to_hash(&"b0".to_string());
+        block_store
+            .put(&genesis_root, &ssz_encode(&genesis_block)[..])
+            .unwrap();
+        let justification = Justification::from(&state);
+
+        // b0 <- b1 <- b3
+        //    <- b2
+        let mut add_block = |id: &str, parent: Hash256| {
+            let block_root = id_to_hash(&id.to_string());
+            let mut block = genesis_block.clone();
+            block.slot = spec.genesis_slot + if parent == genesis_root { 1 } else { 2 };
+            block.previous_block_root = parent;
+            block.body.eth1_data.block_hash = block_root;
+            block_store
+                .put(&block_root, &ssz_encode(&block)[..])
+                .unwrap();
+            fork_choice
+                .add_block(&block, &block_root, justification, &spec)
+                .unwrap();
+            block_root
+        };
+        let b1 = add_block("b1", genesis_root);
+        let b2 = add_block("b2", genesis_root);
+        let b3 = add_block("b3", b1);
+
+        for (validator_index, block_root) in [b3, b1, b2].iter().enumerate() {
+            fork_choice
+                .add_vote(
+                    validator_index as u64,
+                    block_root,
+                    spec.genesis_epoch,
+                    &spec,
+                )
+                .unwrap();
+        }
+
+        let justified = JustifiedCheckpoint::new(genesis_root, justification, &state, &spec);
+        fork_choice.find_head(&justified, &spec).unwrap();
+        let tree = fork_choice.block_tree(&justified).unwrap();
+
+        let balance = justified.balances[0];
+        let node = |root: Hash256| {
+            tree.nodes
+                .iter()
+                .find(|node| node.root == root)
+                .unwrap_or_else(|| panic!("{:?} should include {}", fork_choice_algo, root))
+        };
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(tree.nodes[0].root, genesis_root);
+        assert_eq!(node(genesis_root).weight, 3 * balance);
+        assert_eq!(node(b1).weight, 2 * balance);
+        assert_eq!(node(b1).voters, vec![1]);
+        assert_eq!(node(b1).parent, Some(genesis_root));
+        assert_eq!(node(b2).weight, balance);
+        assert_eq!(node(b3).weight, balance);
+        assert_eq!(node(b3).slot, spec.genesis_slot + 2);
+
+        assert_eq!(tree.to_dot().matches(" -> ").count(), 3);
+    }
+}
+
 // run a generic test over given YAML test vectors
 fn test_yaml_vectors(
     fork_choice_algo: ForkChoiceAlgorithm,
@@ -104,8 +344,10 @@ fn test_yaml_vectors(
     // process the tests
     for test_case in test_cases {
         // setup a fresh test
-        let (mut fork_choice, block_store, state_root) =
+        let (mut fork_choice, block_store, state) =
             setup_inital_state(&fork_choice_algo, emulated_validators);
+        let state_root = state.canonical_root();
+        let justification = Justification::from(&state);
 
         // keep a hashmap of block_id's to block_hashes (random hashes to abstract block_id)
         //let mut block_id_map: HashMap<String, Hash256> = HashMap::new();
@@ -154,7 +396,7 @@ fn test_yaml_vectors(
             // run add block for fork choice if not genesis
             if parent_id != block_id {
                 fork_choice
-                    .add_block(&beacon_block, &block_hash, &spec)
+                    .add_block(&beacon_block, &block_hash, justification, &spec)
                     .unwrap();
             }
         }
@@ -175,7 +417,12 @@ fn test_yaml_vectors(
                         "Not enough validators to emulate weights"
                     );
                     fork_choice
-                        .add_attestation(current_validator as u64, &block_root, &spec)
+                        .add_vote(
+                            current_validator as u64,
+                            &block_root,
+                            spec.genesis_epoch,
+                            &spec,
+                        )
                         .unwrap();
                     current_validator += 1;
                 }
@@ -183,9 +430,9 @@ fn test_yaml_vectors(
         }
 
         // everything is set up, run the fork choice, using genesis as the head
-        let head = fork_choice
-            .find_head(&genesis_hash.unwrap(), &spec)
-            .unwrap();
+        let justified =
+            JustifiedCheckpoint::new(genesis_hash.unwrap(), justification, &state, &spec);
+        let head = fork_choice.find_head(&justified, &spec).unwrap();
 
         // compare the result to the expected test
         let success = test_case["heads"]
@@ -220,26 +467,16 @@ fn load_test_cases_from_yaml(file_path: &str) -> Vec<yaml_rust::Yaml> {
 fn setup_inital_state(
     fork_choice_algo: &ForkChoiceAlgorithm,
     num_validators: usize,
-) -> (Box<ForkChoice>, Arc<BeaconBlockStore<MemoryDB>>, Hash256) {
+) -> (
+    Box<ForkChoice>,
+    Arc<BeaconBlockStore<MemoryDB>>,
+    BeaconState,
+) {
     let db = Arc::new(MemoryDB::open());
     let block_store = Arc::new(BeaconBlockStore::new(db.clone()));
-    let state_store = Arc::new(BeaconStateStore::new(db.clone()));
 
     // the fork choice instantiation
-    let fork_choice: Box<ForkChoice> = match fork_choice_algo {
-        ForkChoiceAlgorithm::OptimizedLMDGhost => Box::new(OptimizedLMDGhost::new(
-            block_store.clone(),
-            state_store.clone(),
-        )),
-        ForkChoiceAlgorithm::BitwiseLMDGhost => Box::new(BitwiseLMDGhost::new(
-            block_store.clone(),
-            state_store.clone(),
-        )),
-        ForkChoiceAlgorithm::SlowLMDGhost => {
-            Box::new(SlowLMDGhost::new(block_store.clone(), state_store.clone()))
-        }
-        ForkChoiceAlgorithm::LongestChain => Box::new(LongestChain::new(block_store.clone())),
-    };
+    let fork_choice = fork_choice_algo.build(block_store.clone());
 
     let spec = ChainSpec::foundation();
 
@@ -248,13 +485,8 @@ fn setup_inital_state(
     state_builder.build_caches(&spec).unwrap();
     let (state, _keypairs) = state_builder.build();
 
-    let state_root = state.canonical_root();
-    state_store
-        .put(&state_root, &ssz_encode(&state)[..])
-        .unwrap();
-
     // return initialised vars
-    (fork_choice, block_store, state_root)
+    (fork_choice, block_store, state)
 }
 
 // convert a block_id into a Hash256 -- assume input is hex encoded;
@@ -1,3 +1,7 @@
+mod persistence;
+
+pub use persistence::PersistedOperationPool;
+
 use int_to_bytes::int_to_bytes8;
 use itertools::Itertools;
 use parking_lot::RwLock;
@@ -597,6 +601,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn persisted_pool_round_trip() {
+        let rng = &mut XorShiftRng::from_seed([42; 16]);
+        let (spec, state) = test_state(rng);
+        let op_pool = OperationPool::new();
+
+        for deposit in dummy_deposits(rng, &state, &spec, 0, 3) {
+            assert_eq!(op_pool.insert_deposit(deposit, &state, &spec), Ok(Fresh));
+        }
+
+        let persisted = PersistedOperationPool::from_operation_pool(&op_pool);
+        let bytes = ssz::ssz_encode(&persisted);
+        let decoded = ssz::decode::<PersistedOperationPool>(&bytes).unwrap();
+        assert_eq!(decoded, persisted);
+
+        let restored = decoded.into_operation_pool(&state, &spec);
+        assert_eq!(restored.num_deposits(), op_pool.num_deposits());
+        assert_eq!(
+            restored.get_deposits(&state, &spec),
+            op_pool.get_deposits(&state, &spec)
+        );
+    }
+
     #[test]
     fn prune_deposits() {
         let rng = &mut XorShiftRng::from_seed([42; 16]);
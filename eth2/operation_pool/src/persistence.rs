@@ -0,0 +1,104 @@
+use crate::{AttestationId, OperationPool};
+use parking_lot::RwLock;
+use ssz_derive::{Decode, Encode};
+use std::collections::HashMap;
+use types::*;
+
+/// An `OperationPool` in a form which may be SSZ-encoded and written to the database, so the
+/// operations gathered by a node are not lost when it restarts.
+///
+/// The keys of the pool's maps are not stored. They are recomputed from each operation (and the
+/// state, for attestations and attester slashings) when the pool is restored.
+#[derive(Debug, PartialEq, Clone, Encode, Decode)]
+pub struct PersistedOperationPool {
+    attestations: Vec<Attestation>,
+    deposits: Vec<Deposit>,
+    attester_slashings: Vec<AttesterSlashing>,
+    proposer_slashings: Vec<ProposerSlashing>,
+    voluntary_exits: Vec<VoluntaryExit>,
+    transfers: Vec<Transfer>,
+}
+
+impl PersistedOperationPool {
+    /// Copies every operation in `operation_pool`.
+    pub fn from_operation_pool(operation_pool: &OperationPool) -> Self {
+        Self {
+            attestations: operation_pool
+                .attestations
+                .read()
+                .values()
+                .flatten()
+                .cloned()
+                .collect(),
+            deposits: operation_pool.deposits.read().values().cloned().collect(),
+            attester_slashings: operation_pool
+                .attester_slashings
+                .read()
+                .values()
+                .cloned()
+                .collect(),
+            proposer_slashings: operation_pool
+                .proposer_slashings
+                .read()
+                .values()
+                .cloned()
+                .collect(),
+            voluntary_exits: operation_pool
+                .voluntary_exits
+                .read()
+                .values()
+                .cloned()
+                .collect(),
+            transfers: operation_pool.transfers.read().iter().cloned().collect(),
+        }
+    }
+
+    /// Rebuilds the `OperationPool`, keying attestations and attester slashings with the fork of
+    /// `state`.
+    ///
+    /// Operations are not validated again; as when they are first inserted, invalid operations
+    /// are filtered out when a block is produced and removed when the pool is pruned.
+    pub fn into_operation_pool(self, state: &BeaconState, spec: &ChainSpec) -> OperationPool {
+        let mut attestations = HashMap::new();
+        for attestation in self.attestations {
+            attestations
+                .entry(AttestationId::from_data(&attestation.data, state, spec))
+                .or_insert_with(Vec::new)
+                .push(attestation);
+        }
+
+        OperationPool {
+            attestations: RwLock::new(attestations),
+            deposits: RwLock::new(
+                self.deposits
+                    .into_iter()
+                    .map(|deposit| (deposit.index, deposit))
+                    .collect(),
+            ),
+            attester_slashings: RwLock::new(
+                self.attester_slashings
+                    .into_iter()
+                    .map(|slashing| {
+                        (
+                            OperationPool::attester_slashing_id(&slashing, state, spec),
+                            slashing,
+                        )
+                    })
+                    .collect(),
+            ),
+            proposer_slashings: RwLock::new(
+                self.proposer_slashings
+                    .into_iter()
+                    .map(|slashing| (slashing.proposer_index, slashing))
+                    .collect(),
+            ),
+            voluntary_exits: RwLock::new(
+                self.voluntary_exits
+                    .into_iter()
+                    .map(|exit| (exit.validator_index, exit))
+                    .collect(),
+            ),
+            transfers: RwLock::new(self.transfers.into_iter().collect()),
+        }
+    }
+}
@@ -13,7 +13,9 @@ pub use validate_attestation::{
     validate_attestation, validate_attestation_time_independent_only,
     validate_attestation_without_signature,
 };
-pub use verify_deposit::{get_existing_validator_index, verify_deposit, verify_deposit_index};
+pub use verify_deposit::{
+    get_existing_validator_index, get_serialized_deposit_data, verify_deposit, verify_deposit_index,
+};
 pub use verify_exit::{verify_exit, verify_exit_time_independent_only};
 pub use verify_slashable_attestation::verify_slashable_attestation;
 pub use verify_transfer::{
@@ -114,7 +114,7 @@ struct SerializedDepositData {
 /// merkle proof.
 ///
 /// Spec v0.5.1
-fn get_serialized_deposit_data(deposit: &Deposit) -> Vec<u8> {
+pub fn get_serialized_deposit_data(deposit: &Deposit) -> Vec<u8> {
     let serialized_deposit_data = SerializedDepositData {
         amount: deposit.deposit_data.amount,
         timestamp: deposit.deposit_data.timestamp,